@@ -0,0 +1,506 @@
+use crate::ai::provider::{AIProvider, ChangeAnalysis, FileAnalysis, FileDiff, IntentionSuggestion, ReviewerSuggestion};
+use crate::core::commit::FlowCommit;
+use crate::core::intention::{Intention, MAX_GOAL_LEN};
+use crate::core::objects::ChangeType;
+use crate::error::{FlowError, Result};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+const MAX_REVIEWERS: usize = 3;
+
+/// Words that may precede the keyword of a function definition.
+const MODIFIERS: &[&str] = &[
+    "pub", "pub(crate)", "pub(super)", "async", "unsafe", "const", "extern", "export", "default",
+    "static", "public", "private", "protected", "final", "abstract", "override",
+];
+
+const FUNCTION_KEYWORDS: &[&str] = &["fn", "def", "function", "func"];
+
+const IMPORT_PREFIXES: &[&str] = &["use ", "pub use ", "import ", "from ", "#include", "require "];
+
+const COMMENT_PREFIXES: &[&str] = &["//", "#", "/*", "*", "--", "<!--"];
+
+/// Comment words that mark a change as a fix.
+const FIX_WORDS: &[&str] = &["fix", "fixes", "fixed", "bug", "regression", "workaround"];
+
+/// Offline provider: heuristics over the diff, no network and no model.
+/// The same changes always get the same answers.
+pub struct LocalProvider;
+
+impl Default for LocalProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalProvider {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    fn analyze_file(diff: &FileDiff) -> FileAnalysis {
+        let language = language_of(diff.path());
+        let is_code = language.is_some_and(|language| !matches!(language, "Markdown" | "JSON" | "TOML" | "YAML"));
+        
+        let (functions_added, functions_modified, functions_removed, imports_changed) = if is_code {
+            let added: BTreeSet<String> = diff.added.iter().filter_map(|line| function_name(line)).collect();
+            let removed: BTreeSet<String> = diff.removed.iter().filter_map(|line| function_name(line)).collect();
+            
+            let imports = diff.added.iter().filter_map(|line| import_line(line).map(|line| format!("+{}", line)))
+                .chain(diff.removed.iter().filter_map(|line| import_line(line).map(|line| format!("-{}", line))))
+                .collect::<BTreeSet<_>>();
+            
+            (
+                added.difference(&removed).cloned().collect(),
+                // A definition line on both sides: the signature changed
+                added.intersection(&removed).cloned().collect(),
+                removed.difference(&added).cloned().collect(),
+                imports.into_iter().collect(),
+            )
+        } else {
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new())
+        };
+        
+        let mut analysis = FileAnalysis {
+            path: diff.path().to_string(),
+            language: language.map(str::to_string),
+            functions_added,
+            functions_modified,
+            functions_removed,
+            imports_changed,
+            additions: diff.added.len(),
+            deletions: diff.removed.len(),
+            risk_score: 0.0,
+        };
+        analysis.risk_score = Self::risk(diff, &analysis);
+        analysis
+    }
+    
+    /// Grows with the size of the change, more so when definitions go
+    /// away; tests and docs weigh half.
+    fn risk(diff: &FileDiff, analysis: &FileAnalysis) -> f32 {
+        let lines = (analysis.additions + analysis.deletions) as f32;
+        let mut risk = lines / (lines + 200.0);
+        
+        if !analysis.functions_removed.is_empty() || !analysis.functions_modified.is_empty() {
+            risk += 0.2;
+        }
+        if diff.is_deleted() {
+            risk += 0.1;
+        }
+        if is_test_path(diff.path()) || analysis.language.as_deref() == Some("Markdown") {
+            risk *= 0.5;
+        }
+        
+        risk.clamp(0.0, 1.0)
+    }
+    
+    fn summary(files: &[FileAnalysis]) -> String {
+        let additions: usize = files.iter().map(|file| file.additions).sum();
+        let deletions: usize = files.iter().map(|file| file.deletions).sum();
+        let mut summary = format!(
+            "{} file{} changed, {} insertions(+), {} deletions(-)",
+            files.len(),
+            if files.len() == 1 { "" } else { "s" },
+            additions,
+            deletions,
+        );
+        
+        let added: Vec<&String> = files.iter().flat_map(|file| &file.functions_added).collect();
+        let removed: Vec<&String> = files.iter().flat_map(|file| &file.functions_removed).collect();
+        if !added.is_empty() {
+            summary.push_str(&format!("; adds {}", list(&added)));
+        }
+        if !removed.is_empty() {
+            summary.push_str(&format!("; removes {}", list(&removed)));
+        }
+        
+        summary
+    }
+    
+    /// What the changes have in common: one file, a shared directory, or
+    /// just their number.
+    fn subject(changes: &[FileDiff]) -> String {
+        if let [only] = changes {
+            return only.path().to_string();
+        }
+        
+        let mut common: Option<Vec<&str>> = None;
+        for diff in changes {
+            let dir: Vec<&str> = diff.path().split('/').collect();
+            let dir = &dir[..dir.len() - 1];
+            common = Some(match common {
+                None => dir.to_vec(),
+                Some(common) => common.iter().zip(dir).take_while(|(a, b)| a == b).map(|(a, _)| *a).collect(),
+            });
+        }
+        
+        match common.map(|parts| parts.join("/")).filter(|dir| !dir.is_empty()) {
+            Some(dir) => format!("{} files in {}", changes.len(), dir),
+            None => format!("{} files", changes.len()),
+        }
+    }
+}
+
+#[async_trait]
+impl AIProvider for LocalProvider {
+    fn name(&self) -> &str {
+        "local"
+    }
+    
+    /// Goal from the change types, the paths and keywords in the diff;
+    /// the newest related intention is mentioned in the context.
+    async fn suggest_intention(&self, changes: &[FileDiff], related: &[Intention]) -> Result<IntentionSuggestion> {
+        if changes.is_empty() {
+            return Err(FlowError::AIAnalysisFailed("No changes to describe".to_string()));
+        }
+        
+        let analysis = self.analyze_changes(changes).await?;
+        let subject = Self::subject(changes);
+        let added: Vec<&String> = analysis.files.iter().flat_map(|file| &file.functions_added).collect();
+        let removed: Vec<&String> = analysis.files.iter().flat_map(|file| &file.functions_removed).collect();
+        let keywords = comment_keywords(changes);
+        
+        let goal = if changes.iter().all(FileDiff::is_added) {
+            format!("Add {}", subject)
+        } else if changes.iter().all(FileDiff::is_deleted) {
+            format!("Remove {}", subject)
+        } else if changes.iter().all(|diff| diff.is_renamed() && diff.added.is_empty() && diff.removed.is_empty()) {
+            format!("Move {}", subject)
+        } else if FIX_WORDS.iter().any(|word| keywords.contains(*word)) {
+            format!("Fix {}", subject)
+        } else if !added.is_empty() {
+            format!("Add {} in {}", list(&added), subject)
+        } else if !removed.is_empty() {
+            format!("Remove {} from {}", list(&removed), subject)
+        } else if changes.iter().all(|diff| is_test_path(diff.path())) {
+            format!("Update tests in {}", subject)
+        } else {
+            format!("Update {}", subject)
+        };
+        let goal = truncate(&goal, MAX_GOAL_LEN);
+        
+        let impact = match analysis.risk_score {
+            risk if risk >= 0.5 => Some("Large change; review carefully".to_string()),
+            _ if !removed.is_empty() => Some(format!("Callers of {} need updating", list(&removed))),
+            _ => None,
+        };
+        
+        let context = match related.first() {
+            Some(previous) => format!("{}; follows \"{}\"", analysis.summary, previous.goal),
+            None => analysis.summary,
+        };
+        
+        // Heuristics guess at the shape of a change, not its purpose
+        let confidence = if changes.iter().all(|diff| diff.binary) {
+            0.3
+        } else if changes.len() == 1 {
+            0.6
+        } else {
+            0.5
+        };
+        
+        Ok(IntentionSuggestion {
+            goal,
+            context: Some(context),
+            impact,
+            confidence,
+            // Committing tags the intention with the repository's taxonomy
+            tags: Vec::new(),
+        })
+    }
+    
+    async fn analyze_changes(&self, changes: &[FileDiff]) -> Result<ChangeAnalysis> {
+        let files: Vec<FileAnalysis> = changes.iter().map(Self::analyze_file).collect();
+        let risk_score = files.iter().map(|file| file.risk_score).fold(0.0, f32::max);
+        
+        Ok(ChangeAnalysis {
+            summary: Self::summary(&files),
+            risk_score,
+            files,
+        })
+    }
+    
+    async fn suggest_reviewers(
+        &self,
+        author: &str,
+        changes: &[FileDiff],
+        history: &[FlowCommit],
+    ) -> Result<Vec<ReviewerSuggestion>> {
+        let mut paths: HashSet<&str> = HashSet::new();
+        for diff in changes {
+            paths.insert(diff.path());
+            if let ChangeType::Renamed { from } = &diff.change.change_type {
+                paths.insert(from);
+            }
+        }
+        let dirs: HashSet<&str> = paths.iter().map(|path| parent_dir(path)).collect();
+        
+        // Per author: score, plus the changed files they touched before
+        let mut candidates: BTreeMap<&str, (f32, BTreeSet<&str>)> = BTreeMap::new();
+        for commit in history {
+            let name = commit.intention.author.as_str();
+            if name == author {
+                continue;
+            }
+            
+            for change in &commit.changes {
+                let path = change.path.as_str();
+                if paths.contains(path) {
+                    let candidate = candidates.entry(name).or_default();
+                    candidate.0 += 1.0;
+                    candidate.1.insert(path);
+                } else if dirs.contains(parent_dir(path)) {
+                    candidates.entry(name).or_default().0 += 0.25;
+                }
+            }
+        }
+        
+        let best = candidates.values().map(|(score, _)| *score).fold(0.0, f32::max);
+        let mut reviewers: Vec<ReviewerSuggestion> = candidates
+            .into_iter()
+            .map(|(name, (score, files))| {
+                let files: Vec<&str> = files.into_iter().collect();
+                let reason = if files.is_empty() {
+                    "Changed files next to these".to_string()
+                } else {
+                    format!("Changed {} before", list(&files))
+                };
+                ReviewerSuggestion { name: name.to_string(), reason, score: score / best }
+            })
+            .collect();
+        
+        reviewers.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        reviewers.truncate(MAX_REVIEWERS);
+        Ok(reviewers)
+    }
+}
+
+fn language_of(path: &str) -> Option<&'static str> {
+    let extension = path.rsplit_once('.')?.1;
+    
+    Some(match extension {
+        "rs" => "Rust",
+        "py" => "Python",
+        "js" | "jsx" | "mjs" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" => "Kotlin",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "hpp" => "C++",
+        "rb" => "Ruby",
+        "sh" => "Shell",
+        "sql" => "SQL",
+        "md" => "Markdown",
+        "json" => "JSON",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        _ => return None,
+    })
+}
+
+/// Name of the function a line defines, e.g. `parse` for `pub fn parse(`
+/// or `def parse(`. Go receivers are skipped.
+fn function_name(line: &str) -> Option<String> {
+    let mut words = line.split_whitespace().skip_while(|word| MODIFIERS.contains(word));
+    
+    if !FUNCTION_KEYWORDS.contains(&words.next()?) {
+        return None;
+    }
+    
+    let mut name = words.next()?;
+    if name.starts_with('(') {
+        name = words.find(|word| word.contains(')')).and_then(|_| words.next())?;
+    }
+    
+    let name: String = name.chars().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$').collect();
+    (!name.is_empty()).then_some(name)
+}
+
+fn import_line(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let is_import = IMPORT_PREFIXES.iter().any(|prefix| line.starts_with(prefix)) || line.contains("require(");
+    is_import.then_some(line)
+}
+
+/// Lowercase words of the comments a diff adds, which often say why.
+fn comment_keywords(changes: &[FileDiff]) -> BTreeSet<String> {
+    changes.iter()
+        .flat_map(|diff| &diff.added)
+        .map(|line| line.trim())
+        .filter(|line| COMMENT_PREFIXES.iter().any(|prefix| line.starts_with(prefix)))
+        .flat_map(|line| line.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.len() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn is_test_path(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    path.split('/').any(|part| part == "tests" || part == "test")
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// `a, b, c and 2 more`
+fn list<T: AsRef<str>>(items: &[T]) -> String {
+    const SHOWN: usize = 3;
+    
+    let names: Vec<&str> = items.iter().map(AsRef::as_ref).collect();
+    match names.len() {
+        0..=SHOWN => names.join(", "),
+        n => format!("{} and {} more", names[..SHOWN].join(", "), n - SHOWN),
+    }
+}
+
+/// At most `max` bytes of `text`, cut at a character boundary.
+fn truncate(text: &str, max: usize) -> String {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::objects::{FileChange, Hash};
+    use crate::utils::diff::{diff_strings, DiffOptions};
+    
+    fn diff(change: FileChange, old: &str, new: &str) -> FileDiff {
+        FileDiff::new(change, Some(&diff_strings(old, new, &DiffOptions::default()).unwrap()))
+    }
+    
+    fn modified(path: &str, old: &str, new: &str) -> FileDiff {
+        diff(FileChange::new_modified(path.to_string(), Hash::new(new.as_bytes()), Hash::new(old.as_bytes())), old, new)
+    }
+    
+    #[tokio::test]
+    async fn test_analyze_changes() {
+        let provider = LocalProvider::new();
+        let changes = vec![
+            modified(
+                "src/parser.rs",
+                "use std::fmt;\n\nfn parse(input: &str) {}\n\nfn legacy() {}\n",
+                "use std::fmt;\nuse std::io;\n\npub fn parse(input: &str, strict: bool) {}\n\npub(crate) async fn parse_header() {}\n",
+            ),
+            modified("README.md", "# Parser\n", "# Parser\n\nfn not_code() is prose here\n"),
+        ];
+        
+        let analysis = provider.analyze_changes(&changes).await.unwrap();
+        let parser = &analysis.files[0];
+        assert_eq!(parser.language.as_deref(), Some("Rust"));
+        assert_eq!(parser.functions_added, vec!["parse_header".to_string()]);
+        assert_eq!(parser.functions_modified, vec!["parse".to_string()]);
+        assert_eq!(parser.functions_removed, vec!["legacy".to_string()]);
+        assert_eq!(parser.imports_changed, vec!["+use std::io;".to_string()]);
+        assert!(parser.risk_score > 0.2 && parser.risk_score < 1.0);
+        
+        // Docs are not searched for code
+        assert!(analysis.files[1].functions_added.is_empty());
+        assert_eq!(analysis.risk_score, parser.risk_score);
+        assert_eq!(analysis.summary, "2 files changed, 5 insertions(+), 2 deletions(-); adds parse_header; removes legacy");
+    }
+    
+    #[tokio::test]
+    async fn test_suggest_intention() {
+        let provider = LocalProvider::new();
+        
+        let added = vec![
+            diff(FileChange::new_added("src/auth/login.rs".to_string(), Hash::new(b"a")), "", "fn login() {}\n"),
+            diff(FileChange::new_added("src/auth/token.rs".to_string(), Hash::new(b"b")), "", "fn token() {}\n"),
+        ];
+        let suggestion = provider.suggest_intention(&added, &[]).await.unwrap();
+        assert_eq!(suggestion.goal, "Add 2 files in src/auth");
+        assert!(suggestion.tags.is_empty());
+        assert_eq!(suggestion.confidence, 0.5);
+        assert!(suggestion.clone().into_intention().validate().is_ok());
+        
+        // Deterministic
+        assert_eq!(provider.suggest_intention(&added, &[]).await.unwrap(), suggestion);
+        
+        let edited = vec![modified("src/lib.rs", "fn a() {}\n", "fn a() {}\nfn b() {}\n")];
+        assert_eq!(provider.suggest_intention(&edited, &[]).await.unwrap().goal, "Add b in src/lib.rs");
+        
+        let tests = vec![modified("tests/cli.rs", "let x = 1;\n", "let x = 2;\n")];
+        assert_eq!(provider.suggest_intention(&tests, &[]).await.unwrap().goal, "Update tests in tests/cli.rs");
+        
+        assert!(matches!(provider.suggest_intention(&[], &[]).await, Err(FlowError::AIAnalysisFailed(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_suggested_goal_fits_validation() {
+        let provider = LocalProvider::new();
+        let path = format!("docs/{}.md", "é".repeat(150));
+        
+        let suggestion = provider.suggest_intention(&[modified(&path, "a\n", "b\n")], &[]).await.unwrap();
+        assert!(suggestion.goal.len() <= MAX_GOAL_LEN && suggestion.goal.len() > MAX_GOAL_LEN - 2);
+        assert!(suggestion.goal.starts_with("Update docs/é"));
+        assert!(suggestion.into_intention().validate().is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_suggest_intention_uses_comments_and_history() {
+        let provider = LocalProvider::new();
+        let fix = vec![modified(
+            "src/db/pool.rs",
+            "let size = max;\n",
+            "// Fix: the pool leaked connections on timeout\nlet size = max.min(limit);\n",
+        )];
+        let related = vec![Intention::new("Add a connection pool".to_string(), None, None, 0.8)];
+        
+        let suggestion = provider.suggest_intention(&fix, &related).await.unwrap();
+        assert_eq!(suggestion.goal, "Fix src/db/pool.rs");
+        assert!(suggestion.clone().into_intention().tags.contains(&"bugfix".to_string()));
+        assert!(suggestion.context.unwrap().ends_with("; follows \"Add a connection pool\""));
+        
+        // Only comments count, not code that happens to say "fix"
+        let code = vec![modified("src/lib.rs", "let a = 1;\n", "let fix = 2;\n")];
+        assert_eq!(provider.suggest_intention(&code, &[]).await.unwrap().goal, "Update src/lib.rs");
+    }
+    
+    #[tokio::test]
+    async fn test_suggest_reviewers() {
+        let provider = LocalProvider::new();
+        let commit = |author: &str, paths: &[&str]| {
+            let mut intention = Intention::new("Work".to_string(), None, None, 0.8);
+            intention.author = author.to_string();
+            let changes = paths.iter()
+                .map(|path| FileChange::new_modified(path.to_string(), Hash::new(b"new"), Hash::new(b"old")))
+                .collect();
+            FlowCommit::new(intention, changes, Hash::new(b"tree"), vec![])
+        };
+        let history = vec![
+            commit("ana", &["src/parser.rs"]),
+            commit("ana", &["src/parser.rs", "src/lexer.rs"]),
+            commit("ben", &["src/lexer.rs"]),
+            commit("me", &["src/parser.rs"]),
+            commit("cy", &["docs/guide.md"]),
+        ];
+        let changes = vec![modified("src/parser.rs", "a\n", "b\n")];
+        
+        let reviewers = provider.suggest_reviewers("me", &changes, &history).await.unwrap();
+        let ranked: Vec<(&str, f32)> = reviewers.iter().map(|r| (r.name.as_str(), r.score)).collect();
+        // ana changed the file twice and a neighbour once; ben only the neighbour
+        assert_eq!(ranked, vec![("ana", 1.0), ("ben", 0.25 / 2.25)]);
+        assert_eq!(reviewers[0].reason, "Changed src/parser.rs before");
+    }
+    
+    #[test]
+    fn test_function_names() {
+        assert_eq!(function_name("    pub async fn load_all(repo: &Repository)"), Some("load_all".to_string()));
+        assert_eq!(function_name("def handle(self):"), Some("handle".to_string()));
+        assert_eq!(function_name("export default function render() {"), Some("render".to_string()));
+        assert_eq!(function_name("func (r *Repo) Commit(msg string) error {"), Some("Commit".to_string()));
+        assert_eq!(function_name("let fn = 3;"), None);
+        assert_eq!(function_name("// fn commented()"), None);
+    }
+}
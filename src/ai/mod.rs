@@ -0,0 +1,41 @@
+pub mod provider;
+pub mod local;
+pub mod openai;
+
+pub use local::LocalProvider;
+pub use openai::{OpenAIProvider, OpenAISettings};
+pub use provider::{AIProvider, FileDiff};
+
+use crate::error::{FlowError, Result};
+use crate::storage::config::Config;
+
+/// The provider selected by `config.ai_mode`.
+pub fn provider_for(config: &Config) -> Result<Box<dyn AIProvider>> {
+    match config.ai_mode.as_str() {
+        "local" => Ok(Box::new(LocalProvider::new())),
+        "openai" => Ok(Box::new(OpenAIProvider::new(OpenAISettings::from_config(&config.ai))?)),
+        "claude" => Err(FlowError::NotImplemented(format!("{} AI provider", config.ai_mode))),
+        other => Err(FlowError::ConfigError(format!(
+            "Unknown AI mode: {} (expected local, openai or claude)", other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_provider_selection() {
+        let config = Config::new("test-repo".to_string(), "local".to_string());
+        assert_eq!(provider_for(&config).unwrap().name(), "local");
+        
+        let mut config = Config::new("test-repo".to_string(), "openai".to_string());
+        config.ai.model = Some("my-model".to_string());
+        assert_eq!(provider_for(&config).unwrap().name(), "my-model");
+        
+        let mut config = Config::new("test-repo".to_string(), "local".to_string());
+        config.ai_mode = "psychic".to_string();
+        assert!(matches!(provider_for(&config), Err(FlowError::ConfigError(_))));
+    }
+}
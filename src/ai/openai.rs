@@ -0,0 +1,487 @@
+use crate::ai::local::LocalProvider;
+use crate::ai::provider::{AIProvider, ChangeAnalysis, FileDiff, IntentionSuggestion, ReviewerSuggestion};
+use crate::core::commit::FlowCommit;
+use crate::core::intention::Intention;
+use crate::core::objects::ChangeType;
+use crate::error::{FlowError, Result};
+use crate::storage::config::AIConfig;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+/// Consulted when the config has no API key, so the key can stay out of
+/// the repository.
+pub const API_KEY_ENV: &str = "FLOWVERSION_AI_API_KEY";
+
+/// Diff text sent to the model is cut off after this many characters.
+const MAX_DIFF_CHARS: usize = 12_000;
+
+const INTENTION_PROMPT: &str = "You write commit intentions for FlowVersion, a version control system \
+that records why code changed. Given a diff, reply with the goal of the change in the imperative mood \
+(at most 200 characters), optional context and expected impact, your confidence from 0 to 1, and a few \
+short lowercase tags.";
+
+const ANALYSIS_PROMPT: &str = "You review diffs. Reply with a short summary of what the change does \
+and a risk score from 0 (trivial) to 1 (likely to break something).";
+
+/// Connection settings of an `OpenAIProvider`.
+#[derive(Debug, Clone)]
+pub struct OpenAISettings {
+    /// API root, without the trailing `/chat/completions`
+    pub base_url: String,
+    pub model: String,
+    /// Sent as a bearer token; local servers often need none
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub retry_backoff: Duration,
+}
+
+impl OpenAISettings {
+    pub fn from_config(config: &AIConfig) -> Self {
+        Self {
+            base_url: config.base_url.clone(),
+            model: config.model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            api_key: config.api_key.clone().or_else(|| std::env::var(API_KEY_ENV).ok()),
+            timeout: Duration::from_secs(config.timeout_secs),
+            max_retries: config.max_retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: Option<String>,
+    #[serde(default)]
+    refusal: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelAnalysis {
+    summary: String,
+    risk_score: f32,
+}
+
+/// Provider for any OpenAI-compatible `chat/completions` endpoint. Replies
+/// are constrained by a JSON schema and checked before use.
+///
+/// The model writes intentions and change summaries; per-file facts and
+/// reviewer suggestions come from the diff and history, as with
+/// `LocalProvider`.
+pub struct OpenAIProvider {
+    settings: OpenAISettings,
+    client: reqwest::Client,
+    local: LocalProvider,
+}
+
+impl OpenAIProvider {
+    pub fn new(settings: OpenAISettings) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .build()?;
+        
+        Ok(Self {
+            settings,
+            client,
+            local: LocalProvider::new(),
+        })
+    }
+    
+    /// Sends one chat completion whose reply must match `schema` and
+    /// returns the reply as JSON. Timeouts, connection failures, 429 and
+    /// 5xx responses are retried with exponential backoff.
+    async fn complete(&self, system: &str, prompt: String, schema_name: &str, schema: Value) -> Result<Value> {
+        let url = format!("{}/chat/completions", self.settings.base_url.trim_end_matches('/'));
+        let body = json!({
+            "model": self.settings.model,
+            "temperature": 0,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": { "name": schema_name, "strict": true, "schema": schema },
+            },
+        });
+        
+        let mut attempt = 0;
+        let response = loop {
+            let mut request = self.client.post(&url).json(&body);
+            if let Some(key) = &self.settings.api_key {
+                request = request.bearer_auth(key);
+            }
+            
+            let result = request.send().await;
+            let retryable = match &result {
+                Ok(response) => response.status().as_u16() == 429 || response.status().is_server_error(),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+            if !retryable || attempt >= self.settings.max_retries {
+                break result?.error_for_status()?;
+            }
+            
+            let delay = self.settings.retry_backoff.saturating_mul(2u32.saturating_pow(attempt));
+            match &result {
+                Ok(response) => warn!("AI request failed with {}, retrying in {:?}", response.status(), delay),
+                Err(e) => warn!("AI request failed: {}, retrying in {:?}", e, delay),
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        
+        let completion: ChatCompletion = response.json().await.map_err(|e| {
+            if e.is_decode() {
+                FlowError::InvalidAIResponse(e.to_string())
+            } else {
+                FlowError::NetworkError(e)
+            }
+        })?;
+        let message = completion.choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| FlowError::InvalidAIResponse("No choices in the reply".to_string()))?
+            .message;
+        
+        if let Some(refusal) = message.refusal {
+            return Err(FlowError::InvalidAIResponse(format!("Model refused: {}", refusal)));
+        }
+        let content = message.content
+            .ok_or_else(|| FlowError::InvalidAIResponse("Reply has no content".to_string()))?;
+        debug!("AI reply: {}", content);
+        
+        parse_reply(&content)
+    }
+    
+    /// The diff as the model sees it, cut off after `MAX_DIFF_CHARS`.
+    fn describe_changes(changes: &[FileDiff]) -> String {
+        let mut text = String::new();
+        
+        for diff in changes {
+            let header = match &diff.change.change_type {
+                ChangeType::Added => format!("added {}", diff.path()),
+                ChangeType::Modified => format!("modified {}", diff.path()),
+                ChangeType::Deleted => format!("deleted {}", diff.path()),
+                ChangeType::Renamed { from } => format!("renamed {} -> {}", from, diff.path()),
+                ChangeType::Copied { from } => format!("copied {} -> {}", from, diff.path()),
+            };
+            text.push_str(&format!("### {}\n", header));
+            
+            if diff.binary {
+                text.push_str("(binary file)\n");
+            }
+            for line in &diff.removed {
+                text.push_str(&format!("-{}\n", line));
+            }
+            for line in &diff.added {
+                text.push_str(&format!("+{}\n", line));
+            }
+            
+            if text.len() > MAX_DIFF_CHARS {
+                let mut end = MAX_DIFF_CHARS;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+                text.push_str("\n[diff truncated]\n");
+                break;
+            }
+        }
+        
+        text
+    }
+}
+
+/// Parses the reply content, tolerating a Markdown code fence around it.
+fn parse_reply(content: &str) -> Result<Value> {
+    let content = content.trim();
+    let content = content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .and_then(|inner| inner.strip_suffix("```"))
+        .unwrap_or(content);
+    
+    serde_json::from_str(content).map_err(|e| FlowError::InvalidAIResponse(format!("Reply is not JSON: {}", e)))
+}
+
+fn check_score(name: &str, score: f32) -> Result<()> {
+    if (0.0..=1.0).contains(&score) {
+        Ok(())
+    } else {
+        Err(FlowError::InvalidAIResponse(format!("{} {} is outside 0.0-1.0", name, score)))
+    }
+}
+
+/// Schema of an `IntentionSuggestion`; strict mode wants every property
+/// listed as required, so optional ones are nullable instead. It rejects
+/// `maxLength`, so the goal's length is left to `Intention::validate`.
+fn intention_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "goal": { "type": "string" },
+            "context": { "type": ["string", "null"] },
+            "impact": { "type": ["string", "null"] },
+            "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+            "tags": { "type": "array", "items": { "type": "string" } },
+        },
+        "required": ["goal", "context", "impact", "confidence", "tags"],
+        "additionalProperties": false,
+    })
+}
+
+fn analysis_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "summary": { "type": "string" },
+            "risk_score": { "type": "number", "minimum": 0, "maximum": 1 },
+        },
+        "required": ["summary", "risk_score"],
+        "additionalProperties": false,
+    })
+}
+
+#[async_trait]
+impl AIProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        &self.settings.model
+    }
+    
+    async fn suggest_intention(&self, changes: &[FileDiff], related: &[Intention]) -> Result<IntentionSuggestion> {
+        if changes.is_empty() {
+            return Err(FlowError::AIAnalysisFailed("No changes to describe".to_string()));
+        }
+        
+        let mut prompt = String::new();
+        if !related.is_empty() {
+            prompt.push_str("Recent intentions for these files, newest first:\n");
+            for intention in related {
+                prompt.push_str(&format!("- {} [{}]\n", intention.goal, intention.tags.join(", ")));
+            }
+            prompt.push('\n');
+        }
+        prompt.push_str(&Self::describe_changes(changes));
+        
+        let reply = self.complete(INTENTION_PROMPT, prompt, "intention_suggestion", intention_schema()).await?;
+        let suggestion: IntentionSuggestion = serde_json::from_value(reply)
+            .map_err(|e| FlowError::InvalidAIResponse(format!("Unexpected intention: {}", e)))?;
+        
+        check_score("Confidence", suggestion.confidence)?;
+        suggestion.clone().into_intention().validate()
+            .map_err(|e| FlowError::InvalidAIResponse(e.to_string()))?;
+        
+        Ok(suggestion)
+    }
+    
+    async fn analyze_changes(&self, changes: &[FileDiff]) -> Result<ChangeAnalysis> {
+        let facts = self.local.analyze_changes(changes).await?;
+        
+        let reply = self.complete(ANALYSIS_PROMPT, Self::describe_changes(changes), "change_analysis", analysis_schema()).await?;
+        let analysis: ModelAnalysis = serde_json::from_value(reply)
+            .map_err(|e| FlowError::InvalidAIResponse(format!("Unexpected analysis: {}", e)))?;
+        check_score("Risk score", analysis.risk_score)?;
+        
+        Ok(ChangeAnalysis {
+            summary: analysis.summary,
+            risk_score: analysis.risk_score,
+            files: facts.files,
+        })
+    }
+    
+    async fn suggest_reviewers(
+        &self,
+        author: &str,
+        changes: &[FileDiff],
+        history: &[FlowCommit],
+    ) -> Result<Vec<ReviewerSuggestion>> {
+        self.local.suggest_reviewers(author, changes, history).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::objects::{FileChange, Hash};
+    use crate::utils::diff::{diff_strings, DiffOptions};
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    
+    fn settings(server: &MockServer) -> OpenAISettings {
+        OpenAISettings {
+            base_url: format!("{}/v1", server.uri()),
+            model: "test-model".to_string(),
+            api_key: Some("test-key".to_string()),
+            timeout: Duration::from_secs(5),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(1),
+        }
+    }
+    
+    fn reply(content: Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": content.to_string() },
+                "finish_reason": "stop",
+            }],
+        }))
+    }
+    
+    fn changes() -> Vec<FileDiff> {
+        let change = FileChange::new_modified("src/auth.rs".to_string(), Hash::new(b"new"), Hash::new(b"old"));
+        let diff = diff_strings("fn login() {}\n", "fn login(user: &str) {}\n", &DiffOptions::default()).unwrap();
+        vec![FileDiff::new(change, Some(&diff))]
+    }
+    
+    #[tokio::test]
+    async fn test_suggest_intention() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer test-key"))
+            .and(body_partial_json(json!({
+                "model": "test-model",
+                "response_format": { "type": "json_schema", "json_schema": { "name": "intention_suggestion" } },
+            })))
+            .respond_with(reply(json!({
+                "goal": "Require a user name to log in",
+                "context": null,
+                "impact": "Callers of login must pass a user",
+                "confidence": 0.9,
+                "tags": ["security"],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        
+        let provider = OpenAIProvider::new(settings(&server)).unwrap();
+        let related = vec![Intention::new("Add login".to_string(), None, None, 0.8)];
+        let suggestion = provider.suggest_intention(&changes(), &related).await.unwrap();
+        assert_eq!(suggestion.goal, "Require a user name to log in");
+        assert_eq!(suggestion.context, None);
+        assert_eq!(suggestion.confidence, 0.9);
+        
+        // The diff went into the prompt
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let prompt = body["messages"][1]["content"].as_str().unwrap();
+        assert!(prompt.starts_with("Recent intentions for these files, newest first:\n- Add login [feature, security]\n"));
+        assert!(prompt.contains("### modified src/auth.rs\n-fn login() {}\n+fn login(user: &str) {}"));
+    }
+    
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(reply(json!({ "summary": "Adds a parameter", "risk_score": 0.4 })))
+            .mount(&server)
+            .await;
+        
+        let provider = OpenAIProvider::new(settings(&server)).unwrap();
+        let analysis = provider.analyze_changes(&changes()).await.unwrap();
+        assert_eq!(analysis.summary, "Adds a parameter");
+        assert_eq!(analysis.risk_score, 0.4);
+        assert_eq!(analysis.files[0].functions_modified, vec!["login".to_string()]);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+        
+        let provider = OpenAIProvider::new(settings(&server)).unwrap();
+        assert!(matches!(provider.suggest_intention(&changes(), &[]).await, Err(FlowError::NetworkError(_))));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        
+        let provider = OpenAIProvider::new(settings(&server)).unwrap();
+        assert!(matches!(provider.suggest_intention(&changes(), &[]).await, Err(FlowError::NetworkError(_))));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(reply(json!({ "summary": "late", "risk_score": 0.1 })).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+        
+        let mut settings = settings(&server);
+        settings.timeout = Duration::from_millis(100);
+        settings.max_retries = 1;
+        
+        let provider = OpenAIProvider::new(settings).unwrap();
+        match provider.analyze_changes(&changes()).await {
+            Err(FlowError::NetworkError(e)) => assert!(e.is_timeout()),
+            other => panic!("expected a timeout, got {:?}", other.map(|analysis| analysis.summary)),
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_invalid_replies() {
+        let cases = [
+            ResponseTemplate::new(200).set_body_string("not json"),
+            ResponseTemplate::new(200).set_body_json(json!({ "choices": [] })),
+            reply(json!({ "goal": "Fix it" })),
+            reply(json!({ "goal": "Fix it", "confidence": 4.0, "tags": [] })),
+            reply(json!({ "goal": "", "confidence": 0.5, "tags": [] })),
+            ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{ "message": { "content": null, "refusal": "I can't help with that" } }],
+            })),
+        ];
+        
+        for (number, response) in cases.into_iter().enumerate() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST")).respond_with(response).mount(&server).await;
+            
+            let provider = OpenAIProvider::new(settings(&server)).unwrap();
+            let result = provider.suggest_intention(&changes(), &[]).await;
+            assert!(matches!(result, Err(FlowError::InvalidAIResponse(_))), "case {}: {:?}", number, result);
+        }
+    }
+    
+    #[test]
+    fn test_parse_reply_accepts_code_fences() {
+        let value = parse_reply("```json\n{\"summary\": \"x\", \"risk_score\": 0}\n```").unwrap();
+        assert_eq!(value["summary"], "x");
+        assert!(parse_reply("{\"a\": 1}").is_ok());
+    }
+}
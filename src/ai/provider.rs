@@ -0,0 +1,135 @@
+use crate::core::commit::FlowCommit;
+use crate::core::intention::Intention;
+use crate::core::objects::{ChangeType, FileChange};
+use crate::error::Result;
+use crate::utils::diff::{DiffLine, DiffResult};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// One changed file as providers see it: the change plus the lines it adds
+/// and removes. Binary files carry no lines.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub change: FileChange,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub binary: bool,
+}
+
+impl FileDiff {
+    /// `diff` is the diff of `change`, `None` for a binary file.
+    pub fn new(change: FileChange, diff: Option<&DiffResult>) -> Self {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for line in diff.map(|diff| diff.changes.as_slice()).unwrap_or_default() {
+            match line {
+                DiffLine::Addition(text) => added.push(text.clone()),
+                DiffLine::Deletion(text) => removed.push(text.clone()),
+                DiffLine::Context(_) => {}
+            }
+        }
+        
+        Self { change, added, removed, binary: diff.is_none() }
+    }
+    
+    pub fn path(&self) -> &str {
+        &self.change.path
+    }
+    
+    pub fn is_added(&self) -> bool {
+        matches!(self.change.change_type, ChangeType::Added)
+    }
+    
+    pub fn is_deleted(&self) -> bool {
+        matches!(self.change.change_type, ChangeType::Deleted)
+    }
+    
+    pub fn is_renamed(&self) -> bool {
+        matches!(self.change.change_type, ChangeType::Renamed { .. })
+    }
+}
+
+/// A proposed intention for a set of changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntentionSuggestion {
+    pub goal: String,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub impact: Option<String>,
+    /// How sure the provider is, 0.0-1.0
+    pub confidence: f32,
+    /// Tags chosen by a model or with `--tag`; they are kept on top of
+    /// those the repository's taxonomy derives when committing
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl IntentionSuggestion {
+    /// The intention to commit with: tags derived from the text as for any
+    /// other intention, plus the chosen ones.
+    pub fn into_intention(self) -> Intention {
+        let mut intention = Intention::new(self.goal, self.context, self.impact, self.confidence);
+        intention.add_tags(self.tags);
+        intention
+    }
+}
+
+/// What a set of changes does, file by file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeAnalysis {
+    pub summary: String,
+    /// Rough likelihood the change breaks something, 0.0-1.0
+    pub risk_score: f32,
+    pub files: Vec<FileAnalysis>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAnalysis {
+    pub path: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub functions_added: Vec<String>,
+    #[serde(default)]
+    pub functions_modified: Vec<String>,
+    #[serde(default)]
+    pub functions_removed: Vec<String>,
+    #[serde(default)]
+    pub imports_changed: Vec<String>,
+    pub additions: usize,
+    pub deletions: usize,
+    pub risk_score: f32,
+}
+
+/// Someone who knows the changed code.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewerSuggestion {
+    pub name: String,
+    pub reason: String,
+    /// Relative fit, 0.0-1.0; the best candidate scores 1.0
+    pub score: f32,
+}
+
+/// Source of AI assistance, selected by `Config::ai_mode` (see
+/// `ai::provider_for`).
+#[async_trait]
+pub trait AIProvider: Send + Sync {
+    /// Name shown to the user, e.g. `local`.
+    fn name(&self) -> &str;
+    
+    /// Proposes why `changes` were made; `related` are recent intentions
+    /// of commits that touched the same files, newest first.
+    async fn suggest_intention(&self, changes: &[FileDiff], related: &[Intention]) -> Result<IntentionSuggestion>;
+    
+    async fn analyze_changes(&self, changes: &[FileDiff]) -> Result<ChangeAnalysis>;
+    
+    /// Ranks people to review `changes`, from the commits in `history`;
+    /// `author` is never suggested.
+    async fn suggest_reviewers(
+        &self,
+        author: &str,
+        changes: &[FileDiff],
+        history: &[FlowCommit],
+    ) -> Result<Vec<ReviewerSuggestion>>;
+}
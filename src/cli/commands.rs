@@ -1,7 +1,9 @@
 use crate::error::{FlowError, Result};
-use crate::core::repository::Repository;
-use crate::cli::handlers::{InitHandler, AddHandler, CommitHandler, LogHandler};
-use crate::Commands;
+use crate::cli::handlers::{InitHandler, AddHandler, RmHandler, MvHandler, StatusHandler, CommitHandler, LogHandler, ShowHandler, TagsHandler, DiffHandler, StreamHandler, QuantumHandler, CheckoutHandler, RestoreHandler, GcHandler, FsckHandler, OpHandler, CheckIgnoreHandler, AiHandler};
+use crate::core::repository::AddMode;
+use crate::core::query::{Comparison, Query};
+use crate::core::tags::validate_tag;
+use crate::{AiCommands, Commands, OpCommands, QuantumCommands, StreamCommands};
 use tracing::{info, debug};
 
 pub async fn handle_command(command: Option<Commands>) -> Result<()> {
@@ -11,9 +13,34 @@ pub async fn handle_command(command: Option<Commands>) -> Result<()> {
             InitHandler::new().handle(name, ai_mode).await
         }
         
-        Some(Commands::Add { files, intention }) => {
-            debug!("Handling add command: files={:?}, intention={:?}", files, intention);
-            AddHandler::new().handle(files, intention).await
+        Some(Commands::Add { files, intention, all, update, dry_run }) => {
+            debug!("Handling add command: files={:?}, intention={:?}, all={}, update={}, dry_run={}",
+                   files, intention, all, update, dry_run);
+            
+            let mode = if all {
+                AddMode::All
+            } else if update {
+                AddMode::Update
+            } else {
+                AddMode::Paths
+            };
+            AddHandler::new().handle(files, intention, mode, dry_run).await
+        }
+        
+        Some(Commands::Rm { paths, cached, recursive, force }) => {
+            debug!("Handling rm command: paths={:?}, cached={}, recursive={}, force={}",
+                   paths, cached, recursive, force);
+            RmHandler::new().handle(paths, cached, recursive, force).await
+        }
+        
+        Some(Commands::Mv { sources, destination, force }) => {
+            debug!("Handling mv command: sources={:?}, destination={}, force={}", sources, destination, force);
+            MvHandler::new().handle(sources, destination, force).await
+        }
+        
+        Some(Commands::Status { porcelain }) => {
+            debug!("Handling status command: porcelain={}", porcelain);
+            StatusHandler::new().handle(porcelain).await
         }
         
         Some(Commands::Commit { 
@@ -21,37 +48,213 @@ pub async fn handle_command(command: Option<Commands>) -> Result<()> {
             context, 
             impact, 
             confidence, 
-            ai_suggest 
+            tags,
+            ai_suggest,
+            yes,
+            sign,
         }) => {
-            debug!("Handling commit command: intention={}", intention);
+            debug!("Handling commit command: intention={:?}, ai_suggest={}, yes={}", intention, ai_suggest, yes);
             
-            if ai_suggest {
-                return Err(FlowError::NotImplemented("AI suggestion feature".to_string()));
+            if let Some(conf) = confidence {
+                if !(0.0..=1.0).contains(&conf) {
+                    return Err(FlowError::InvalidConfidenceScore(conf));
+                }
+            }
+            for tag in &tags {
+                validate_tag(tag)?;
             }
             
-            if let Some(conf) = confidence {
-                if conf < 0.0 || conf > 1.0 {
+            let handler = CommitHandler::new();
+            match (ai_suggest, intention) {
+                (false, Some(goal)) => handler.handle(goal, context, impact, confidence, tags, sign).await,
+                (_, goal) => handler.handle_suggested(goal, context, impact, confidence, tags, yes, sign).await,
+            }
+        }
+        
+        Some(Commands::Log {
+            oneline,
+            graph,
+            intentions,
+            show_signature,
+            tag,
+            author,
+            since,
+            until,
+            min_confidence,
+            goal,
+            path,
+            query,
+        }) => {
+            debug!("Handling log command: oneline={}, graph={}, intentions={}, show_signature={}, query={:?}", 
+                   oneline, graph, intentions, show_signature, query);
+            
+            let mut filters = Vec::new();
+            if let Some(tag) = tag {
+                filters.push(Query::Tag(tag));
+            }
+            if let Some(author) = author {
+                filters.push(Query::Author(author));
+            }
+            if let Some(since) = since {
+                filters.push(Query::since(&since)?);
+            }
+            if let Some(until) = until {
+                filters.push(Query::until(&until)?);
+            }
+            if let Some(conf) = min_confidence {
+                if !(0.0..=1.0).contains(&conf) {
                     return Err(FlowError::InvalidConfidenceScore(conf));
                 }
+                filters.push(Query::Confidence(Comparison::GreaterOrEqual, conf));
+            }
+            if let Some(goal) = goal {
+                filters.push(Query::goal(&goal)?);
+            }
+            if let Some(path) = path {
+                filters.push(Query::path(&path));
+            }
+            if let Some(query) = query {
+                filters.push(Query::parse(&query)?);
             }
             
-            CommitHandler::new().handle(intention, context, impact, confidence).await
+            LogHandler::new().handle(oneline, graph, intentions, show_signature, Query::all(filters)).await
         }
         
-        Some(Commands::Log { oneline, graph, intentions }) => {
-            debug!("Handling log command: oneline={}, graph={}, intentions={}", 
-                   oneline, graph, intentions);
-            LogHandler::new().handle(oneline, graph, intentions).await
+        Some(Commands::Show { commit_id, stat, name_only, json }) => {
+            debug!("Handling show command: commit_id={}, stat={}, name_only={}, json={}",
+                   commit_id, stat, name_only, json);
+            ShowHandler::new().handle(commit_id, stat, name_only, json).await
         }
         
-        Some(Commands::Show { commit_id }) => {
-            debug!("Handling show command: commit_id={}", commit_id);
-            Err(FlowError::NotImplemented("show command".to_string()))
+        Some(Commands::Checkout { target, force }) => {
+            debug!("Handling checkout command: target={}, force={}", target, force);
+            CheckoutHandler::new().handle(target, force).await
         }
         
-        Some(Commands::Diff { commit1, commit2 }) => {
-            debug!("Handling diff command: commit1={:?}, commit2={:?}", commit1, commit2);
-            Err(FlowError::NotImplemented("diff command".to_string()))
+        Some(Commands::Restore { paths, source, force }) => {
+            debug!("Handling restore command: paths={:?}, source={:?}, force={}", paths, source, force);
+            RestoreHandler::new().handle(paths, source, force).await
+        }
+        
+        Some(Commands::Stream { action }) => {
+            let handler = StreamHandler::new();
+            match action {
+                StreamCommands::Create { name, mode, description, from } => {
+                    debug!("Handling stream create: name={}, mode={}, from={:?}", name, mode, from);
+                    handler.create(name, mode, description, from).await
+                }
+                StreamCommands::List => {
+                    debug!("Handling stream list");
+                    handler.list().await
+                }
+                StreamCommands::Switch { name } => {
+                    debug!("Handling stream switch: name={}", name);
+                    handler.switch(name).await
+                }
+                StreamCommands::Delete { name, force } => {
+                    debug!("Handling stream delete: name={}, force={}", name, force);
+                    handler.delete(name, force).await
+                }
+                StreamCommands::Merge { source, intention, abort } => {
+                    debug!("Handling stream merge: source={:?}, abort={}", source, abort);
+                    handler.merge(source, intention, abort).await
+                }
+            }
+        }
+        
+        Some(Commands::Quantum { action }) => {
+            let handler = QuantumHandler::new();
+            match action {
+                QuantumCommands::Create { name, variants, description, from } => {
+                    debug!("Handling quantum create: name={}, variants={:?}", name, variants);
+                    handler.create(name, variants, description, from).await
+                }
+                QuantumCommands::Switch { name, variant } => {
+                    debug!("Handling quantum switch: name={}, variant={}", name, variant);
+                    handler.switch(name, variant).await
+                }
+                QuantumCommands::Observe { name, variant, reports } => {
+                    debug!("Handling quantum observe: name={}, variant={}, reports={:?}", name, variant, reports);
+                    handler.observe(name, variant, reports).await
+                }
+                QuantumCommands::Status { name } => {
+                    debug!("Handling quantum status: name={}", name);
+                    handler.status(name).await
+                }
+                QuantumCommands::Collapse { name, variant } => {
+                    debug!("Handling quantum collapse: name={}, variant={:?}", name, variant);
+                    handler.collapse(name, variant).await
+                }
+            }
+        }
+        
+        Some(Commands::Diff {
+            commit1,
+            commit2,
+            staged,
+            stat,
+            context,
+            ignore_whitespace,
+            ignore_case,
+            algorithm,
+        }) => {
+            debug!("Handling diff command: commit1={:?}, commit2={:?}, staged={}", commit1, commit2, staged);
+            DiffHandler::new()
+                .handle(commit1, commit2, staged, stat, context, ignore_whitespace, ignore_case, algorithm)
+                .await
+        }
+        
+        Some(Commands::Tags) => {
+            debug!("Handling tags command");
+            TagsHandler::new().handle().await
+        }
+        
+        Some(Commands::Gc { grace_days }) => {
+            debug!("Handling gc command: grace_days={}", grace_days);
+            GcHandler::new().handle(grace_days).await
+        }
+        
+        Some(Commands::Fsck { repair }) => {
+            debug!("Handling fsck command: repair={}", repair);
+            FsckHandler::new().handle(repair).await
+        }
+        
+        Some(Commands::CheckIgnore { paths, non_matching }) => {
+            debug!("Handling check-ignore command: paths={:?}, non_matching={}", paths, non_matching);
+            CheckIgnoreHandler::new().handle(paths, non_matching).await
+        }
+        
+        Some(Commands::Undo { force }) => {
+            debug!("Handling undo command: force={}", force);
+            OpHandler::new().undo(force).await
+        }
+        
+        Some(Commands::Op { action }) => {
+            let handler = OpHandler::new();
+            match action {
+                OpCommands::Log { limit } => {
+                    debug!("Handling op log: limit={:?}", limit);
+                    handler.log(limit).await
+                }
+                OpCommands::Restore { id, force } => {
+                    debug!("Handling op restore: id={}, force={}", id, force);
+                    handler.restore(id, force).await
+                }
+            }
+        }
+        
+        Some(Commands::Ai { action }) => {
+            let handler = AiHandler::new();
+            match action {
+                AiCommands::Analyze { paths, json } => {
+                    debug!("Handling ai analyze: paths={:?}, json={}", paths, json);
+                    handler.analyze(paths, json).await
+                }
+                AiCommands::SuggestReviewers => {
+                    debug!("Handling ai suggest-reviewers");
+                    handler.suggest_reviewers().await
+                }
+            }
         }
         
         None => {
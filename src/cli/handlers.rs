@@ -1,7 +1,20 @@
 use crate::error::{FlowError, Result};
-use crate::core::{repository::Repository, intention::Intention};
-use std::path::Path;
-use tracing::{info, debug, warn};
+use crate::ai::{self, provider::{AIProvider, IntentionSuggestion}, FileDiff};
+use crate::core::{
+    repository::{AddMode, MergeOutcome, Repository, RepositoryStatus},
+    intention::Intention,
+    commit::{FlowCommit, SignatureStatus, SIGNATURE_ALGORITHM},
+    fsck::IssueKind,
+    stream::StreamMode,
+    objects::{ChangeType, FileChange, Hash, Snapshot},
+    query::Query,
+};
+use crate::utils::fs::Pathspec;
+use crate::utils::diff::{diff_strings, format_unified, DiffAlgorithm, DiffOptions, DiffResult};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use tracing::{info, debug};
 
 pub struct InitHandler;
 
@@ -12,7 +25,7 @@ impl InitHandler {
     
     pub async fn handle(&self, name: Option<String>, ai_mode: String) -> Result<()> {
         let current_dir = std::env::current_dir()
-            .map_err(|e| FlowError::IoError(e))?;
+            .map_err(FlowError::IoError)?;
         
         debug!("Initializing repository in: {:?}", current_dir);
         
@@ -48,30 +61,38 @@ impl AddHandler {
         Self
     }
     
-    pub async fn handle(&self, files: Vec<String>, intention: Option<String>) -> Result<()> {
+    pub async fn handle(&self, files: Vec<String>, intention: Option<String>, mode: AddMode, dry_run: bool) -> Result<()> {
         let current_dir = std::env::current_dir()
-            .map_err(|e| FlowError::IoError(e))?;
+            .map_err(FlowError::IoError)?;
         
-        let repo = Repository::open(&current_dir).await?;
+        // A dry run changes nothing, so it need not wait for the lock
+        let repo = if dry_run {
+            Repository::open(&current_dir).await?
+        } else {
+            Repository::open_locked(&current_dir).await?
+        };
         
         debug!("Adding files: {:?}", files);
         
-        let mut added_files = Vec::new();
-        for file_path in files {
-            let path = Path::new(&file_path);
-            
-            if !path.exists() {
-                warn!("File does not exist: {}", file_path);
-                return Err(FlowError::FileNotFound(file_path));
-            }
-            
-            repo.add_file(&file_path).await?;
-            added_files.push(file_path);
+        let staged = repo.add_paths(&files, mode, intention.as_deref(), dry_run).await?;
+        
+        let description = match mode {
+            AddMode::All => "add -A",
+            AddMode::Update => "add -u",
+            AddMode::Paths => "add",
+        };
+        if !dry_run {
+            repo.record_operation(format!("{} {}", description, files.join(" ")).trim_end()).await?;
         }
         
-        info!("Added {} files to staging area", added_files.len());
-        for file in &added_files {
-            println!("added: {}", file);
+        info!("Staged {} paths", staged.len());
+        for change in &staged {
+            let action = if change.removed { "removed" } else { "added" };
+            if dry_run {
+                println!("would be {}: {}", action, change.path);
+            } else {
+                println!("{}: {}", action, change.path);
+            }
         }
         
         if let Some(intent_text) = intention {
@@ -83,6 +104,195 @@ impl AddHandler {
     }
 }
 
+pub struct RmHandler;
+
+impl RmHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, paths: Vec<String>, cached: bool, recursive: bool, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let removed = repo.remove_paths(&paths, cached, recursive, force).await?;
+        
+        let description = if cached { "rm --cached" } else { "rm" };
+        repo.record_operation(&format!("{} {}", description, paths.join(" "))).await?;
+        
+        for path in &removed {
+            println!("rm '{}'", path);
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct MvHandler;
+
+impl MvHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, sources: Vec<String>, destination: String, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let moved = repo.move_paths(&sources, &destination, force).await?;
+        repo.record_operation(&format!("mv {} {}", sources.join(" "), destination)).await?;
+        
+        for (from, to) in &moved {
+            println!("renamed: {} -> {}", from, to);
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct StatusHandler;
+
+impl StatusHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, porcelain: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        
+        let status = repo.get_status().await?;
+        
+        if porcelain {
+            print!("{}", Self::format_porcelain(&status));
+        } else {
+            Self::print_long(&status);
+        }
+        
+        Ok(())
+    }
+    
+    /// One line per path: staged column, working tree column, a space and the path.
+    ///
+    /// `A` added, `M` modified, `D` deleted, `R` renamed, `??` untracked.
+    pub fn format_porcelain(status: &RepositoryStatus) -> String {
+        let mut columns: BTreeMap<String, (char, char)> = BTreeMap::new();
+        
+        for change in &status.staged {
+            let code = Self::change_code(&change.change_type);
+            let path = match &change.change_type {
+                ChangeType::Renamed { from } | ChangeType::Copied { from } => {
+                    format!("{} -> {}", from, change.path)
+                }
+                _ => change.path.clone(),
+            };
+            columns.entry(path).or_insert((' ', ' ')).0 = code;
+        }
+        
+        for path in &status.modified {
+            columns.entry(path.clone()).or_insert((' ', ' ')).1 = 'M';
+        }
+        
+        for path in &status.deleted {
+            columns.entry(path.clone()).or_insert((' ', ' ')).1 = 'D';
+        }
+        
+        let mut output = String::new();
+        for (path, (staged, unstaged)) in columns {
+            output.push_str(&format!("{}{} {}\n", staged, unstaged, path));
+        }
+        
+        for path in &status.untracked {
+            output.push_str(&format!("?? {}\n", path));
+        }
+        
+        output
+    }
+    
+    fn change_code(change_type: &ChangeType) -> char {
+        match change_type {
+            ChangeType::Added => 'A',
+            ChangeType::Modified => 'M',
+            ChangeType::Deleted => 'D',
+            ChangeType::Renamed { .. } => 'R',
+            ChangeType::Copied { .. } => 'C',
+        }
+    }
+    
+    fn print_long(status: &RepositoryStatus) {
+        match &status.stream {
+            Some(stream) => match &status.variant {
+                Some(variant) => println!("On quantum stream {}, variant {}", stream, variant),
+                None => println!("On stream {}", stream),
+            },
+            None => println!("HEAD detached"),
+        }
+        
+        if let Some(merging) = &status.merging {
+            if status.unmerged.is_empty() {
+                println!("All conflicts fixed; run \"flow commit\" to conclude merging {}", &merging.as_str()[..8]);
+            } else {
+                println!("Merging {}; fix conflicts and run \"flow commit\"", &merging.as_str()[..8]);
+            }
+        }
+        
+        if status.is_clean() {
+            println!("nothing to commit, working tree clean");
+            return;
+        }
+        
+        if !status.staged.is_empty() {
+            println!();
+            println!("Changes to be committed:");
+            for change in &status.staged {
+                match &change.change_type {
+                    ChangeType::Added => println!("  new file:   {}", change.path),
+                    ChangeType::Modified => println!("  modified:   {}", change.path),
+                    ChangeType::Deleted => println!("  deleted:    {}", change.path),
+                    ChangeType::Renamed { from } => println!("  renamed:    {} -> {}", from, change.path),
+                    ChangeType::Copied { from } => println!("  copied:     {} -> {}", from, change.path),
+                }
+            }
+        }
+        
+        if !status.unmerged.is_empty() {
+            println!();
+            println!("Unmerged paths:");
+            for path in &status.unmerged {
+                println!("  both modified:   {}", path);
+            }
+        }
+        
+        if !status.modified.is_empty() || !status.deleted.is_empty() {
+            println!();
+            println!("Changes not staged for commit:");
+            for path in &status.modified {
+                println!("  modified:   {}", path);
+            }
+            for path in &status.deleted {
+                println!("  deleted:    {}", path);
+            }
+        }
+        
+        if !status.untracked.is_empty() {
+            println!();
+            println!("Untracked files:");
+            for path in &status.untracked {
+                println!("  {}", path);
+            }
+        }
+    }
+}
+
+/// How many earlier intentions of the staged files an AI suggestion sees.
+const RELATED_INTENTIONS: usize = 5;
+
 pub struct CommitHandler;
 
 impl CommitHandler {
@@ -95,20 +305,218 @@ impl CommitHandler {
         goal: String, 
         context: Option<String>, 
         impact: Option<String>, 
-        confidence: Option<f32>
+        confidence: Option<f32>,
+        tags: Vec<String>,
+        sign: bool,
     ) -> Result<()> {
         let current_dir = std::env::current_dir()
-            .map_err(|e| FlowError::IoError(e))?;
+            .map_err(FlowError::IoError)?;
         
-        let repo = Repository::open(&current_dir).await?;
+        let mut repo = Repository::open_locked(&current_dir).await?;
+        if sign {
+            repo.enable_signing()?;
+        }
         
         debug!("Creating commit with intention: {}", goal);
         
         // Create intention object
-        let intention = Intention::new(goal, context, impact, confidence.unwrap_or(0.8));
+        let mut intention = Intention::new(goal, context, impact, confidence.unwrap_or(0.8));
+        intention.add_tags(tags);
+        
+        Self::commit(&repo, intention).await
+    }
+    
+    /// Commits with an intention the configured AI provider suggests from
+    /// the staged diff and recent intentions of the same files. Fields given
+    /// on the command line replace the suggested ones. Unless `yes`, the
+    /// suggestion is shown to accept, edit or reject.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle_suggested(
+        &self,
+        goal: Option<String>,
+        context: Option<String>,
+        impact: Option<String>,
+        confidence: Option<f32>,
+        tags: Vec<String>,
+        yes: bool,
+        sign: bool,
+    ) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        // The provider call and the review can take a while; only lock to commit
+        let repo = Repository::open(&current_dir).await?;
+        let provider = ai::provider_for(repo.get_config())?;
+        
+        let (mut suggestion, staged) = Self::suggest(&repo, provider.as_ref()).await?;
+        if let Some(goal) = goal {
+            suggestion.goal = goal;
+        }
+        if context.is_some() {
+            suggestion.context = context;
+        }
+        if impact.is_some() {
+            suggestion.impact = impact;
+        }
+        if let Some(confidence) = confidence {
+            suggestion.confidence = confidence;
+        }
+        for tag in tags {
+            if !suggestion.tags.contains(&tag) {
+                suggestion.tags.push(tag);
+            }
+        }
+        
+        println!("Suggested intention ({}):", provider.name());
+        Self::print_suggestion(&suggestion);
+        
+        if !yes {
+            let mut input = std::io::stdin().lock();
+            let mut output = std::io::stdout();
+            suggestion = Self::review(suggestion, &mut input, &mut output)?
+                .ok_or(FlowError::OperationCancelled)?;
+        }
+        drop(repo);
+        
+        let mut repo = Repository::open_locked(&current_dir).await?;
+        Self::ensure_staged(&repo, &staged).await?;
+        if sign {
+            repo.enable_signing()?;
+        }
+        Self::commit(&repo, suggestion.into_intention()).await
+    }
+    
+    /// Asks `provider` for an intention for the staged changes, which are
+    /// returned along with it.
+    async fn suggest(repo: &Repository, provider: &dyn AIProvider) -> Result<(IntentionSuggestion, Vec<FileChange>)> {
+        let changes = repo.get_status().await?.staged;
+        if changes.is_empty() {
+            return Err(FlowError::InvalidRepoState("No changes staged for commit".to_string()));
+        }
+        
+        let mut paths: Vec<String> = changes.iter().map(|change| change.path.clone()).collect();
+        for change in &changes {
+            if let ChangeType::Renamed { from } = &change.change_type {
+                paths.push(from.clone());
+            }
+        }
+        let related = repo.recent_intentions_for(&paths, RELATED_INTENTIONS).await?;
+        let diffs = FilePatch::file_diffs(repo, &changes).await?;
+        
+        let suggestion = provider.suggest_intention(&diffs, &related).await?;
+        debug!("Suggested intention: {:?}", suggestion);
+        Ok((suggestion, changes))
+    }
+    
+    /// Fails unless `staged` are still the staged changes, so a suggestion
+    /// is only committed for the changes it describes.
+    async fn ensure_staged(repo: &Repository, staged: &[FileChange]) -> Result<()> {
+        let contents = |changes: &[FileChange]| {
+            let mut contents: Vec<(String, String)> = changes.iter()
+                .map(|change| (change.path.clone(), change.content_hash.as_str().to_string()))
+                .collect();
+            contents.sort();
+            contents
+        };
+        
+        if contents(&repo.get_status().await?.staged) != contents(staged) {
+            return Err(FlowError::InvalidRepoState(
+                "Staged changes differ from those the suggestion was made for; run the commit again".to_string()
+            ));
+        }
+        
+        Ok(())
+    }
+    
+    fn print_suggestion(suggestion: &IntentionSuggestion) {
+        println!("    Goal: {}", suggestion.goal);
+        if let Some(context) = &suggestion.context {
+            println!("    Context: {}", context);
+        }
+        if let Some(impact) = &suggestion.impact {
+            println!("    Impact: {}", impact);
+        }
+        println!("    Confidence: {:.1}%", suggestion.confidence * 100.0);
+        if !suggestion.tags.is_empty() {
+            println!("    Tags: {}", suggestion.tags.join(", "));
+        }
+    }
+    
+    /// Asks whether to accept, edit or reject the suggestion; `None` when
+    /// rejected or when input ends.
+    fn review(
+        mut suggestion: IntentionSuggestion,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Option<IntentionSuggestion>> {
+        loop {
+            write!(output, "Commit with this intention? [Y]es / [e]dit / [n]o: ")?;
+            output.flush()?;
+            
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                return Ok(None);
+            }
+            
+            match answer.trim().to_lowercase().as_str() {
+                "" | "y" | "yes" => return Ok(Some(suggestion)),
+                "n" | "no" => return Ok(None),
+                "e" | "edit" => {
+                    suggestion = Self::edit(suggestion, input, output)?;
+                    writeln!(output)?;
+                    Self::print_suggestion(&suggestion);
+                }
+                _ => writeln!(output, "Please answer y, e or n")?,
+            }
+        }
+    }
+    
+    /// Prompts for each field; an empty answer keeps the value and `-`
+    /// clears an optional one.
+    fn edit(
+        mut suggestion: IntentionSuggestion,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<IntentionSuggestion> {
+        if let Some(goal) = Self::ask(input, output, "Goal", &suggestion.goal)? {
+            suggestion.goal = goal;
+        }
+        if let Some(context) = Self::ask(input, output, "Context", suggestion.context.as_deref().unwrap_or(""))? {
+            suggestion.context = Some(context).filter(|context| context != "-");
+        }
+        if let Some(impact) = Self::ask(input, output, "Impact", suggestion.impact.as_deref().unwrap_or(""))? {
+            suggestion.impact = Some(impact).filter(|impact| impact != "-");
+        }
+        
+        let current = suggestion.confidence.to_string();
+        while let Some(answer) = Self::ask(input, output, "Confidence (0.0-1.0)", &current)? {
+            match answer.parse::<f32>() {
+                Ok(confidence) if (0.0..=1.0).contains(&confidence) => {
+                    suggestion.confidence = confidence;
+                    break;
+                }
+                _ => writeln!(output, "Confidence must be a number between 0.0 and 1.0")?,
+            }
+        }
+        
+        Ok(suggestion)
+    }
+    
+    /// One field's new value; `None` keeps the current one.
+    fn ask(input: &mut impl BufRead, output: &mut impl Write, label: &str, current: &str) -> Result<Option<String>> {
+        write!(output, "{} [{}]: ", label, current)?;
+        output.flush()?;
         
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        let answer = answer.trim();
+        Ok((!answer.is_empty()).then(|| answer.to_string()))
+    }
+    
+    async fn commit(repo: &Repository, intention: Intention) -> Result<()> {
         // Execute commit
-        let commit_id = repo.commit_with_intention(intention).await?;
+        let commit_id = repo.commit_with_intention(intention.clone()).await?;
+        repo.record_operation(&format!("commit: {}", intention.goal)).await?;
         
         info!("Created commit: {}", commit_id);
         let commit_str = commit_id.to_string();
@@ -125,6 +533,10 @@ impl CommitHandler {
         
         println!("Confidence: {:.1}%", intention.confidence * 100.0);
         
+        // Tags are final only once the repository's taxonomy has seen the changed paths
+        let commit = repo.load_commit(&commit_id).await?;
+        println!("Tags: {}", commit.intention.tags.join(", "));
+        
         Ok(())
     }
 }
@@ -136,9 +548,16 @@ impl LogHandler {
         Self
     }
     
-    pub async fn handle(&self, oneline: bool, graph: bool, intentions: bool) -> Result<()> {
+    pub async fn handle(
+        &self,
+        oneline: bool,
+        _graph: bool,
+        intentions: bool,
+        show_signature: bool,
+        filter: Option<Query>,
+    ) -> Result<()> {
         let current_dir = std::env::current_dir()
-            .map_err(|e| FlowError::IoError(e))?;
+            .map_err(FlowError::IoError)?;
         
         let repo = Repository::open(&current_dir).await?;
         
@@ -153,18 +572,39 @@ impl LogHandler {
         
         info!("Retrieved {} commits", commits.len());
         
+        let commits = match filter {
+            Some(filter) => filter.filter(commits),
+            None => commits,
+        };
+        if commits.is_empty() {
+            println!("No matching commits");
+            return Ok(());
+        }
+        
+        let mut bad_signatures = 0;
         for commit in commits.iter().rev() {
+            let status = commit.signature_status();
+            if matches!(status, SignatureStatus::Bad(_)) {
+                bad_signatures += 1;
+            }
+            
             if oneline {
                 let commit_str = commit.id.to_string();
                 let short_id = if commit_str.len() >= 8 { &commit_str[..8] } else { &commit_str };
                 print!("{} ", short_id);
                 print!("{}", commit.intention.goal);
-                if intentions && commit.intention.context.is_some() {
-                    print!(" ({})", commit.intention.context.as_ref().unwrap());
+                if let (true, Some(context)) = (intentions, &commit.intention.context) {
+                    print!(" ({})", context);
+                }
+                if show_signature {
+                    print!(" [{}]", describe_signature(&status));
                 }
                 println!();
             } else {
                 println!("commit {}", commit.id);
+                if show_signature {
+                    println!("Signature: {}", describe_signature(&status));
+                }
                 println!("Date: {}", commit.timestamp.format("%Y-%m-%d %H:%M:%S"));
                 println!();
                 println!("    Goal: {}", commit.intention.goal);
@@ -181,12 +621,1158 @@ impl LogHandler {
                 
                 if intentions {
                     println!("    Tags: {:?}", commit.intention.tags);
+                    print_sub_intentions(commit);
                 }
                 
                 println!();
             }
         }
         
+        if show_signature && bad_signatures > 0 {
+            return Err(FlowError::SignatureVerificationFailed(format!(
+                "{} commit(s) have invalid signatures",
+                bad_signatures
+            )));
+        }
+        
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn describe_signature(status: &SignatureStatus) -> String {
+    match status {
+        SignatureStatus::Unsigned => "unsigned".to_string(),
+        SignatureStatus::Good { public_key } => format!("good ed25519 signature by key {}", &public_key[..16]),
+        SignatureStatus::Bad(reason) => format!("BAD signature: {}", reason),
+    }
+}
+
+/// Lists a commit's per-path intentions under its own.
+fn print_sub_intentions(commit: &FlowCommit) {
+    if commit.sub_intentions.is_empty() {
+        return;
+    }
+    
+    println!("    Sub-intentions:");
+    for sub in &commit.sub_intentions {
+        println!("      - {} ({})", sub.intention.goal, sub.paths.join(", "));
+    }
+}
+
+/// Where the new side of a change is read from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ContentSource {
+    ObjectStore,
+    WorkingTree,
+}
+
+/// The rendered difference for one changed path.
+pub struct FilePatch {
+    pub change: FileChange,
+    pub old_path: String,
+    pub binary: bool,
+    pub diff: DiffResult,
+}
+
+impl FilePatch {
+    /// Loads both sides of each change from the object store and diffs them.
+    pub async fn from_changes(
+        repo: &Repository,
+        changes: &[FileChange],
+        options: &DiffOptions,
+    ) -> Result<Vec<FilePatch>> {
+        Self::from_changes_with_source(repo, changes, options, ContentSource::ObjectStore).await
+    }
+    
+    /// Like `from_changes`, but reads the new side from `new_side`.
+    pub async fn from_changes_with_source(
+        repo: &Repository,
+        changes: &[FileChange],
+        options: &DiffOptions,
+        new_side: ContentSource,
+    ) -> Result<Vec<FilePatch>> {
+        let mut patches = Vec::new();
+        
+        for change in changes {
+            let old_content = Self::load_side(repo, change.previous_hash.as_ref()).await?;
+            let new_content = match new_side {
+                ContentSource::WorkingTree if !change.content_hash.is_zero() => {
+                    std::fs::read(repo.root_path().join(&change.path))?
+                }
+                _ => Self::load_side(repo, Some(&change.content_hash)).await?,
+            };
+            
+            let old_path = match &change.change_type {
+                ChangeType::Renamed { from } | ChangeType::Copied { from } => from.clone(),
+                _ => change.path.clone(),
+            };
+            
+            let (binary, diff) = match (String::from_utf8(old_content), String::from_utf8(new_content)) {
+                (Ok(old), Ok(new)) => (false, diff_strings(&old, &new, options)?),
+                _ => (true, DiffResult::empty()),
+            };
+            
+            patches.push(FilePatch { change: change.clone(), old_path, binary, diff });
+        }
+        
+        Ok(patches)
+    }
+    
+    /// Loads and diffs each change the way AI providers see it.
+    pub async fn file_diffs(repo: &Repository, changes: &[FileChange]) -> Result<Vec<FileDiff>> {
+        let patches = Self::from_changes(repo, changes, &DiffOptions::default()).await?;
+        
+        Ok(patches.into_iter()
+            .map(|patch| FileDiff::new(patch.change, (!patch.binary).then_some(&patch.diff)))
+            .collect())
+    }
+    
+    async fn load_side(repo: &Repository, hash: Option<&Hash>) -> Result<Vec<u8>> {
+        match hash {
+            Some(hash) if !hash.is_zero() => repo.load_blob_content(hash).await,
+            _ => Ok(Vec::new()),
+        }
+    }
+    
+    pub fn change_label(&self) -> &'static str {
+        match self.change.change_type {
+            ChangeType::Added => "added",
+            ChangeType::Modified => "modified",
+            ChangeType::Deleted => "deleted",
+            ChangeType::Renamed { .. } => "renamed",
+            ChangeType::Copied { .. } => "copied",
+        }
+    }
+    
+    pub fn print(&self) {
+        let path = &self.change.path;
+        println!("diff --flow a/{} b/{}", self.old_path, path);
+        
+        match &self.change.change_type {
+            ChangeType::Added => println!("new file mode 100{:o}", self.change.mode),
+            ChangeType::Deleted => println!("deleted file mode 100{:o}", self.change.mode),
+            ChangeType::Renamed { from } => {
+                println!("rename from {}", from);
+                println!("rename to {}", path);
+            }
+            ChangeType::Copied { from } => {
+                println!("copy from {}", from);
+                println!("copy to {}", path);
+            }
+            ChangeType::Modified => {}
+        }
+        
+        if self.binary {
+            println!("Binary files differ");
+            return;
+        }
+        
+        if self.diff.changes.is_empty() {
+            return;
+        }
+        
+        match self.change.change_type {
+            ChangeType::Added => println!("--- /dev/null"),
+            _ => println!("--- a/{}", self.old_path),
+        }
+        match self.change.change_type {
+            ChangeType::Deleted => println!("+++ /dev/null"),
+            _ => println!("+++ b/{}", path),
+        }
+        print!("{}", format_unified(&self.diff));
+    }
+    
+    /// `--stat` style summary: one line per file plus a total.
+    pub fn print_stat(patches: &[FilePatch]) {
+        let width = patches.iter()
+            .map(|patch| Self::display_path(patch).len())
+            .max()
+            .unwrap_or(0);
+        
+        let mut additions = 0;
+        let mut deletions = 0;
+        for patch in patches {
+            let name = Self::display_path(patch);
+            
+            if patch.binary {
+                println!(" {:<width$} | Bin", name, width = width);
+                continue;
+            }
+            
+            additions += patch.diff.additions;
+            deletions += patch.diff.deletions;
+            println!(
+                " {:<width$} | {:>4} {}{}",
+                name,
+                patch.diff.additions + patch.diff.deletions,
+                "+".repeat(patch.diff.additions.min(40)),
+                "-".repeat(patch.diff.deletions.min(40)),
+                width = width,
+            );
+        }
+        
+        println!(
+            " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+            patches.len(),
+            if patches.len() == 1 { "" } else { "s" },
+            additions,
+            if additions == 1 { "" } else { "s" },
+            deletions,
+            if deletions == 1 { "" } else { "s" },
+        );
+    }
+    
+    fn display_path(patch: &FilePatch) -> String {
+        if patch.old_path != patch.change.path {
+            format!("{} => {}", patch.old_path, patch.change.path)
+        } else {
+            patch.change.path.clone()
+        }
+    }
+}
+
+pub struct ShowHandler;
+
+impl ShowHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, commit_spec: String, stat: bool, name_only: bool, json: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        
+        let commit_id = repo.resolve_commit(&commit_spec).await?;
+        debug!("Showing commit {}", commit_id);
+        let commit = repo.load_commit(&commit_id).await?;
+        
+        if name_only && !json {
+            Self::print_header(&commit);
+            for change in &commit.changes {
+                println!("{}", change.path);
+            }
+            return Self::check_signature(&commit);
+        }
+        
+        let patches = FilePatch::from_changes(&repo, &commit.changes, &DiffOptions::default()).await?;
+        
+        if json {
+            let output = Self::to_json(&commit, &patches, name_only);
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            return Self::check_signature(&commit);
+        }
+        
+        Self::print_header(&commit);
+        
+        if stat {
+            FilePatch::print_stat(&patches);
+        } else {
+            for patch in &patches {
+                patch.print();
+            }
+        }
+        
+        Self::check_signature(&commit)
+    }
+    
+    /// Fails the command for tampered or badly signed commits, after output.
+    fn check_signature(commit: &FlowCommit) -> Result<()> {
+        match commit.signature_status() {
+            SignatureStatus::Bad(reason) => Err(FlowError::SignatureVerificationFailed(reason)),
+            _ => Ok(()),
+        }
+    }
+    
+    fn print_header(commit: &FlowCommit) {
+        let intention = &commit.intention;
+        
+        println!("commit {}", commit.id);
+        if commit.is_merge_commit() {
+            let parents: Vec<String> = commit.parent_commits.iter()
+                .map(|parent| parent.as_str()[..8].to_string())
+                .collect();
+            println!("Merge: {}", parents.join(" "));
+        }
+        println!("Author: {}", intention.author);
+        println!("Date: {}", commit.timestamp.format("%Y-%m-%d %H:%M:%S"));
+        
+        let signature = commit.signature_status();
+        if signature != SignatureStatus::Unsigned {
+            println!("Signature: {}", describe_signature(&signature));
+        }
+        
+        println!();
+        println!("    Goal: {}", intention.goal);
+        
+        if let Some(context) = &intention.context {
+            println!("    Context: {}", context);
+        }
+        
+        if let Some(impact) = &intention.impact {
+            println!("    Impact: {}", impact);
+        }
+        
+        println!("    Confidence: {:.1}%", intention.confidence * 100.0);
+        
+        if !intention.tags.is_empty() {
+            println!("    Tags: {}", intention.tags.join(", "));
+        }
+        
+        print_sub_intentions(commit);
+        
+        println!();
+    }
+    
+    fn signature_json(commit: &FlowCommit) -> serde_json::Value {
+        match commit.signature_status() {
+            SignatureStatus::Unsigned => serde_json::json!({ "status": "unsigned" }),
+            SignatureStatus::Good { public_key } => serde_json::json!({
+                "status": "good",
+                "algorithm": SIGNATURE_ALGORITHM,
+                "public_key": public_key,
+            }),
+            SignatureStatus::Bad(reason) => serde_json::json!({ "status": "bad", "reason": reason }),
+        }
+    }
+    
+    fn to_json(commit: &FlowCommit, patches: &[FilePatch], name_only: bool) -> serde_json::Value {
+        let files: Vec<serde_json::Value> = patches.iter()
+            .map(|patch| {
+                let mut file = serde_json::json!({
+                    "path": patch.change.path,
+                    "old_path": patch.old_path,
+                    "change_type": patch.change_label(),
+                });
+                
+                if !name_only {
+                    file["binary"] = serde_json::json!(patch.binary);
+                    file["additions"] = serde_json::json!(patch.diff.additions);
+                    file["deletions"] = serde_json::json!(patch.diff.deletions);
+                    file["patch"] = serde_json::json!(format_unified(&patch.diff));
+                }
+                
+                file
+            })
+            .collect();
+        
+        serde_json::json!({
+            "id": commit.id,
+            "parents": commit.parent_commits,
+            "timestamp": commit.timestamp,
+            "intention": commit.intention,
+            "sub_intentions": commit.sub_intentions,
+            "signature": Self::signature_json(commit),
+            "files": files,
+        })
+    }
+}
+
+pub struct DiffHandler;
+
+impl DiffHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle(
+        &self,
+        commit1: Option<String>,
+        commit2: Option<String>,
+        staged: bool,
+        stat: bool,
+        context_lines: usize,
+        ignore_whitespace: bool,
+        ignore_case: bool,
+        algorithm: String,
+    ) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        
+        let options = DiffOptions {
+            context_lines,
+            ignore_whitespace,
+            ignore_case,
+            algorithm: algorithm.parse::<DiffAlgorithm>()?,
+        };
+        
+        let (old, new, new_side) = match (commit1, commit2) {
+            (Some(from), Some(to)) => {
+                if staged {
+                    return Err(FlowError::InvalidRepoState(
+                        "--staged compares against the index and takes at most one commit".to_string()
+                    ));
+                }
+                let old = Self::commit_snapshot(&repo, &from).await?;
+                let new = Self::commit_snapshot(&repo, &to).await?;
+                (old, new, ContentSource::ObjectStore)
+            }
+            (Some(from), None) => {
+                let old = Self::commit_snapshot(&repo, &from).await?;
+                if staged {
+                    (old, repo.get_index_snapshot().await?, ContentSource::ObjectStore)
+                } else {
+                    let new = Self::tracked_working_tree(&repo, &old)?;
+                    (old, new, ContentSource::WorkingTree)
+                }
+            }
+            (None, _) if staged => {
+                (repo.get_head_snapshot().await?, repo.get_index_snapshot().await?, ContentSource::ObjectStore)
+            }
+            (None, _) => {
+                let old = repo.get_index_snapshot().await?;
+                let new = Self::tracked_working_tree(&repo, &old)?;
+                (old, new, ContentSource::WorkingTree)
+            }
+        };
+        
+        let changes = Repository::diff_snapshots(&old, &new);
+        debug!("Diffing {} changed paths", changes.len());
+        
+        let patches = FilePatch::from_changes_with_source(&repo, &changes, &options, new_side).await?;
+        
+        if stat {
+            if !patches.is_empty() {
+                FilePatch::print_stat(&patches);
+            }
+        } else {
+            for patch in &patches {
+                patch.print();
+            }
+        }
+        
+        Ok(())
+    }
+    
+    async fn commit_snapshot(repo: &Repository, spec: &str) -> Result<Snapshot> {
+        let commit_id = repo.resolve_commit(spec).await?;
+        let commit = repo.load_commit(&commit_id).await?;
+        repo.get_commit_snapshot(&commit).await
+    }
+    
+    /// The working tree restricted to paths tracked in `base`; untracked
+    /// files are reported by `flow status`, not by diff.
+    fn tracked_working_tree(
+        repo: &Repository,
+        base: &Snapshot,
+    ) -> Result<Snapshot> {
+        let mut working_tree = repo.scan_working_tree(base)?;
+        working_tree.retain(|path, _| base.contains_key(path));
+        Ok(working_tree)
+    }
+}
+
+pub struct CheckoutHandler;
+
+impl CheckoutHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, target: String, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let checked_out = repo.checkout(&target, force).await?;
+        repo.record_operation(&format!("checkout {}", target)).await?;
+        
+        match checked_out {
+            Some(stream) => println!("Switched to stream {}", stream),
+            None => {
+                let commit_id = repo.resolve_commit("HEAD").await?;
+                println!("HEAD is now detached at {}", &commit_id.as_str()[..8]);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct RestoreHandler;
+
+impl RestoreHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, paths: Vec<String>, source: Option<String>, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let restored = repo.restore_paths(&paths, source.as_deref(), force).await?;
+        repo.record_operation(&format!("restore {}", paths.join(" "))).await?;
+        for path in &restored {
+            println!("restored: {}", path);
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct TagsHandler;
+
+impl TagsHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        let counts = repo.tag_counts().await?;
+        
+        if counts.is_empty() {
+            println!("No commits yet");
+            return Ok(());
+        }
+        
+        let width = counts.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(1);
+        for (tag, count) in counts {
+            println!("{:>width$} {}", count, tag, width = width);
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct GcHandler;
+
+impl GcHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, grace_days: u64) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let grace = std::time::Duration::from_secs(grace_days * 24 * 60 * 60);
+        let report = repo.gc(grace).await?;
+        
+        println!("Packed {} objects ({} as deltas)", report.packed, report.deltas);
+        println!("Pruned {} unreachable objects", report.pruned);
+        if report.kept > 0 {
+            println!("Kept {} unreachable objects younger than {} days", report.kept, grace_days);
+        }
+        println!("Object store: {} -> {} bytes", report.size_before, report.size_after);
+        
+        Ok(())
+    }
+}
+
+pub struct FsckHandler;
+
+impl FsckHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, repair: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        // Only repairs write; a plain check must not block other commands
+        let repo = if repair {
+            Repository::open_locked(&current_dir).await?
+        } else {
+            Repository::open(&current_dir).await?
+        };
+        
+        let report = repo.fsck(repair).await?;
+        if repair {
+            repo.record_operation("fsck --repair").await?;
+        }
+        
+        for issue in &report.issues {
+            println!("{} {}: {}", issue.kind, issue.subject, issue.detail);
+        }
+        for (name, commit_id) in &report.repaired {
+            println!("repaired {}: now at {}", name, &commit_id.as_str()[..8]);
+        }
+        for name in &report.unrepaired {
+            println!("cannot repair {}: no intact commit in its reflog", name);
+        }
+        println!("Checked {} refs and {} objects", report.refs_checked, report.objects_checked);
+        
+        if report.is_clean() {
+            return Ok(());
+        }
+        
+        let summary = [IssueKind::Missing, IssueKind::Corrupt, IssueKind::HashMismatch, IssueKind::Dangling]
+            .into_iter()
+            .filter(|kind| report.count(*kind) > 0)
+            .map(|kind| format!("{} {}", report.count(kind), kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        
+        Err(FlowError::IntegrityCheckFailed { code: report.exit_code(), summary })
+    }
+}
+
+pub struct CheckIgnoreHandler;
+
+impl CheckIgnoreHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn handle(&self, paths: Vec<String>, non_matching: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        
+        let mut any_ignored = false;
+        for path in &paths {
+            match repo.check_ignore(path).await? {
+                Some(rule) => {
+                    any_ignored |= rule.ignored;
+                    println!("{}:{}:{}\t{}", rule.source, rule.line, rule.pattern, path);
+                }
+                None if non_matching => println!("::\t{}", path),
+                None => {}
+            }
+        }
+        
+        if any_ignored {
+            Ok(())
+        } else {
+            Err(FlowError::NothingIgnored)
+        }
+    }
+}
+
+pub struct OpHandler;
+
+impl OpHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn undo(&self, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let operation = repo.undo(force).await?;
+        println!("Undid operation {}: {}", operation.id, operation.operation);
+        
+        Ok(())
+    }
+    
+    pub async fn log(&self, limit: Option<usize>) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        
+        let operations = repo.operations()?;
+        for operation in operations.iter().rev().take(limit.unwrap_or(usize::MAX)) {
+            println!(
+                "{:>4}  {}  {}",
+                operation.id,
+                operation.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                operation.operation
+            );
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn restore(&self, id: u64, force: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open_locked(&current_dir).await?;
+        
+        let operation = repo.restore_operation(id, force).await?;
+        println!("Restored the state after operation {}: {}", operation.id, operation.operation);
+        
+        Ok(())
+    }
+}
+
+pub struct StreamHandler;
+
+impl StreamHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    async fn open_repo(&self) -> Result<Repository> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        Repository::open(&current_dir).await
+    }
+    
+    async fn open_repo_locked(&self) -> Result<Repository> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        Repository::open_locked(&current_dir).await
+    }
+    
+    pub async fn create(
+        &self,
+        name: String,
+        mode: String,
+        description: Option<String>,
+        from: Option<String>,
+    ) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        let mode = mode.parse::<StreamMode>()?;
+        let stream = repo.create_stream(&name, mode, description, from.as_deref()).await?;
+        repo.record_operation(&format!("stream create {}", name)).await?;
+        
+        match &stream.head_commit {
+            Some(head) => println!("Created stream {} ({}) at {}", stream.name, stream.mode, &head.as_str()[..8]),
+            None => println!("Created stream {} ({})", stream.name, stream.mode),
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn list(&self) -> Result<()> {
+        let repo = self.open_repo().await?;
+        
+        let current = repo.current_stream_name()?;
+        
+        for stream in repo.list_streams()? {
+            let marker = if current.as_deref() == Some(stream.name.as_str()) { "*" } else { " " };
+            let head = stream.head_commit
+                .as_ref()
+                .map(|id| id.as_str()[..8].to_string())
+                .unwrap_or_else(|| "(empty)".to_string());
+            
+            print!("{} {} [{}] {}", marker, stream.name, stream.mode, head);
+            if let Some(description) = &stream.description {
+                print!(" - {}", description);
+            }
+            println!();
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn switch(&self, name: String) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        repo.switch_stream(&name).await?;
+        repo.record_operation(&format!("stream switch {}", name)).await?;
+        println!("Switched to stream {}", name);
+        
+        Ok(())
+    }
+    
+    pub async fn delete(&self, name: String, force: bool) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        repo.delete_stream(&name, force).await?;
+        repo.record_operation(&format!("stream delete {}", name)).await?;
+        println!("Deleted stream {}", name);
+        
+        Ok(())
+    }
+    
+    pub async fn merge(&self, source: Option<String>, intention: Option<String>, abort: bool) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        if abort {
+            repo.abort_merge().await?;
+            repo.record_operation("merge --abort").await?;
+            println!("Merge aborted");
+            return Ok(());
+        }
+        
+        let source = source.ok_or_else(|| FlowError::InvalidRepoState("Nothing to merge".to_string()))?;
+        
+        // A conflicted merge still changes the index and merge state
+        let outcome = repo.merge_stream(&source, intention).await;
+        repo.record_operation(&format!("merge {}", source)).await?;
+        
+        match outcome {
+            Ok(MergeOutcome::UpToDate) => println!("Already up to date"),
+            Ok(MergeOutcome::FastForward(commit_id)) => {
+                println!("Fast-forward to {}", &commit_id.as_str()[..8]);
+            }
+            Ok(MergeOutcome::Merged(commit_id)) => {
+                println!("Merged {} ({})", source, &commit_id.as_str()[..8]);
+            }
+            Err(FlowError::MergeConflict(paths)) => {
+                for path in &paths {
+                    println!("CONFLICT: {}", path);
+                }
+                println!("Automatic merge failed; fix conflicts, `flow add` them, then `flow commit`");
+                return Err(FlowError::MergeConflict(paths));
+            }
+            Err(e) => return Err(e),
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct QuantumHandler;
+
+impl QuantumHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    async fn open_repo(&self) -> Result<Repository> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        Repository::open(&current_dir).await
+    }
+    
+    async fn open_repo_locked(&self) -> Result<Repository> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        Repository::open_locked(&current_dir).await
+    }
+    
+    pub async fn create(
+        &self,
+        name: String,
+        variants: Vec<String>,
+        description: Option<String>,
+        from: Option<String>,
+    ) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        let stream = repo.create_quantum_stream(&name, &variants, description, from.as_deref()).await?;
+        repo.record_operation(&format!("quantum create {}", name)).await?;
+        
+        match &stream.head_commit {
+            Some(head) => println!("Created quantum stream {} at {}", stream.name, &head.as_str()[..8]),
+            None => println!("Created quantum stream {}", stream.name),
+        }
+        for variant in &variants {
+            println!("  {}@{}", stream.name, variant);
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn switch(&self, name: String, variant: String) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        repo.switch_variant(&name, &variant).await?;
+        repo.record_operation(&format!("quantum switch {}@{}", name, variant)).await?;
+        println!("Switched to variant {}@{}", name, variant);
+        
+        Ok(())
+    }
+    
+    pub async fn observe(&self, name: String, variant: String, reports: Vec<PathBuf>) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        let observed = repo.observe_variant(&name, &variant, &reports).await?;
+        repo.record_operation(&format!("quantum observe {}@{}", name, variant)).await?;
+        
+        for evidence in observed {
+            let summary = evidence.summary();
+            println!(
+                "Observed {}@{} from {}: {} passed, {} failed, {} skipped",
+                name, variant, evidence.source, summary.passed, summary.failed, summary.skipped
+            );
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn status(&self, name: String) -> Result<()> {
+        let repo = self.open_repo().await?;
+        
+        let (stream, state) = repo.load_quantum_stream(&name)?;
+        let current = repo.current_variant()?;
+        
+        print!("Quantum stream {}", stream.name);
+        if let Some(base) = &state.base {
+            print!(" (base {})", &base.as_str()[..8]);
+        }
+        println!();
+        
+        if let Some(collapse) = &state.collapsed {
+            println!(
+                "Collapsed onto {} on {}",
+                collapse.variant,
+                collapse.collapsed_at.format("%Y-%m-%d %H:%M:%S UTC")
+            );
+            if !collapse.rejected.is_empty() {
+                println!("Archived variants: {}", collapse.rejected.join(", "));
+            }
+        }
+        
+        let width = state.variants.keys().map(|name| name.len()).max().unwrap_or(0);
+        for (variant_name, variant) in &state.variants {
+            let on_variant = current.as_ref()
+                .is_some_and(|(current_stream, current_variant)| current_stream == &name && current_variant == variant_name);
+            let marker = if on_variant { "*" } else { " " };
+            let head = repo.variant_head(&name, variant_name)?
+                .map(|id| id.as_str()[..8].to_string())
+                .unwrap_or_else(|| "(empty)".to_string());
+            let evidence = match variant.latest_summary() {
+                Some(summary) => format!(
+                    "{} passed, {} failed, {} skipped ({:.0}%)",
+                    summary.passed,
+                    summary.failed,
+                    summary.skipped,
+                    summary.pass_rate() * 100.0
+                ),
+                None => "not observed".to_string(),
+            };
+            
+            println!("{} {:width$}  {}  {}", marker, variant_name, head, evidence, width = width);
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn collapse(&self, name: String, variant: Option<String>) -> Result<()> {
+        let repo = self.open_repo_locked().await?;
+        
+        let collapse = repo.collapse_quantum_stream(&name, variant.as_deref()).await?;
+        repo.record_operation(&format!("quantum collapse {}", name)).await?;
+        
+        match &collapse.head {
+            Some(head) => println!("Collapsed {} onto {} ({})", name, collapse.variant, &head.as_str()[..8]),
+            None => println!("Collapsed {} onto {}", name, collapse.variant),
+        }
+        if !collapse.rejected.is_empty() {
+            println!("Archived variants: {}", collapse.rejected.join(", "));
+        }
+        
+        Ok(())
+    }
+}
+
+pub struct AiHandler;
+
+impl AiHandler {
+    pub fn new() -> Self {
+        Self
+    }
+    
+    pub async fn analyze(&self, paths: Vec<String>, json: bool) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        let provider = ai::provider_for(repo.get_config())?;
+        
+        let changes = Self::staged_diffs(&repo, &paths).await?;
+        let analysis = provider.analyze_changes(&changes).await?;
+        
+        if json {
+            println!("{}", serde_json::to_string_pretty(&analysis)?);
+            return Ok(());
+        }
+        
+        println!("{} (risk {:.2}, by {})", analysis.summary, analysis.risk_score, provider.name());
+        for file in &analysis.files {
+            println!();
+            println!(
+                "{} ({}, +{} -{}, risk {:.2})",
+                file.path,
+                file.language.as_deref().unwrap_or("unknown"),
+                file.additions,
+                file.deletions,
+                file.risk_score
+            );
+            for name in &file.functions_added {
+                println!("  + {}", name);
+            }
+            for name in &file.functions_modified {
+                println!("  ~ {}", name);
+            }
+            for name in &file.functions_removed {
+                println!("  - {}", name);
+            }
+            for import in &file.imports_changed {
+                println!("  import {}", import);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    pub async fn suggest_reviewers(&self) -> Result<()> {
+        let current_dir = std::env::current_dir()
+            .map_err(FlowError::IoError)?;
+        
+        let repo = Repository::open(&current_dir).await?;
+        let provider = ai::provider_for(repo.get_config())?;
+        
+        let changes = Self::staged_diffs(&repo, &[]).await?;
+        let history = repo.get_log().await?;
+        let author = Intention::get_current_user();
+        
+        let reviewers = provider.suggest_reviewers(&author, &changes, &history).await?;
+        if reviewers.is_empty() {
+            println!("No reviewers found: nobody else has changed these files");
+        }
+        for reviewer in &reviewers {
+            println!("{:<20} {:.2}  {}", reviewer.name, reviewer.score, reviewer.reason);
+        }
+        
+        Ok(())
+    }
+    
+    /// Staged changes, limited to `paths` when given.
+    async fn staged_diffs(repo: &Repository, paths: &[String]) -> Result<Vec<FileDiff>> {
+        let specs = paths.iter().map(|path| Pathspec::parse(path)).collect::<Result<Vec<_>>>()?;
+        let changes: Vec<FileChange> = repo.get_status().await?.staged
+            .into_iter()
+            .filter(|change| specs.is_empty() || specs.iter().any(|spec| spec.matches(&change.path)))
+            .collect();
+        
+        if changes.is_empty() {
+            return Err(FlowError::InvalidRepoState("No staged changes to analyze".to_string()));
+        }
+        
+        FilePatch::file_diffs(repo, &changes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    
+    fn suggestion() -> IntentionSuggestion {
+        IntentionSuggestion {
+            goal: "Update parser".to_string(),
+            context: Some("1 file changed".to_string()),
+            impact: Some("Parsing".to_string()),
+            confidence: 0.6,
+            tags: vec!["feature".to_string()],
+        }
+    }
+    
+    fn review(answers: &str) -> (Option<IntentionSuggestion>, String) {
+        let mut output = Vec::new();
+        let reviewed = CommitHandler::review(suggestion(), &mut Cursor::new(answers), &mut output).unwrap();
+        (reviewed, String::from_utf8(output).unwrap())
+    }
+    
+    #[test]
+    fn test_review_accepts_and_rejects() {
+        assert_eq!(review("y\n").0, Some(suggestion()));
+        assert_eq!(review("\n").0, Some(suggestion()));
+        assert_eq!(review("n\n").0, None);
+        
+        let (reviewed, output) = review("maybe\nyes\n");
+        assert_eq!(reviewed, Some(suggestion()));
+        assert!(output.contains("Please answer y, e or n"));
+    }
+    
+    #[test]
+    fn test_review_end_of_input_rejects() {
+        assert_eq!(review("").0, None);
+    }
+    
+    #[test]
+    fn test_review_edits_fields() {
+        // New goal, clear the context, keep the impact, retry a bad confidence
+        let (reviewed, output) = review("e\nFix the parser\n-\n\n1.5\nhigh\n0.9\ny\n");
+        
+        let reviewed = reviewed.unwrap();
+        assert_eq!(reviewed.goal, "Fix the parser");
+        assert_eq!(reviewed.context, None);
+        assert_eq!(reviewed.impact.as_deref(), Some("Parsing"));
+        assert_eq!(reviewed.confidence, 0.9);
+        assert_eq!(output.matches("Confidence must be a number between 0.0 and 1.0").count(), 2);
+    }
+    
+    #[test]
+    fn test_edit_keeps_fields_at_end_of_input() {
+        let mut output = Vec::new();
+        let edited = CommitHandler::edit(suggestion(), &mut Cursor::new("Fix it\n"), &mut output).unwrap();
+        
+        assert_eq!(edited, IntentionSuggestion { goal: "Fix it".to_string(), ..suggestion() });
+    }
+    
+    #[tokio::test]
+    async fn test_suggested_commit_tags_follow_repository_taxonomy() {
+        use crate::core::tags::TagRule;
+        use crate::storage::config::Config;
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let config_path = path.join(".flowversion").join("config.json");
+        let mut config = Config::load(&config_path).unwrap();
+        config.tags = vec![TagRule {
+            tag: "docs".to_string(),
+            words: Vec::new(),
+            patterns: Vec::new(),
+            paths: vec!["*.md".to_string()],
+        }];
+        config.save(&config_path).unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        let provider = ai::provider_for(repo.get_config()).unwrap();
+        let commit_suggested = |extra_tags: Vec<String>| {
+            let (repo, provider) = (&repo, provider.as_ref());
+            async move {
+                let (mut suggestion, _) = CommitHandler::suggest(repo, provider).await.unwrap();
+                suggestion.tags.extend(extra_tags);
+                let commit_id = repo.commit_with_intention(suggestion.into_intention()).await.unwrap();
+                repo.load_commit(&commit_id).await.unwrap().intention.tags
+            }
+        };
+        
+        // Built-in tags the taxonomy dropped stay dropped; --tag is kept
+        std::fs::write(path.join("guide.md"), "# Guide\n").unwrap();
+        repo.add_file("guide.md").await.unwrap();
+        assert_eq!(commit_suggested(Vec::new()).await, vec!["docs"]);
+        
+        std::fs::write(path.join("guide.md"), "# Guide\n\nMore\n").unwrap();
+        repo.add_file("guide.md").await.unwrap();
+        assert_eq!(commit_suggested(vec!["hotfix".to_string()]).await, vec!["docs", "hotfix"]);
+    }
+    
+    #[tokio::test]
+    async fn test_suggestion_needs_unchanged_staged_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let provider = ai::provider_for(repo.get_config()).unwrap();
+        std::fs::write(path.join("a.txt"), "one").unwrap();
+        repo.add_file("a.txt").await.unwrap();
+        
+        let (_, staged) = CommitHandler::suggest(&repo, provider.as_ref()).await.unwrap();
+        CommitHandler::ensure_staged(&repo, &staged).await.unwrap();
+        
+        // Restaging different content invalidates the suggestion, as does staging another file
+        std::fs::write(path.join("a.txt"), "two").unwrap();
+        repo.add_file("a.txt").await.unwrap();
+        assert!(matches!(CommitHandler::ensure_staged(&repo, &staged).await, Err(FlowError::InvalidRepoState(_))));
+        
+        let (_, staged) = CommitHandler::suggest(&repo, provider.as_ref()).await.unwrap();
+        std::fs::write(path.join("b.txt"), "b").unwrap();
+        repo.add_file("b.txt").await.unwrap();
+        assert!(matches!(CommitHandler::ensure_staged(&repo, &staged).await, Err(FlowError::InvalidRepoState(_))));
+    }
+}
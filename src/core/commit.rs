@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
-use crate::core::{intention::Intention, objects::{CommitId, Hash, FileChange}};
+use chrono::{DateTime, SecondsFormat, Utc};
+use crate::core::{intention::{Intention, SubIntention}, objects::{CommitId, Hash, FileChange}};
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlowCommit {
@@ -11,6 +14,9 @@ pub struct FlowCommit {
     pub parent_commits: Vec<CommitId>,
     pub timestamp: DateTime<Utc>,
     pub signature: Option<CommitSignature>,
+    /// Per-path intentions staged with the changes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_intentions: Vec<SubIntention>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +26,16 @@ pub struct CommitSignature {
     pub public_key: String,
 }
 
+/// Outcome of checking a commit's signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureStatus {
+    Unsigned,
+    /// Signed by the hex-encoded public key, and nothing was altered since
+    Good { public_key: String },
+    /// Signed, but the signature or the commit content does not check out
+    Bad(String),
+}
+
 impl FlowCommit {
     pub fn new(
         intention: Intention,
@@ -27,84 +43,171 @@ impl FlowCommit {
         tree_hash: Hash,
         parent_commits: Vec<CommitId>,
     ) -> Self {
-        Self {
-            id: CommitId::new(),
+        let mut commit = Self {
+            id: CommitId::from_hash(Hash::new(b"")),
             intention,
             changes,
             tree_hash,
             parent_commits,
             timestamp: Utc::now(),
             signature: None,
-        }
+            sub_intentions: Vec::new(),
+        };
+        commit.id = CommitId::from_hash(commit.calculate_commit_hash());
+        commit
     }
     
-    pub fn is_merge_commit(&self) -> bool {
-        self.parent_commits.len() > 1
+    /// Attaches per-path intentions, which are part of the commit id.
+    pub fn with_sub_intentions(mut self, sub_intentions: Vec<SubIntention>) -> Self {
+        self.sub_intentions = sub_intentions;
+        self.id = CommitId::from_hash(self.calculate_commit_hash());
+        self
     }
     
-    pub fn is_root_commit(&self) -> bool {
-        self.parent_commits.is_empty()
+    /// The sub-intention explaining why `path` changed, if any.
+    #[cfg(test)]
+    pub fn sub_intention_for(&self, path: &str) -> Option<&SubIntention> {
+        self.sub_intentions.iter().find(|sub| sub.paths.iter().any(|p| p == path))
     }
     
-    pub fn short_id(&self) -> String {
-        self.id.to_string()[..8].to_string()
+    pub fn is_merge_commit(&self) -> bool {
+        self.parent_commits.len() > 1
     }
     
-    pub fn get_changed_files(&self) -> Vec<String> {
-        self.changes.iter()
-            .map(|change| change.path.clone())
-            .collect()
+    #[cfg(test)]
+    pub fn is_root_commit(&self) -> bool {
+        self.parent_commits.is_empty()
     }
     
+    #[cfg(test)]
     pub fn files_count(&self) -> usize {
         self.changes.len()
     }
     
-    pub fn has_file(&self, path: &str) -> bool {
-        self.changes.iter().any(|change| change.path == path)
-    }
-    
-    pub fn sign(&mut self, _private_key: &str) -> crate::error::Result<()> {
-        // TODO: Implement actual cryptographic signing
-        // For now, this is a placeholder
+    /// Signs the commit with an Ed25519 key. The signature does not take
+    /// part in the commit id, so signing never changes it.
+    pub fn sign(&mut self, key: &SigningKey) -> crate::error::Result<()> {
+        let signature = key.sign(&self.signed_bytes());
+        
         self.signature = Some(CommitSignature {
-            algorithm: "EdDSA".to_string(),
-            signature: "placeholder_signature".to_string(),
-            public_key: "placeholder_public_key".to_string(),
+            algorithm: SIGNATURE_ALGORITHM.to_string(),
+            signature: hex::encode(signature.to_bytes()),
+            public_key: hex::encode(key.verifying_key().to_bytes()),
         });
         Ok(())
     }
     
+    #[cfg(test)]
     pub fn verify_signature(&self) -> bool {
-        // TODO: Implement actual signature verification
-        // For now, just check if signature exists
-        self.signature.is_some()
+        matches!(self.signature_status(), SignatureStatus::Good { .. })
+    }
+    
+    pub fn signature_status(&self) -> SignatureStatus {
+        let signature = match &self.signature {
+            Some(signature) => signature,
+            None => return SignatureStatus::Unsigned,
+        };
+        
+        if signature.algorithm != SIGNATURE_ALGORITHM {
+            return SignatureStatus::Bad(format!("unsupported algorithm {}", signature.algorithm));
+        }
+        
+        if !self.verify_id() {
+            return SignatureStatus::Bad("commit id does not match its content".to_string());
+        }
+        
+        let public_key = hex::decode(&signature.public_key)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok());
+        let signature_bytes = hex::decode(&signature.signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .map(|bytes| ed25519_dalek::Signature::from_bytes(&bytes));
+        
+        let (public_key, signature_bytes) = match (public_key, signature_bytes) {
+            (Some(public_key), Some(signature_bytes)) => (public_key, signature_bytes),
+            _ => return SignatureStatus::Bad("malformed signature or public key".to_string()),
+        };
+        
+        match public_key.verify(&self.signed_bytes(), &signature_bytes) {
+            Ok(()) => SignatureStatus::Good { public_key: signature.public_key.clone() },
+            Err(_) => SignatureStatus::Bad("signature does not match commit content".to_string()),
+        }
+    }
+    
+    /// What a signature covers: the canonical bytes plus the fields left out
+    /// of the commit id, so that editing anything in a stored commit
+    /// invalidates its signature.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let changes = serde_json::to_vec(&self.changes).unwrap_or_default();
+        
+        let mut data = self.canonical_bytes();
+        data.extend_from_slice(format!("intention-id {}\n", self.intention.id).as_bytes());
+        data.extend_from_slice(format!("changes {}\n", Hash::new(&changes)).as_bytes());
+        data
     }
     
     pub fn to_bytes(&self) -> crate::error::Result<Vec<u8>> {
         serde_json::to_vec(self)
-            .map_err(|e| crate::error::FlowError::SerializationError(e))
+            .map_err(crate::error::FlowError::SerializationError)
     }
     
     pub fn from_bytes(bytes: &[u8]) -> crate::error::Result<Self> {
         serde_json::from_slice(bytes)
-            .map_err(|e| crate::error::FlowError::SerializationError(e))
+            .map_err(crate::error::FlowError::SerializationError)
     }
     
-    pub fn calculate_commit_hash(&self) -> Hash {
-        // Create a deterministic representation for hashing
-        let mut data = Vec::new();
-        data.extend_from_slice(self.id.to_string().as_bytes());
-        data.extend_from_slice(self.intention.id.to_string().as_bytes());
-        data.extend_from_slice(self.tree_hash.as_str().as_bytes());
+    /// Deterministic serialization of everything that identifies a commit.
+    ///
+    /// The intention's random id, the recorded change list (derivable from
+    /// the trees) and the signature are deliberately left out, so identical
+    /// history hashes identically on every machine.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let intention = &self.intention;
+        let intention_fields = serde_json::json!({
+            "goal": intention.goal,
+            "context": intention.context,
+            "impact": intention.impact,
+            "confidence": intention.confidence,
+            "tags": intention.tags,
+            "related_intentions": intention.related_intentions,
+            "timestamp": intention.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+        });
         
+        let mut data = String::new();
+        data.push_str(&format!("tree {}\n", self.tree_hash));
         for parent in &self.parent_commits {
-            data.extend_from_slice(parent.to_string().as_bytes());
+            data.push_str(&format!("parent {}\n", parent));
+        }
+        data.push_str(&format!("author {}\n", intention.author));
+        data.push_str(&format!(
+            "timestamp {}\n",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true)
+        ));
+        data.push_str(&format!("intention {}\n", intention_fields));
+        // Only present when there are any, so older commits keep their ids
+        for sub in &self.sub_intentions {
+            let sub_fields = serde_json::json!({
+                "goal": sub.intention.goal,
+                "confidence": sub.intention.confidence,
+                "tags": sub.intention.tags,
+                "paths": sub.paths,
+                "timestamp": sub.intention.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            });
+            data.push_str(&format!("sub-intention {}\n", sub_fields));
         }
         
-        data.extend_from_slice(self.timestamp.to_rfc3339().as_bytes());
-        
-        Hash::new(&data)
+        data.into_bytes()
+    }
+    
+    pub fn calculate_commit_hash(&self) -> Hash {
+        Hash::new(&self.canonical_bytes())
+    }
+    
+    /// Whether `id` still matches the commit's content.
+    pub fn verify_id(&self) -> bool {
+        self.id == CommitId::from_hash(self.calculate_commit_hash())
     }
 }
 
@@ -177,8 +280,8 @@ mod tests {
             0.9
         );
         
-        let parent1 = CommitId::new();
-        let parent2 = CommitId::new();
+        let parent1 = CommitId::from_hash(Hash::new(b"parent1"));
+        let parent2 = CommitId::from_hash(Hash::new(b"parent2"));
         
         let commit = FlowCommit::new(
             intention,
@@ -220,4 +323,86 @@ mod tests {
         // Different tree hashes should produce different commit hashes
         assert_ne!(hash1, hash2);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_commit_id_is_content_addressed() {
+        let intention = Intention::new(
+            "Deterministic".to_string(),
+            None,
+            None,
+            0.8
+        );
+        
+        let commit = FlowCommit::new(intention, vec![], Hash::new(b"tree"), vec![]);
+        assert_eq!(commit.id, CommitId::from_hash(commit.calculate_commit_hash()));
+        assert!(commit.verify_id());
+        
+        // Same content with a different intention id hashes identically
+        let mut copy = commit.clone();
+        copy.intention.id = uuid::Uuid::new_v4();
+        assert_eq!(copy.calculate_commit_hash(), commit.calculate_commit_hash());
+        
+        // Tampering with the content invalidates the id
+        let mut tampered = commit.clone();
+        tampered.intention.goal = "Something else".to_string();
+        assert!(!tampered.verify_id());
+    }
+    
+    #[test]
+    fn test_sub_intentions_are_part_of_the_id() {
+        let intention = Intention::new("Parser".to_string(), None, None, 0.8);
+        let commit = FlowCommit::new(intention, vec![], Hash::new(b"tree"), vec![]);
+        let plain_id = commit.id.clone();
+        
+        let sub = SubIntention {
+            intention: Intention::new("Document the grammar".to_string(), None, None, 0.8),
+            paths: vec!["docs/grammar.md".to_string()],
+        };
+        let commit = commit.with_sub_intentions(vec![sub]);
+        assert_ne!(commit.id, plain_id);
+        assert!(commit.verify_id());
+        assert_eq!(commit.sub_intention_for("docs/grammar.md").unwrap().intention.goal, "Document the grammar");
+        assert!(commit.sub_intention_for("src/parser.rs").is_none());
+        
+        let stored = FlowCommit::from_bytes(&commit.to_bytes().unwrap()).unwrap();
+        assert!(stored.verify_id());
+        
+        let mut tampered = stored.clone();
+        tampered.sub_intentions[0].paths.push("src/parser.rs".to_string());
+        assert!(!tampered.verify_id());
+    }
+    
+    #[test]
+    fn test_sign_and_verify() {
+        let intention = Intention::new("Signed".to_string(), None, None, 0.8);
+        let mut commit = FlowCommit::new(intention, vec![], Hash::new(b"tree"), vec![]);
+        assert_eq!(commit.signature_status(), SignatureStatus::Unsigned);
+        assert!(!commit.verify_signature());
+        
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let id = commit.id.clone();
+        commit.sign(&key).unwrap();
+        assert_eq!(commit.id, id);
+        assert!(commit.verify_signature());
+        
+        // Survives a round trip through storage
+        let stored = FlowCommit::from_bytes(&commit.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            stored.signature_status(),
+            SignatureStatus::Good { public_key: hex::encode(key.verifying_key().to_bytes()) }
+        );
+        
+        // Any edit is caught, including fields outside the commit id
+        let mut tampered = stored.clone();
+        tampered.intention.goal = "Forged".to_string();
+        assert!(matches!(tampered.signature_status(), SignatureStatus::Bad(_)));
+        
+        let mut tampered = stored.clone();
+        tampered.changes.push(FileChange::new_added("evil.txt".to_string(), Hash::new(b"evil")));
+        assert!(matches!(tampered.signature_status(), SignatureStatus::Bad(_)));
+        
+        let mut tampered = stored;
+        tampered.signature.as_mut().unwrap().public_key = hex::encode(SigningKey::from_bytes(&[8u8; 32]).verifying_key().to_bytes());
+        assert!(!tampered.verify_signature());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::core::objects::CommitId;
+use std::fmt;
+
+/// Exit status bits of `flow fsck`, or-ed together when several kinds of
+/// problem are found. 1 is left for ordinary errors.
+pub const EXIT_MISSING: i32 = 2;
+pub const EXIT_CORRUPT: i32 = 4;
+pub const EXIT_HASH_MISMATCH: i32 = 8;
+pub const EXIT_DANGLING: i32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueKind {
+    /// Referenced by a ref or another object, but not in the store
+    Missing,
+    /// Present but unreadable, or a ref that does not hold a commit id
+    Corrupt,
+    /// Content does not hash to the id it is stored under
+    HashMismatch,
+    /// Stored but not reachable from any ref, the index, quantum evidence
+    /// or the operation log
+    Dangling,
+}
+
+impl IssueKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            IssueKind::Missing => EXIT_MISSING,
+            IssueKind::Corrupt => EXIT_CORRUPT,
+            IssueKind::HashMismatch => EXIT_HASH_MISMATCH,
+            IssueKind::Dangling => EXIT_DANGLING,
+        }
+    }
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueKind::Missing => write!(f, "missing"),
+            IssueKind::Corrupt => write!(f, "corrupt"),
+            IssueKind::HashMismatch => write!(f, "hash mismatch"),
+            IssueKind::Dangling => write!(f, "dangling"),
+        }
+    }
+}
+
+/// A problem found by `Repository::fsck`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsckIssue {
+    pub kind: IssueKind,
+    /// What is affected, e.g. `commit <id>`, `ref refs/streams/main`
+    pub subject: String,
+    pub detail: String,
+}
+
+impl FsckIssue {
+    pub fn new(kind: IssueKind, subject: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            kind,
+            subject: subject.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub issues: Vec<FsckIssue>,
+    pub refs_checked: usize,
+    pub objects_checked: usize,
+    /// Refs rewritten by `--repair`, with the commit they now point to
+    pub repaired: Vec<(String, CommitId)>,
+    /// Broken refs that `--repair` found no usable reflog entry for
+    pub unrepaired: Vec<String>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+    
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|issue| issue.kind == kind).count()
+    }
+    
+    pub fn exit_code(&self) -> i32 {
+        self.issues.iter().fold(0, |code, issue| code | issue.kind.exit_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_exit_code_combines_kinds() {
+        let mut report = FsckReport::default();
+        assert_eq!(report.exit_code(), 0);
+        
+        report.issues.push(FsckIssue::new(IssueKind::Dangling, "blob a", ""));
+        report.issues.push(FsckIssue::new(IssueKind::Dangling, "blob b", ""));
+        assert_eq!(report.exit_code(), EXIT_DANGLING);
+        
+        report.issues.push(FsckIssue::new(IssueKind::Missing, "commit c", "parent of d"));
+        assert_eq!(report.exit_code(), EXIT_DANGLING | EXIT_MISSING);
+        assert_eq!(report.count(IssueKind::Dangling), 2);
+    }
+}
@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use crate::core::tags::{validate_tag, TagTaxonomy};
 use crate::error::{FlowError, Result};
 
+/// Longest goal an intention may have, in bytes.
+pub const MAX_GOAL_LEN: usize = 200;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Intention {
     pub id: Uuid,
@@ -14,6 +19,39 @@ pub struct Intention {
     pub timestamp: DateTime<Utc>,
     pub tags: Vec<String>,
     pub related_intentions: Vec<Uuid>,
+    /// Tags given with `add_tags` rather than derived from the text; they
+    /// survive `retag` and are only tracked until the intention is stored
+    #[serde(skip)]
+    pub explicit_tags: Vec<String>,
+}
+
+/// Why some of a commit's files changed, given with `flow add --intention`
+/// and linked from the commit's intention through `related_intentions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubIntention {
+    pub intention: Intention,
+    /// Changed paths the intention explains
+    pub paths: Vec<String>,
+}
+
+impl SubIntention {
+    /// An id derived from the goal, paths and time rather than drawn at
+    /// random: sub-intention ids are linked from the commit's
+    /// `related_intentions`, which the commit id covers.
+    pub fn content_id(&self) -> Uuid {
+        let mut hasher = Sha256::new();
+        hasher.update(self.intention.goal.as_bytes());
+        for path in &self.paths {
+            hasher.update(b"\0");
+            hasher.update(path.as_bytes());
+        }
+        hasher.update(b"\0");
+        hasher.update(self.intention.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true).as_bytes());
+        
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hasher.finalize()[..16]);
+        uuid::Builder::from_custom_bytes(bytes).into_uuid()
+    }
 }
 
 impl Intention {
@@ -35,6 +73,7 @@ impl Intention {
             timestamp: Utc::now(),
             tags,
             related_intentions: Vec::new(),
+            explicit_tags: Vec::new(),
         }
     }
     
@@ -43,132 +82,63 @@ impl Intention {
             return Err(FlowError::InvalidIntention("Goal cannot be empty".to_string()));
         }
         
-        if self.goal.len() > 200 {
-            return Err(FlowError::InvalidIntention("Goal too long (max 200 chars)".to_string()));
+        if self.goal.len() > MAX_GOAL_LEN {
+            return Err(FlowError::InvalidIntention(format!("Goal too long (max {} bytes)", MAX_GOAL_LEN)));
         }
         
         if self.confidence < 0.0 || self.confidence > 1.0 {
             return Err(FlowError::InvalidConfidenceScore(self.confidence));
         }
         
+        for tag in &self.tags {
+            validate_tag(tag)?;
+        }
+        
         Ok(())
     }
     
-    fn generate_tags(goal: &str, context: Option<&str>, impact: Option<&str>) -> Vec<String> {
-        let mut tags = Vec::new();
-        let text = format!("{} {} {}", 
-            goal, 
-            context.unwrap_or(""), 
-            impact.unwrap_or("")
-        ).to_lowercase();
-        
-        // Common development tags
-        if text.contains("bug") || text.contains("fix") || text.contains("error") {
-            tags.push("bugfix".to_string());
-        }
-        
-        if text.contains("feature") || text.contains("add") || text.contains("implement") {
-            tags.push("feature".to_string());
-        }
-        
-        if text.contains("refactor") || text.contains("cleanup") || text.contains("optimize") {
-            tags.push("refactor".to_string());
-        }
-        
-        if text.contains("test") {
-            tags.push("test".to_string());
-        }
-        
-        if text.contains("doc") || text.contains("comment") {
-            tags.push("documentation".to_string());
-        }
-        
-        if text.contains("security") || text.contains("auth") || text.contains("login") {
-            tags.push("security".to_string());
-        }
-        
-        if text.contains("performance") || text.contains("speed") || text.contains("fast") {
-            tags.push("performance".to_string());
-        }
+    pub fn generate_tags(goal: &str, context: Option<&str>, impact: Option<&str>) -> Vec<String> {
+        TagTaxonomy::builtin().tags_for(&Self::text_of(goal, context, impact), &[])
+    }
+    
+    /// Re-derives the tags with a repository's taxonomy and the paths the
+    /// intention covers, keeping those given explicitly (`--tag`, AI
+    /// suggestions, merges).
+    pub fn retag(&mut self, taxonomy: &TagTaxonomy, paths: &[String]) {
+        let text = Self::text_of(&self.goal, self.context.as_deref(), self.impact.as_deref());
         
-        if text.contains("ui") || text.contains("interface") || text.contains("design") {
-            tags.push("ui".to_string());
+        self.tags = taxonomy.tags_for(&text, paths);
+        if !self.explicit_tags.is_empty() {
+            self.tags.retain(|tag| tag != "general");
         }
-        
-        if text.contains("api") || text.contains("endpoint") || text.contains("service") {
-            tags.push("api".to_string());
+        for tag in &self.explicit_tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
         }
-        
-        if text.contains("database") || text.contains("db") || text.contains("sql") {
-            tags.push("database".to_string());
-        }
-        
-        if tags.is_empty() {
-            tags.push("general".to_string());
+    }
+    
+    /// Adds explicit tags not already present, keeping their order.
+    pub fn add_tags(&mut self, tags: impl IntoIterator<Item = String>) {
+        for tag in tags {
+            if !self.explicit_tags.contains(&tag) {
+                self.explicit_tags.push(tag.clone());
+            }
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
         }
-        
-        tags
     }
     
-    fn get_current_user() -> String {
+    fn text_of(goal: &str, context: Option<&str>, impact: Option<&str>) -> String {
+        format!("{} {} {}", goal, context.unwrap_or(""), impact.unwrap_or(""))
+    }
+    
+    pub fn get_current_user() -> String {
         std::env::var("USER")
             .or_else(|_| std::env::var("USERNAME"))
             .unwrap_or_else(|_| "unknown".to_string())
     }
-    
-    pub fn similarity_score(&self, other: &Intention) -> f32 {
-        let mut score = 0.0;
-        let mut factors = 0;
-        
-        // Goal similarity (using simple word matching)
-        let self_words: std::collections::HashSet<&str> = self.goal
-            .to_lowercase()
-            .split_whitespace()
-            .collect();
-        let other_words: std::collections::HashSet<&str> = other.goal
-            .to_lowercase()
-            .split_whitespace()
-            .collect();
-        
-        let intersection = self_words.intersection(&other_words).count();
-        let union = self_words.union(&other_words).count();
-        
-        if union > 0 {
-            score += intersection as f32 / union as f32;
-            factors += 1;
-        }
-        
-        // Tag similarity
-        let self_tags: std::collections::HashSet<&str> = self.tags.iter().map(|s| s.as_str()).collect();
-        let other_tags: std::collections::HashSet<&str> = other.tags.iter().map(|s| s.as_str()).collect();
-        
-        let tag_intersection = self_tags.intersection(&other_tags).count();
-        let tag_union = self_tags.union(&other_tags).count();
-        
-        if tag_union > 0 {
-            score += tag_intersection as f32 / tag_union as f32;
-            factors += 1;
-        }
-        
-        // Author similarity
-        if self.author == other.author {
-            score += 1.0;
-        }
-        factors += 1;
-        
-        // Time proximity (within 24 hours gets bonus)
-        let time_diff = (self.timestamp - other.timestamp).abs();
-        if time_diff.num_hours() < 24 {
-            score += 0.5;
-        }
-        factors += 1;
-        
-        if factors > 0 {
-            score / factors as f32
-        } else {
-            0.0
-        }
-    }
 }
 
 #[cfg(test)]
@@ -223,23 +193,59 @@ mod tests {
     }
     
     #[test]
-    fn test_similarity_score() {
-        let intention1 = Intention::new(
-            "Add user authentication".to_string(),
-            None,
-            None,
-            0.8
-        );
+    fn test_sub_intention_ids_follow_content() {
+        let sub = SubIntention {
+            intention: Intention::new("Document the grammar".to_string(), None, None, 0.8),
+            paths: vec!["docs/grammar.md".to_string()],
+        };
+        let mut same = sub.clone();
+        same.intention.id = Uuid::new_v4();
+        assert_eq!(sub.content_id(), same.content_id());
+        
+        let mut other = sub.clone();
+        other.paths.push("README.md".to_string());
+        assert_ne!(sub.content_id(), other.content_id());
+    }
+    
+    #[test]
+    fn test_tags_match_whole_words() {
+        let tags = Intention::generate_tags("Validate the address field", Some("From user feedback"), None);
+        assert_eq!(tags, vec!["general".to_string()]);
+    }
+    
+    #[test]
+    fn test_retag_keeps_explicit_tags() {
+        use crate::core::tags::TagRule;
         
-        let intention2 = Intention::new(
-            "Add user login system".to_string(),
-            None,
-            None,
-            0.9
-        );
+        let mut intention = Intention::new("Fix the login form".to_string(), None, None, 0.8);
+        intention.add_tags(vec!["release-blocker".to_string()]);
         
-        let score = intention1.similarity_score(&intention2);
-        assert!(score > 0.0);
-        assert!(score <= 1.0);
+        let taxonomy = TagTaxonomy::new(&[TagRule {
+            tag: "frontend".to_string(),
+            words: Vec::new(),
+            patterns: Vec::new(),
+            paths: vec!["web/**".to_string()],
+        }]).unwrap();
+        intention.retag(&taxonomy, &["web/login.tsx".to_string()]);
+        
+        assert_eq!(intention.tags, vec!["frontend".to_string(), "release-blocker".to_string()]);
+    }
+    
+    #[test]
+    fn test_retag_keeps_explicit_tags_the_builtins_derive() {
+        use crate::core::tags::TagRule;
+        
+        let mut intention = Intention::new("Harden login".to_string(), None, None, 0.8);
+        intention.add_tags(vec!["security".to_string(), "release".to_string()]);
+        
+        let taxonomy = TagTaxonomy::new(&[TagRule {
+            tag: "frontend".to_string(),
+            words: Vec::new(),
+            patterns: Vec::new(),
+            paths: vec!["web/**".to_string()],
+        }]).unwrap();
+        intention.retag(&taxonomy, &["src/auth.rs".to_string()]);
+        
+        assert_eq!(intention.tags, vec!["security".to_string(), "release".to_string()]);
     }
-}
\ No newline at end of file
+}
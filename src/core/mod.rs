@@ -2,4 +2,8 @@ pub mod intention;
 pub mod commit;
 pub mod repository;
 pub mod stream;
-pub mod objects;
\ No newline at end of file
+pub mod objects;
+pub mod quantum;
+pub mod fsck;
+pub mod tags;
+pub mod query;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Hash(String);
@@ -18,6 +18,15 @@ impl Hash {
         Hash(s)
     }
     
+    /// Placeholder hash used for content that no longer exists (deletions).
+    pub fn zero() -> Self {
+        Hash("0".repeat(64))
+    }
+    
+    pub fn is_zero(&self) -> bool {
+        self.0.chars().all(|c| c == '0')
+    }
+    
     pub fn as_str(&self) -> &str {
         &self.0
     }
@@ -35,25 +44,27 @@ impl From<String> for Hash {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CommitId(Uuid);
-
-impl Default for CommitId {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+/// Content-addressed commit identity: the SHA-256 of the commit's
+/// canonical serialization (see `FlowCommit::canonical_bytes`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CommitId(String);
 
 impl CommitId {
-    pub fn new() -> Self {
-        CommitId(Uuid::new_v4())
+    pub fn from_hash(hash: Hash) -> Self {
+        CommitId(hash.as_str().to_string())
     }
     
-    pub fn from_uuid(uuid: Uuid) -> Self {
-        CommitId(uuid)
+    pub fn parse(s: &str) -> crate::error::Result<Self> {
+        let s = s.trim();
+        
+        if s.len() != 64 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(crate::error::FlowError::InvalidCommitId(s.to_string()));
+        }
+        
+        Ok(CommitId(s.to_ascii_lowercase()))
     }
     
-    pub fn as_uuid(&self) -> &Uuid {
+    pub fn as_str(&self) -> &str {
         &self.0
     }
 }
@@ -64,7 +75,7 @@ impl std::fmt::Display for CommitId {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct StreamId(Uuid);
 
 impl Default for StreamId {
@@ -77,14 +88,6 @@ impl StreamId {
     pub fn new() -> Self {
         StreamId(Uuid::new_v4())
     }
-    
-    pub fn from_uuid(uuid: Uuid) -> Self {
-        StreamId(uuid)
-    }
-    
-    pub fn as_uuid(&self) -> &Uuid {
-        &self.0
-    }
 }
 
 impl std::fmt::Display for StreamId {
@@ -136,17 +139,27 @@ impl FileChange {
         Self {
             path,
             change_type: ChangeType::Deleted,
-            content_hash: Hash::from_string("0".repeat(64)), // Empty hash for deleted files
+            content_hash: Hash::zero(), // Empty hash for deleted files
             previous_hash: Some(previous_hash),
             mode: 0o644,
         }
     }
+    
+    pub fn new_renamed(from: String, path: String, content_hash: Hash) -> Self {
+        Self {
+            path,
+            change_type: ChangeType::Renamed { from },
+            previous_hash: Some(content_hash.clone()),
+            content_hash,
+            mode: 0o644,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TreeObject {
     pub id: Hash,
-    pub entries: HashMap<String, TreeEntry>,
+    pub entries: BTreeMap<String, TreeEntry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -170,7 +183,7 @@ impl TreeObject {
     pub fn new() -> Self {
         Self {
             id: Hash::from_string("".to_string()), // Will be calculated when stored
-            entries: HashMap::new(),
+            entries: BTreeMap::new(),
         }
     }
     
@@ -183,11 +196,57 @@ impl TreeObject {
     }
     
     pub fn calculate_hash(&mut self) {
-        let serialized = serde_json::to_vec(self).unwrap();
+        // Only the entries are hashed so the id never depends on itself
+        let serialized = serde_json::to_vec(&self.entries).unwrap();
         self.id = Hash::new(&serialized);
     }
+    
+    /// Builds the nested trees for a flat path -> file snapshot.
+    ///
+    /// Returns the root tree hash and every tree object (root last) that
+    /// needs to be persisted for the root to be resolvable.
+    pub fn build_from_snapshot(snapshot: &Snapshot) -> (Hash, Vec<TreeObject>) {
+        let mut trees = Vec::new();
+        let root = Self::build_level(snapshot.iter().map(|(p, e)| (p.as_str(), e)), &mut trees);
+        (root, trees)
+    }
+    
+    fn build_level<'a>(
+        files: impl Iterator<Item = (&'a str, &'a SnapshotEntry)>,
+        trees: &mut Vec<TreeObject>,
+    ) -> Hash {
+        let mut tree = TreeObject::new();
+        let mut subdirs: BTreeMap<&str, Vec<(&str, &SnapshotEntry)>> = BTreeMap::new();
+        
+        for (path, entry) in files {
+            match path.split_once('/') {
+                Some((dir, rest)) => subdirs.entry(dir).or_default().push((rest, entry)),
+                None => tree.add_blob(path.to_string(), entry.hash.clone(), entry.mode),
+            }
+        }
+        
+        for (dir, children) in subdirs {
+            let hash = Self::build_level(children.into_iter(), trees);
+            tree.add_tree(dir.to_string(), hash);
+        }
+        
+        tree.calculate_hash();
+        let id = tree.id.clone();
+        trees.push(tree);
+        id
+    }
 }
 
+/// A single file as recorded in a flattened tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    pub hash: Hash,
+    pub mode: u32,
+}
+
+/// The full project state as a flat map of normalized paths to files.
+pub type Snapshot = BTreeMap<String, SnapshotEntry>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlobObject {
     pub id: Hash,
@@ -195,18 +254,11 @@ pub struct BlobObject {
 }
 
 impl BlobObject {
+    #[cfg(test)]
     pub fn new(content: Vec<u8>) -> Self {
         let id = Hash::new(&content);
         Self { id, content }
     }
-    
-    pub fn from_string(content: String) -> Self {
-        Self::new(content.into_bytes())
-    }
-    
-    pub fn as_string(&self) -> Result<String, std::string::FromUtf8Error> {
-        String::from_utf8(self.content.clone())
-    }
 }
 
 #[cfg(test)]
@@ -225,6 +277,16 @@ mod tests {
         assert_ne!(hash1, hash3);
     }
     
+    #[test]
+    fn test_commit_id_parse() {
+        let hash = Hash::new(b"commit");
+        let id = CommitId::parse(hash.as_str()).unwrap();
+        assert_eq!(id, CommitId::from_hash(hash));
+        
+        assert!(CommitId::parse("not-a-commit").is_err());
+        assert!(CommitId::parse("67e55044-10b1-426f-9247-bb680e5fe0c8").is_err());
+    }
+    
     #[test]
     fn test_file_change_creation() {
         let hash = Hash::new(b"content");
@@ -242,12 +304,12 @@ mod tests {
     
     #[test]
     fn test_blob_object() {
-        let content = "Hello, world!".to_string();
-        let blob = BlobObject::from_string(content.clone());
+        let content = b"Hello, world!".to_vec();
+        let blob = BlobObject::new(content.clone());
         
-        assert_eq!(blob.as_string().unwrap(), content);
+        assert_eq!(blob.content, content);
         
-        let blob2 = BlobObject::from_string(content);
+        let blob2 = BlobObject::new(content);
         assert_eq!(blob.id, blob2.id);
     }
     
@@ -262,4 +324,26 @@ mod tests {
         assert!(!tree.id.as_str().is_empty());
         assert!(tree.entries.contains_key("file.txt"));
     }
+    
+    #[test]
+    fn test_build_from_snapshot() {
+        let mut snapshot = Snapshot::new();
+        let entry = SnapshotEntry { hash: Hash::new(b"content"), mode: 0o644 };
+        snapshot.insert("README.md".to_string(), entry.clone());
+        snapshot.insert("src/main.rs".to_string(), entry.clone());
+        snapshot.insert("src/core/mod.rs".to_string(), entry);
+        
+        let (root, trees) = TreeObject::build_from_snapshot(&snapshot);
+        
+        // src/core, src and the root
+        assert_eq!(trees.len(), 3);
+        let root_tree = trees.last().unwrap();
+        assert_eq!(root_tree.id, root);
+        assert!(matches!(root_tree.entries.get("README.md"), Some(TreeEntry::Blob { .. })));
+        assert!(matches!(root_tree.entries.get("src"), Some(TreeEntry::Tree { .. })));
+        
+        // Identical snapshots always produce the same root
+        let (root_again, _) = TreeObject::build_from_snapshot(&snapshot);
+        assert_eq!(root, root_again);
+    }
 }
\ No newline at end of file
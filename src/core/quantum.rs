@@ -0,0 +1,344 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use crate::core::objects::{CommitId, Hash};
+use crate::error::{FlowError, Result};
+
+/// Key under `Stream::metadata` holding a quantum stream's state.
+pub const METADATA_KEY: &str = "quantum";
+
+/// Variants of a quantum stream and the evidence gathered for them.
+///
+/// Variant heads live in ref files under `refs/quantum/<stream>/`; this
+/// state only records what is needed to compare and collapse them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuantumState {
+    /// Commit every variant started from
+    pub base: Option<CommitId>,
+    pub variants: BTreeMap<String, QuantumVariant>,
+    pub collapsed: Option<Collapse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantumVariant {
+    pub created_at: DateTime<Utc>,
+    pub evidence: Vec<Evidence>,
+}
+
+/// Record of the variant chosen when the stream was collapsed. Rejected
+/// variants keep their heads under `refs/archive/quantum/<stream>/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collapse {
+    pub variant: String,
+    pub head: Option<CommitId>,
+    pub rejected: Vec<String>,
+    pub collapsed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvidenceFormat {
+    Junit,
+    Json,
+}
+
+/// Test results attached to a variant by `flow quantum observe`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Evidence {
+    /// File the results were read from, as given by the user
+    pub source: String,
+    pub format: EvidenceFormat,
+    /// Blob holding the original report
+    pub report: Hash,
+    /// Variant head the results were observed at
+    pub commit: Option<CommitId>,
+    pub passed: u32,
+    pub failed: u32,
+    pub skipped: u32,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Counts parsed from a test report.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TestSummary {
+    pub passed: u32,
+    pub failed: u32,
+    pub skipped: u32,
+}
+
+impl TestSummary {
+    /// Fraction of executed (non-skipped) tests that passed.
+    pub fn pass_rate(&self) -> f32 {
+        let executed = self.passed + self.failed;
+        if executed == 0 {
+            0.0
+        } else {
+            self.passed as f32 / executed as f32
+        }
+    }
+}
+
+impl Evidence {
+    pub fn summary(&self) -> TestSummary {
+        TestSummary { passed: self.passed, failed: self.failed, skipped: self.skipped }
+    }
+}
+
+impl QuantumVariant {
+    pub fn new() -> Self {
+        Self {
+            created_at: Utc::now(),
+            evidence: Vec::new(),
+        }
+    }
+    
+    /// Results of the most recent observation, if any.
+    pub fn latest_summary(&self) -> Option<TestSummary> {
+        self.evidence.iter()
+            .max_by_key(|evidence| evidence.recorded_at)
+            .map(|evidence| evidence.summary())
+    }
+}
+
+impl Default for QuantumVariant {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QuantumState {
+    pub fn new(base: Option<CommitId>, variants: &[String]) -> Result<Self> {
+        if variants.len() < 2 {
+            return Err(FlowError::InvalidQuantumFeature(
+                "A quantum stream needs at least two variants".to_string()
+            ));
+        }
+        
+        let mut state = Self { base, ..Default::default() };
+        for name in variants {
+            Self::validate_variant_name(name)?;
+            
+            if state.variants.insert(name.clone(), QuantumVariant::new()).is_some() {
+                return Err(FlowError::InvalidQuantumFeature(format!("Duplicate variant: {}", name)));
+            }
+        }
+        
+        Ok(state)
+    }
+    
+    /// Reads the state stored in a stream's metadata, if it has any.
+    pub fn from_metadata(metadata: &serde_json::Value) -> Result<Option<Self>> {
+        match metadata.get(METADATA_KEY) {
+            Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+            None => Ok(None),
+        }
+    }
+    
+    pub fn store_in(&self, metadata: &mut serde_json::Value) -> Result<()> {
+        if !metadata.is_object() {
+            *metadata = serde_json::json!({});
+        }
+        metadata[METADATA_KEY] = serde_json::to_value(self)?;
+        Ok(())
+    }
+    
+    /// Variant names become a single ref path component.
+    pub fn validate_variant_name(name: &str) -> Result<()> {
+        let valid = !name.is_empty()
+            && name.len() <= 100
+            && !name.starts_with('.')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        
+        if !valid {
+            return Err(FlowError::InvalidQuantumFeature(format!(
+                "{} (variant names may only use letters, digits, '-', '_' and '.')",
+                name
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed.is_some()
+    }
+    
+    pub fn variant(&self, name: &str) -> Result<&QuantumVariant> {
+        self.variants.get(name)
+            .ok_or_else(|| FlowError::InvalidQuantumFeature(format!("Unknown variant: {}", name)))
+    }
+    
+    pub fn variant_mut(&mut self, name: &str) -> Result<&mut QuantumVariant> {
+        self.variants.get_mut(name)
+            .ok_or_else(|| FlowError::InvalidQuantumFeature(format!("Unknown variant: {}", name)))
+    }
+    
+    /// The variant whose latest observation has the best pass rate (more
+    /// passing tests breaks ties). Fails if no variant has been observed or
+    /// the best result is shared, since the choice would be arbitrary.
+    pub fn best_variant(&self) -> Result<String> {
+        let mut ranked: Vec<(&String, TestSummary)> = self.variants.iter()
+            .filter_map(|(name, variant)| variant.latest_summary().map(|summary| (name, summary)))
+            .collect();
+        
+        if ranked.is_empty() {
+            return Err(FlowError::InvalidQuantumFeature(
+                "No variant has been observed yet; name the variant to collapse to".to_string()
+            ));
+        }
+        
+        let key = |summary: &TestSummary| (summary.pass_rate(), summary.passed);
+        ranked.sort_by(|a, b| key(&b.1).partial_cmp(&key(&a.1)).unwrap_or(std::cmp::Ordering::Equal));
+        
+        if ranked.len() > 1 && key(&ranked[0].1) == key(&ranked[1].1) {
+            return Err(FlowError::InvalidQuantumFeature(format!(
+                "Variants {} and {} have equal evidence; name the variant to collapse to",
+                ranked[0].0, ranked[1].0
+            )));
+        }
+        
+        Ok(ranked[0].0.clone())
+    }
+}
+
+/// Parses a test report. JUnit XML is recognised by a leading `<`;
+/// anything else is read as JSON, either `{"passed": n, "failed": n,
+/// "skipped": n}` or `{"tests": [{"name": "...", "status": "passed"}]}`.
+pub fn parse_test_report(content: &str) -> Result<(EvidenceFormat, TestSummary)> {
+    if content.trim_start().starts_with('<') {
+        Ok((EvidenceFormat::Junit, parse_junit(content)?))
+    } else {
+        Ok((EvidenceFormat::Json, parse_json_report(content)?))
+    }
+}
+
+fn parse_junit(content: &str) -> Result<TestSummary> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|e| FlowError::InvalidQuantumFeature(format!("Invalid JUnit XML: {}", e)))?;
+    
+    let mut summary = TestSummary::default();
+    let mut found = false;
+    
+    for testcase in document.descendants().filter(|node| node.has_tag_name("testcase")) {
+        found = true;
+        let has_child = |tag: &str| testcase.children().any(|child| child.has_tag_name(tag));
+        
+        if has_child("failure") || has_child("error") {
+            summary.failed += 1;
+        } else if has_child("skipped") {
+            summary.skipped += 1;
+        } else {
+            summary.passed += 1;
+        }
+    }
+    
+    if !found {
+        return Err(FlowError::InvalidQuantumFeature("JUnit report contains no test cases".to_string()));
+    }
+    
+    Ok(summary)
+}
+
+fn parse_json_report(content: &str) -> Result<TestSummary> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    let invalid = || FlowError::InvalidQuantumFeature(
+        "JSON report needs passed/failed/skipped counts or a tests array".to_string()
+    );
+    
+    if let Some(tests) = value.get("tests").and_then(|tests| tests.as_array()) {
+        let mut summary = TestSummary::default();
+        
+        for test in tests {
+            match test.get("status").and_then(|status| status.as_str()) {
+                Some("passed") | Some("pass") | Some("ok") => summary.passed += 1,
+                Some("failed") | Some("fail") | Some("error") => summary.failed += 1,
+                Some("skipped") | Some("skip") => summary.skipped += 1,
+                _ => return Err(invalid()),
+            }
+        }
+        
+        return Ok(summary);
+    }
+    
+    let count = |key: &str| value.get(key).and_then(|n| n.as_u64()).map(|n| n as u32);
+    match (count("passed"), count("failed")) {
+        (Some(passed), Some(failed)) => Ok(TestSummary {
+            passed,
+            failed,
+            skipped: count("skipped").unwrap_or(0),
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_junit_report() {
+        let report = r#"<?xml version="1.0"?>
+<testsuites>
+  <testsuite name="auth" tests="4">
+    <testcase name="login"/>
+    <testcase name="logout"/>
+    <testcase name="expiry"><failure message="boom"/></testcase>
+    <testcase name="sso"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+        
+        let (format, summary) = parse_test_report(report).unwrap();
+        assert_eq!(format, EvidenceFormat::Junit);
+        assert_eq!(summary, TestSummary { passed: 2, failed: 1, skipped: 1 });
+        assert!((summary.pass_rate() - 2.0 / 3.0).abs() < f32::EPSILON);
+    }
+    
+    #[test]
+    fn test_parse_json_reports() {
+        let (format, counts) = parse_test_report(r#"{"passed": 9, "failed": 1}"#).unwrap();
+        assert_eq!(format, EvidenceFormat::Json);
+        assert_eq!(counts, TestSummary { passed: 9, failed: 1, skipped: 0 });
+        
+        let (_, listed) = parse_test_report(
+            r#"{"tests": [{"name": "a", "status": "passed"}, {"name": "b", "status": "skipped"}]}"#
+        ).unwrap();
+        assert_eq!(listed, TestSummary { passed: 1, failed: 0, skipped: 1 });
+        
+        assert!(parse_test_report(r#"{"ok": true}"#).is_err());
+        assert!(parse_test_report("<testsuite/>").is_err());
+    }
+    
+    #[test]
+    fn test_quantum_state_variants() {
+        let variants = vec!["fast".to_string(), "safe".to_string()];
+        let mut state = QuantumState::new(None, &variants).unwrap();
+        
+        assert!(QuantumState::new(None, &variants[..1]).is_err());
+        assert!(QuantumState::new(None, &["a".to_string(), "a".to_string()]).is_err());
+        assert!(QuantumState::new(None, &["a".to_string(), "b/c".to_string()]).is_err());
+        
+        // Nothing observed yet, then a clear winner
+        assert!(state.best_variant().is_err());
+        
+        let evidence = |passed, failed| Evidence {
+            source: "results.json".to_string(),
+            format: EvidenceFormat::Json,
+            report: Hash::new(b"report"),
+            commit: None,
+            passed,
+            failed,
+            skipped: 0,
+            recorded_at: Utc::now(),
+        };
+        state.variant_mut("fast").unwrap().evidence.push(evidence(8, 2));
+        state.variant_mut("safe").unwrap().evidence.push(evidence(10, 0));
+        assert_eq!(state.best_variant().unwrap(), "safe");
+        
+        // Round-trips through stream metadata
+        let mut metadata = serde_json::Value::Null;
+        state.store_in(&mut metadata).unwrap();
+        let loaded = QuantumState::from_metadata(&metadata).unwrap().unwrap();
+        assert_eq!(loaded.variants.len(), 2);
+        assert_eq!(loaded.variant("safe").unwrap().evidence[0].passed, 10);
+    }
+}
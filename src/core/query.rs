@@ -0,0 +1,478 @@
+use crate::core::commit::FlowCommit;
+use crate::core::objects::{ChangeType, CommitId};
+use crate::error::{FlowError, Result};
+use crate::utils::fs::normalize_repo_path;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
+
+/// How a commit's confidence compares to a bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn holds(self, value: f32, bound: f32) -> bool {
+        match self {
+            Comparison::Less => value < bound,
+            Comparison::LessOrEqual => value <= bound,
+            Comparison::Greater => value > bound,
+            Comparison::GreaterOrEqual => value >= bound,
+            Comparison::Equal => (value - bound).abs() < f32::EPSILON,
+        }
+    }
+}
+
+/// A filter over the commits `flow log` shows.
+///
+/// Written as `field:value` terms combined with `and`, `or`, `not` and
+/// parentheses, e.g. `tag:bugfix and (confidence<0.5 or not author:ci)`.
+/// Values with spaces go in quotes.
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// The intention or one of its sub-intentions carries the tag
+    Tag(String),
+    /// Case-insensitive substring of the intention's author
+    Author(String),
+    /// Case-insensitive regex over the goal
+    Goal(Regex),
+    /// The commit changed the path or something under it, under any
+    /// earlier name it had before being moved
+    Path(String),
+    Since(DateTime<Utc>),
+    Until(DateTime<Utc>),
+    Confidence(Comparison, f32),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    pub fn goal(pattern: &str) -> Result<Self> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Query::Goal)
+            .map_err(|e| FlowError::InvalidQuery(format!("goal pattern {}: {}", pattern, e)))
+    }
+    
+    pub fn path(path: &str) -> Self {
+        Query::Path(normalize_repo_path(path))
+    }
+    
+    /// Commits from `date` on: `YYYY-MM-DD`, RFC 3339, or an age such as
+    /// `12h`, `3d` or `2w`.
+    pub fn since(date: &str) -> Result<Self> {
+        parse_date(date, false).map(Query::Since)
+    }
+    
+    /// Commits up to `date`; a bare day includes the whole day.
+    pub fn until(date: &str) -> Result<Self> {
+        parse_date(date, true).map(Query::Until)
+    }
+    
+    /// All of `queries`, or `None` when there are none.
+    pub fn all(queries: Vec<Query>) -> Option<Self> {
+        queries.into_iter().reduce(|all, query| Query::And(Box::new(all), Box::new(query)))
+    }
+    
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser { tokens, position: 0 };
+        
+        let query = parser.or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(FlowError::InvalidQuery(format!(
+                "unexpected {} in '{}'", parser.tokens[parser.position].describe(), expression
+            )));
+        }
+        
+        Ok(query)
+    }
+    
+    /// The commits of `log` (newest first, as `Repository::get_log` returns
+    /// them) that match, in the same order.
+    pub fn filter(&self, log: Vec<FlowCommit>) -> Vec<FlowCommit> {
+        let mut paths = Vec::new();
+        self.collect_paths(&mut paths);
+        let touched: HashMap<&str, HashSet<CommitId>> = paths.into_iter()
+            .map(|path| (path, commits_touching(path, &log)))
+            .collect();
+        
+        log.into_iter()
+            .filter(|commit| self.matches(commit, &touched))
+            .collect()
+    }
+    
+    fn collect_paths<'a>(&'a self, paths: &mut Vec<&'a str>) {
+        match self {
+            Query::Path(path) => paths.push(path),
+            Query::And(left, right) | Query::Or(left, right) => {
+                left.collect_paths(paths);
+                right.collect_paths(paths);
+            }
+            Query::Not(query) => query.collect_paths(paths),
+            _ => {}
+        }
+    }
+    
+    fn matches(&self, commit: &FlowCommit, touched: &HashMap<&str, HashSet<CommitId>>) -> bool {
+        let intention = &commit.intention;
+        
+        match self {
+            Query::Tag(tag) => intention.tags.iter()
+                .chain(commit.sub_intentions.iter().flat_map(|sub| &sub.intention.tags))
+                .any(|candidate| candidate.eq_ignore_ascii_case(tag)),
+            Query::Author(author) => intention.author.to_lowercase().contains(&author.to_lowercase()),
+            Query::Goal(regex) => regex.is_match(&intention.goal),
+            Query::Path(path) => touched.get(path.as_str()).is_some_and(|ids| ids.contains(&commit.id)),
+            Query::Since(since) => commit.timestamp >= *since,
+            Query::Until(until) => commit.timestamp <= *until,
+            Query::Confidence(comparison, bound) => comparison.holds(intention.confidence, *bound),
+            Query::And(left, right) => left.matches(commit, touched) && right.matches(commit, touched),
+            Query::Or(left, right) => left.matches(commit, touched) || right.matches(commit, touched),
+            Query::Not(query) => !query.matches(commit, touched),
+        }
+    }
+}
+
+/// Walks `log` from newest to oldest, picking up a file's earlier names
+/// from the renames that gave it its current one.
+fn commits_touching(path: &str, log: &[FlowCommit]) -> HashSet<CommitId> {
+    let covers = |name: &str, candidate: &str| {
+        name.is_empty() || candidate == name || candidate.strip_prefix(name).is_some_and(|rest| rest.starts_with('/'))
+    };
+    
+    let mut names = vec![path.to_string()];
+    let mut touching = HashSet::new();
+    
+    for commit in log {
+        let mut earlier_names = Vec::new();
+        
+        for change in &commit.changes {
+            let from = match &change.change_type {
+                ChangeType::Renamed { from } => Some(from),
+                _ => None,
+            };
+            
+            if names.iter().any(|name| covers(name, &change.path)) {
+                touching.insert(commit.id.clone());
+                if let Some(from) = from {
+                    earlier_names.push(from.clone());
+                }
+            } else if from.is_some_and(|from| names.iter().any(|name| covers(name, from))) {
+                touching.insert(commit.id.clone());
+            }
+        }
+        
+        for name in earlier_names {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    
+    touching
+}
+
+fn parse_date(text: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    let invalid = || FlowError::InvalidQuery(format!(
+        "invalid date '{}' (expected YYYY-MM-DD, RFC 3339, or an age like 3d)", text
+    ));
+    
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    
+    if let Ok(day) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let time = if end_of_day {
+            day.and_hms_nano_opt(23, 59, 59, 999_999_999)
+        } else {
+            day.and_hms_opt(0, 0, 0)
+        };
+        return time.map(|time| time.and_utc()).ok_or_else(invalid);
+    }
+    
+    let split = text.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let amount: i64 = text[..split].parse().map_err(|_| invalid())?;
+    let age = match &text[split..] {
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => return Err(invalid()),
+    };
+    
+    age.and_then(|age| Utc::now().checked_sub_signed(age))
+        .ok_or_else(|| FlowError::InvalidQuery(format!("age '{}' is out of range", text)))
+}
+
+#[derive(Debug)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(Query),
+}
+
+impl Token {
+    fn describe(&self) -> &'static str {
+        match self {
+            Token::Open => "'('",
+            Token::Close => "')'",
+            Token::And => "'and'",
+            Token::Or => "'or'",
+            Token::Not => "'not'",
+            Token::Term(_) => "filter",
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    
+    while i < chars.len() {
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            c if c.is_alphabetic() => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                
+                let keyword = match word.as_str() {
+                    "and" => Some(Token::And),
+                    "or" => Some(Token::Or),
+                    "not" => Some(Token::Not),
+                    _ => None,
+                };
+                if let (Some(keyword), false) = (keyword, chars.get(i).is_some_and(|c| ":<>=".contains(*c))) {
+                    tokens.push(keyword);
+                    continue;
+                }
+                
+                let operator_start = i;
+                while i < chars.len() && ":<>=".contains(chars[i]) {
+                    i += 1;
+                }
+                let operator: String = chars[operator_start..i].iter().collect();
+                
+                let value = read_value(&chars, &mut i)?;
+                tokens.push(Token::Term(term(&word, &operator, &value)?));
+            }
+            c => return Err(FlowError::InvalidQuery(format!("unexpected '{}' in '{}'", c, expression))),
+        }
+    }
+    
+    Ok(tokens)
+}
+
+/// A quoted value, or everything up to whitespace or a `)` that closes a
+/// group rather than one opened inside the value (as in `goal:fix(es)?`).
+fn read_value(chars: &[char], i: &mut usize) -> Result<String> {
+    if let Some(&quote) = chars.get(*i).filter(|c| **c == '"' || **c == '\'') {
+        let start = *i + 1;
+        let end = chars[start..].iter().position(|c| *c == quote)
+            .map(|offset| start + offset)
+            .ok_or_else(|| FlowError::InvalidQuery("unterminated quote".to_string()))?;
+        *i = end + 1;
+        return Ok(chars[start..end].iter().collect());
+    }
+    
+    let start = *i;
+    let mut depth = 0;
+    while *i < chars.len() && !chars[*i].is_whitespace() {
+        match chars[*i] {
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        *i += 1;
+    }
+    
+    Ok(chars[start..*i].iter().collect())
+}
+
+fn term(field: &str, operator: &str, value: &str) -> Result<Query> {
+    if value.is_empty() {
+        return Err(FlowError::InvalidQuery(format!("missing value for {}", field)));
+    }
+    
+    let comparison = match operator {
+        ":" | "=" => Comparison::Equal,
+        "<" => Comparison::Less,
+        "<=" => Comparison::LessOrEqual,
+        ">" => Comparison::Greater,
+        ">=" => Comparison::GreaterOrEqual,
+        _ => return Err(FlowError::InvalidQuery(format!("expected ':' after {}", field))),
+    };
+    if comparison != Comparison::Equal && field != "confidence" {
+        return Err(FlowError::InvalidQuery(format!("{} can only be matched with ':'", field)));
+    }
+    
+    match field {
+        "tag" => Ok(Query::Tag(value.to_string())),
+        "author" => Ok(Query::Author(value.to_string())),
+        "goal" => Query::goal(value),
+        "path" => Ok(Query::path(value)),
+        "since" => Query::since(value),
+        "until" => Query::until(value),
+        "confidence" => {
+            let bound: f32 = value.parse()
+                .map_err(|_| FlowError::InvalidQuery(format!("invalid confidence '{}'", value)))?;
+            if !(0.0..=1.0).contains(&bound) {
+                return Err(FlowError::InvalidConfidenceScore(bound));
+            }
+            Ok(Query::Confidence(comparison, bound))
+        }
+        _ => Err(FlowError::InvalidQuery(format!(
+            "unknown field '{}' (expected tag, author, goal, path, since, until or confidence)", field
+        ))),
+    }
+}
+
+/// Precedence from loosest to tightest: `or`, `and`, `not`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Query> {
+        let mut query = self.and()?;
+        while self.eat(|token| matches!(token, Token::Or)) {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+    
+    fn and(&mut self) -> Result<Query> {
+        let mut query = self.not()?;
+        while self.eat(|token| matches!(token, Token::And)) {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+    
+    fn not(&mut self) -> Result<Query> {
+        if self.eat(|token| matches!(token, Token::Not)) {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        
+        if self.eat(|token| matches!(token, Token::Open)) {
+            let query = self.or()?;
+            if !self.eat(|token| matches!(token, Token::Close)) {
+                return Err(FlowError::InvalidQuery("missing ')'".to_string()));
+            }
+            return Ok(query);
+        }
+        
+        match self.tokens.get(self.position) {
+            Some(Token::Term(query)) => {
+                let query = query.clone();
+                self.position += 1;
+                Ok(query)
+            }
+            Some(token) => Err(FlowError::InvalidQuery(format!("expected a filter, found {}", token.describe()))),
+            None => Err(FlowError::InvalidQuery("expected a filter at the end".to_string())),
+        }
+    }
+    
+    fn eat(&mut self, expected: impl Fn(&Token) -> bool) -> bool {
+        let found = self.tokens.get(self.position).is_some_and(expected);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::intention::Intention;
+    use crate::core::objects::{FileChange, Hash};
+    
+    fn commit(goal: &str, tags: &[&str], confidence: f32, changes: Vec<FileChange>) -> FlowCommit {
+        let mut intention = Intention::new(goal.to_string(), None, None, confidence);
+        intention.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        FlowCommit::new(intention, changes, Hash::zero(), Vec::new())
+    }
+    
+    fn goals(query: &str, log: &[FlowCommit]) -> Vec<String> {
+        Query::parse(query).unwrap()
+            .filter(log.to_vec())
+            .into_iter()
+            .map(|commit| commit.intention.goal)
+            .collect()
+    }
+    
+    #[test]
+    fn test_boolean_expressions() {
+        let log = vec![
+            commit("Fix login crash", &["bugfix", "security"], 0.4, Vec::new()),
+            commit("Add export", &["feature"], 0.9, Vec::new()),
+            commit("Fix typo", &["bugfix"], 0.95, Vec::new()),
+        ];
+        
+        assert_eq!(goals("tag:bugfix and confidence<0.5", &log), vec!["Fix login crash"]);
+        assert_eq!(goals("tag:feature or confidence>=0.95", &log), vec!["Add export", "Fix typo"]);
+        assert_eq!(goals("not tag:bugfix", &log), vec!["Add export"]);
+        assert_eq!(goals("goal:'fix (login|typo)' and not (tag:security or confidence<0.2)", &log), vec!["Fix typo"]);
+        assert_eq!(goals("goal:^(add|fix)\\s+e", &log), vec!["Add export"]);
+        assert_eq!(goals("TAG:Security", &log), vec!["Fix login crash"]);
+    }
+    
+    #[test]
+    fn test_path_follows_renames() {
+        let hash = Hash::new(b"content");
+        // Newest first, as get_log returns them
+        let log = vec![
+            commit("Edit parser", &[], 0.8, vec![FileChange::new_modified("src/parser.rs".to_string(), hash.clone(), hash.clone())]),
+            commit("Move parser", &[], 0.8, vec![FileChange::new_renamed("parse.rs".to_string(), "src/parser.rs".to_string(), hash.clone())]),
+            commit("Edit lexer", &[], 0.8, vec![FileChange::new_modified("lexer.rs".to_string(), hash.clone(), hash.clone())]),
+            commit("Create parser", &[], 0.8, vec![FileChange::new_added("parse.rs".to_string(), hash.clone())]),
+        ];
+        
+        assert_eq!(goals("path:src/parser.rs", &log), vec!["Edit parser", "Move parser", "Create parser"]);
+        assert_eq!(goals("path:src", &log), vec!["Edit parser", "Move parser", "Create parser"]);
+        assert_eq!(goals("path:./parse.rs", &log), vec!["Move parser", "Create parser"]);
+    }
+    
+    #[test]
+    fn test_dates() {
+        let log = vec![commit("Now", &[], 0.8, Vec::new())];
+        
+        assert_eq!(goals("since:1d", &log), vec!["Now"]);
+        assert!(goals("until:1d", &log).is_empty());
+        assert_eq!(goals("since:2020-01-01 and until:2999-12-31", &log), vec!["Now"]);
+        assert!(matches!(Query::since("yesterday"), Err(FlowError::InvalidQuery(_))));
+    }
+    
+    #[test]
+    fn test_invalid_queries() {
+        for query in ["", "tag:", "tag<x", "size:3", "(tag:a", "tag:a and", "tag:a tag:b", "goal:'open", "goal:(", "confidence<high", "since:99999999d", "since:999999999999999d", "until:99999999999999w"] {
+            assert!(Query::parse(query).is_err(), "{} should not parse", query);
+        }
+        assert!(matches!(Query::parse("confidence>2"), Err(FlowError::InvalidConfidenceScore(_))));
+        assert!(matches!(Query::since("999999999999999d"), Err(FlowError::InvalidQuery(_))));
+    }
+}
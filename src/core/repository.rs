@@ -1,24 +1,113 @@
 use crate::error::{FlowError, Result};
 use crate::core::{
-    intention::Intention,
+    intention::{Intention, SubIntention},
     commit::FlowCommit,
-    objects::{CommitId, Hash, FileChange},
+    fsck::{FsckIssue, FsckReport, IssueKind},
+    objects::{ChangeType, CommitId, Hash, FileChange, Snapshot, SnapshotEntry, TreeEntry, TreeObject},
+    quantum::{self, Collapse, Evidence, QuantumState},
+    stream::{Stream, StreamMode},
+    tags::TagTaxonomy,
 };
 use crate::storage::{
-    object_store::ObjectStore,
-    index::Index,
+    encoding::ObjectKind,
+    object_store::{GcReport, ObjectStore},
+    index::{Index, IndexEntry},
     config::Config,
+    migration,
+    stream_store::StreamStore,
+    keys::KeyStore,
+    lock::RepoLock,
+    reflog::{Reflog, ReflogEntry},
+    oplog::{OpLog, Operation, RepoState},
 };
+use ed25519_dalek::SigningKey;
+use crate::utils::fs::{file_mode, list_tracked_files, normalize_path, normalize_repo_path, set_file_mode, write_atomic, Pathspec};
+use crate::utils::ignore_rules::{IgnoreMatch, IgnoreRules};
+use crate::utils::merge::merge3;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 use tracing::{info, debug, warn};
+use walkdir::WalkDir;
 
 const FLOWVERSION_DIR: &str = ".flowversion";
+const MERGE_HEAD_FILE: &str = "MERGE_HEAD";
+const MERGE_CONFLICTS_FILE: &str = "MERGE_CONFLICTS";
+const LOGS_DIR: &str = "logs";
+
+/// Working tree state relative to the index and HEAD.
+#[derive(Debug, Default)]
+pub struct RepositoryStatus {
+    pub stream: Option<String>,
+    /// Quantum variant HEAD is on, if any
+    pub variant: Option<String>,
+    /// Index entries that differ from HEAD
+    pub staged: Vec<FileChange>,
+    /// Tracked files whose content differs from what would be committed
+    pub modified: Vec<String>,
+    /// Tracked files missing from the working tree
+    pub deleted: Vec<String>,
+    pub untracked: Vec<String>,
+    /// Commit being merged in, while a conflicted merge is in progress
+    pub merging: Option<CommitId>,
+    /// Conflicted paths not yet resolved with `flow add`
+    pub unmerged: Vec<String>,
+}
+
+impl RepositoryStatus {
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty()
+            && self.modified.is_empty()
+            && self.deleted.is_empty()
+            && self.untracked.is_empty()
+            && self.merging.is_none()
+    }
+}
+
+/// Which changes `add_paths` stages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddMode {
+    /// New, modified and deleted files matching the pathspecs
+    Paths,
+    /// Every change in the working tree (`-A`), or under the pathspecs
+    All,
+    /// Changes to tracked files only (`-u`), or to those under the pathspecs
+    Update,
+}
+
+/// A path `add_paths` staged, or would stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedPath {
+    pub path: String,
+    /// The file is gone from the working tree, so its removal is staged
+    pub removed: bool,
+}
+
+/// What `merge_stream` did to the current stream.
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    /// The source is already contained in the current stream
+    UpToDate,
+    /// The current stream was moved forward to the source head
+    FastForward(CommitId),
+    /// A two-parent merge commit was recorded
+    Merged(CommitId),
+}
 
 pub struct Repository {
     root_path: PathBuf,
     object_store: ObjectStore,
+    stream_store: StreamStore,
     index: Index,
     config: Config,
+    /// Key new commits are signed with; with `sign_commits` and no key
+    /// set, the user's key is used
+    signing_key: Option<SigningKey>,
+    /// Held for the repository's lifetime when opened with `open_locked`
+    _lock: Option<RepoLock>,
+    /// State when the lock was taken or the last operation was recorded
+    op_start: Mutex<Option<RepoState>>,
 }
 
 impl Repository {
@@ -50,6 +139,7 @@ impl Repository {
             flow_dir.join("HEAD"),
             "ref: refs/streams/main\n"
         )?;
+        StreamStore::new(&flow_dir.join("refs").join("streams")).save(&Stream::main_stream())?;
         
         info!("Repository initialized successfully");
         Ok(())
@@ -61,75 +151,455 @@ impl Repository {
     }
     
     pub async fn open(path: &Path) -> Result<Self> {
-        let flow_dir = path.join(FLOWVERSION_DIR);
+        if !Self::is_initialized(path)? {
+            return Err(FlowError::RepoNotInitialized);
+        }
         
+        Self::load(path, None).await
+    }
+    
+    /// Opens the repository holding `.flowversion/lock`, for commands that
+    /// change refs, the index or objects. The lock is taken before anything
+    /// is read, so no other process can change the state underneath.
+    pub async fn open_locked(path: &Path) -> Result<Self> {
         if !Self::is_initialized(path)? {
             return Err(FlowError::RepoNotInitialized);
         }
         
+        let lock = RepoLock::acquire(&path.join(FLOWVERSION_DIR))?;
+        let mut repo = Self::load(path, Some(lock)).await?;
+        repo.op_start = Mutex::new(Some(repo.capture_state().await?));
+        Ok(repo)
+    }
+    
+    async fn load(path: &Path, lock: Option<RepoLock>) -> Result<Self> {
+        let flow_dir = path.join(FLOWVERSION_DIR);
+        let object_store = ObjectStore::new(&flow_dir.join("objects"));
+        
+        // Migrating rewrites refs, so it needs the lock even when opening to read
+        if migration::has_legacy_commits(&flow_dir)? {
+            let _migration_lock = match lock {
+                Some(_) => None,
+                None => Some(RepoLock::acquire(&flow_dir)?),
+            };
+            let migrated = migration::migrate_legacy_commits(&flow_dir, &object_store).await?;
+            info!("Migrated {} commits to content-addressed ids", migrated);
+        }
+        
         let config = Config::load(&flow_dir.join("config.json"))?;
         let index = Index::load(&flow_dir.join("index.json"))?;
-        let object_store = ObjectStore::new(&flow_dir.join("objects"));
+        let stream_store = StreamStore::new(&flow_dir.join("refs").join("streams"));
         
         Ok(Self {
             root_path: path.to_path_buf(),
             object_store,
+            stream_store,
             index,
             config,
+            signing_key: None,
+            _lock: lock,
+            op_start: Mutex::new(None),
         })
     }
     
+    #[cfg(test)]
     pub async fn add_file(&self, file_path: &str) -> Result<()> {
         let full_path = self.root_path.join(file_path);
+        let repo_path = normalize_repo_path(file_path);
         
         if !full_path.exists() {
+            // Adding a tracked file that was removed from disk stages its deletion
+            if self.get_head_snapshot().await?.contains_key(&repo_path) {
+                debug!("Staging deletion: {}", repo_path);
+                self.index.stage_deletion(repo_path).await?;
+                return self.save_index();
+            }
+            
             return Err(FlowError::FileNotFound(file_path.to_string()));
         }
         
         debug!("Adding file to index: {}", file_path);
+        self.stage_file(repo_path).await?;
+        self.save_index()
+    }
+    
+    /// Stages every change matching `pathspecs` (files, directories or
+    /// globs) and returns the staged paths; with `dry_run` nothing is
+    /// staged. Unchanged files are skipped, as are ignored files that are
+    /// not tracked. Naming an ignored file outright is an error.
+    ///
+    /// An `intention` is recorded for every staged path the pathspecs
+    /// cover, and becomes a sub-intention of the next commit.
+    pub async fn add_paths(&self, pathspecs: &[String], mode: AddMode, intention: Option<&str>, dry_run: bool) -> Result<Vec<StagedPath>> {
+        if pathspecs.is_empty() && mode == AddMode::Paths {
+            return Err(FlowError::InvalidRepoState("Nothing specified, nothing added".to_string()));
+        }
+        if let Some(goal) = intention {
+            Intention::new(goal.to_string(), None, None, 1.0).validate()?;
+        }
+        
+        let head = self.get_head_snapshot().await?;
+        let staged_files = self.index.get_staged_files().await?;
+        let unresolved: HashSet<String> = self.merge_conflicts()?
+            .into_iter()
+            .filter(|path| !staged_files.contains_key(path))
+            .collect();
+        let (expected, _) = Self::apply_changeset(&head, staged_files);
+        let working = self.scan_working_tree(&expected)?;
+        
+        let specs = pathspecs.iter().map(|spec| Pathspec::parse(spec)).collect::<Result<Vec<_>>>()?;
+        let mut spec_matched = vec![false; specs.len()];
+        
+        let candidates: BTreeSet<&String> = match mode {
+            AddMode::Update => expected.keys().collect(),
+            AddMode::Paths | AddMode::All => expected.keys().chain(working.keys()).collect(),
+        };
+        
+        let mut changes = Vec::new();
+        for path in candidates {
+            let mut selected = specs.is_empty();
+            for (spec, matched) in specs.iter().zip(spec_matched.iter_mut()) {
+                if spec.matches(path) {
+                    *matched = true;
+                    selected = true;
+                }
+            }
+            
+            // Resolving a conflict in favour of HEAD leaves nothing to compare
+            let changed = working.get(path) != expected.get(path) || unresolved.contains(path);
+            if selected && changed {
+                changes.push(StagedPath { path: path.clone(), removed: !working.contains_key(path) });
+            }
+        }
+        
+        for (spec, matched) in specs.iter().zip(&spec_matched) {
+            if !matched {
+                let rules = self.ignore_rules()?;
+                let full_path = self.root_path.join(spec.as_str());
+                if !spec.is_glob() && full_path.exists() && rules.is_ignored(spec.as_str(), full_path.is_dir()) {
+                    return Err(FlowError::PathIgnored(spec.as_str().to_string()));
+                }
+                return Err(FlowError::FileNotFound(spec.as_str().to_string()));
+            }
+        }
+        
+        if dry_run {
+            return Ok(changes);
+        }
+        
+        for change in &changes {
+            if !change.removed {
+                self.stage_file(change.path.clone()).await?;
+            } else if head.contains_key(&change.path) {
+                self.index.stage_deletion(change.path.clone()).await?;
+            } else {
+                // Staged as new and deleted since: just forget it
+                self.index.remove_file(&change.path).await?;
+            }
+        }
+        
+        if let Some(goal) = intention {
+            for path in self.index.get_staged_files().await?.into_keys() {
+                if specs.is_empty() || specs.iter().any(|spec| spec.matches(&path)) {
+                    self.index.set_intention(&path, Some(goal.to_string())).await?;
+                }
+            }
+        }
+        self.save_index()?;
         
-        // Read file content
+        info!("Staged {} paths", changes.len());
+        Ok(changes)
+    }
+    
+    /// Stores a working tree file as a blob and records it in the index.
+    async fn stage_file(&self, repo_path: String) -> Result<()> {
+        let full_path = self.root_path.join(&repo_path);
         let content = std::fs::read(&full_path)?;
         let hash = Hash::new(&content);
+        let metadata = full_path.metadata()?;
         
-        // Store content as blob
         self.object_store.store_blob(&hash, content).await?;
+        self.index.add_file_with_mode(repo_path, hash, metadata.len(), file_mode(&metadata)).await
+    }
+    
+    /// Removes tracked files matching `pathspecs` from the index and,
+    /// unless `cached`, from the working tree. Naming a directory needs
+    /// `recursive`. Files with staged or local changes that would be lost
+    /// need `force`.
+    ///
+    /// Returns the removed paths.
+    pub async fn remove_paths(&self, pathspecs: &[String], cached: bool, recursive: bool, force: bool) -> Result<Vec<String>> {
+        let head = self.get_head_snapshot().await?;
+        let (expected, _) = Self::apply_changeset(&head, self.index.get_staged_files().await?);
+        let working = self.scan_working_tree(&expected)?;
+        
+        let mut selected = BTreeSet::new();
+        for pathspec in pathspecs {
+            let pathspec = Pathspec::parse(pathspec)?;
+            
+            let matched: Vec<String> = expected.keys().filter(|path| pathspec.matches(path)).cloned().collect();
+            if matched.is_empty() {
+                return Err(FlowError::FileNotFound(pathspec.as_str().to_string()));
+            }
+            if !recursive && !pathspec.is_glob() && !expected.contains_key(pathspec.as_str()) {
+                return Err(FlowError::InvalidRepoState(format!(
+                    "Not removing directory {} without --recursive", pathspec.as_str()
+                )));
+            }
+            selected.extend(matched);
+        }
         
-        // Add to index
-        self.index.add_file(file_path.to_string(), hash, full_path.metadata()?.len()).await?;
+        // Staged content is lost unless committed or (with --cached) still on
+        // disk; local edits are lost when the file is deleted
+        let hash_of = |snapshot: &Snapshot, path: &String| snapshot.get(path).map(|entry| entry.hash.clone());
+        let at_risk: Vec<&String> = selected.iter()
+            .filter(|path| {
+                let committed = hash_of(&head, path);
+                let staged = hash_of(&expected, path);
+                let on_disk = hash_of(&working, path);
+                if cached {
+                    staged != committed && staged != on_disk
+                } else {
+                    staged != committed || (on_disk.is_some() && on_disk != committed)
+                }
+            })
+            .collect();
         
-        Ok(())
+        if !at_risk.is_empty() && !force {
+            for path in &at_risk {
+                warn!("{} has changes that would be lost (use --cached to keep the file, or --force)", path);
+            }
+            return Err(FlowError::WorkingDirectoryNotClean);
+        }
+        
+        for path in &selected {
+            if head.contains_key(path) {
+                self.index.stage_deletion(path.clone()).await?;
+            } else {
+                self.index.remove_file(path).await?;
+            }
+            
+            let full_path = self.root_path.join(path);
+            if !cached && full_path.exists() {
+                std::fs::remove_file(&full_path)?;
+                self.prune_empty_dirs(&full_path);
+            }
+        }
+        self.save_index()?;
+        
+        info!("Removed {} paths", selected.len());
+        Ok(selected.into_iter().collect())
+    }
+    
+    /// Moves tracked files or directories in the working tree and the
+    /// index. With several sources, or when `destination` is an existing
+    /// directory, each source is moved into it. Overwriting an existing
+    /// file needs `force`.
+    ///
+    /// Each moved file remembers where it came from, so the next commit
+    /// records a rename even if the file is also edited. Returns the moved
+    /// files as (from, to) pairs.
+    pub async fn move_paths(&self, sources: &[String], destination: &str, force: bool) -> Result<Vec<(String, String)>> {
+        let head = self.get_head_snapshot().await?;
+        let staged_files = self.index.get_staged_files().await?;
+        let (expected, _) = Self::apply_changeset(&head, staged_files.clone());
+        
+        let destination = normalize_repo_path(destination);
+        let into_dir = self.root_path.join(&destination).is_dir();
+        if sources.len() > 1 && !into_dir {
+            return Err(FlowError::InvalidRepoState(format!("Destination {} is not a directory", destination)));
+        }
+        
+        let overwritable = |from: &Path, to: &Path| !to.exists() || (force && to.is_file() && from.is_file());
+        let nested = |path: &str, dir: &str| path == dir || path.starts_with(&format!("{}/", dir));
+        
+        // Each rename on disk with the tracked files it moves
+        let mut renames = Vec::new();
+        let mut targets: HashMap<String, String> = HashMap::new();
+        for source in sources {
+            let source = normalize_repo_path(source);
+            let source_path = self.root_path.join(&source);
+            let name = source.rsplit('/').next().unwrap_or(&source);
+            let target = match (into_dir, destination.is_empty()) {
+                (true, true) => name.to_string(),
+                (true, false) => format!("{}/{}", destination, name),
+                (false, _) => destination.clone(),
+            };
+            
+            if source.is_empty() || target == source || target.starts_with(&format!("{}/", source)) {
+                return Err(FlowError::InvalidRepoState(format!("Cannot move {} to {}", source, target)));
+            }
+            // A source inside another one moves along with it
+            if let Some(other) = targets.values().find(|other| nested(other, &source) || nested(&source, other)) {
+                return Err(FlowError::InvalidRepoState(format!("Cannot move both {} and {}", other, source)));
+            }
+            if let Some(other) = targets.insert(target.clone(), source.clone()) {
+                return Err(FlowError::InvalidRepoState(format!("Cannot move both {} and {} to {}", other, source, target)));
+            }
+            if !source_path.exists() {
+                return Err(FlowError::FileNotFound(source));
+            }
+            
+            let prefix = format!("{}/", source);
+            let files: Vec<&String> = expected.keys().filter(|path| **path == source || path.starts_with(&prefix)).collect();
+            if files.is_empty() {
+                return Err(FlowError::InvalidRepoState(format!("Not tracked: {}", source)));
+            }
+            
+            let target_path = self.root_path.join(&target);
+            if !overwritable(&source_path, &target_path) {
+                return Err(FlowError::InvalidRepoState(format!("Destination exists: {} (use --force to overwrite a file)", target)));
+            }
+            
+            let files: Vec<(String, String)> = files.into_iter()
+                .map(|file| (file.clone(), format!("{}{}", target, &file[source.len()..])))
+                .collect();
+            renames.push((source_path, target_path, files));
+        }
+        
+        let mut moves = Vec::new();
+        for (from_path, to_path, files) in renames {
+            // Something may have appeared there since the checks above
+            if !overwritable(&from_path, &to_path) {
+                self.save_index()?;
+                return Err(FlowError::InvalidRepoState(format!(
+                    "Destination exists: {} (use --force to overwrite a file)",
+                    to_path.strip_prefix(&self.root_path).unwrap_or(&to_path).display()
+                )));
+            }
+            
+            // Directories move whole, untracked files included
+            let renamed = match to_path.parent() {
+                Some(parent) => std::fs::create_dir_all(parent),
+                None => Ok(()),
+            }.and_then(|_| std::fs::rename(&from_path, &to_path));
+            if let Err(e) = renamed {
+                // Earlier moves already happened on disk, so the index must record them
+                self.save_index()?;
+                return Err(e.into());
+            }
+            self.prune_empty_dirs(&from_path);
+            
+            for (from, to) in files {
+                // The moved file keeps its staged content; local edits stay unstaged
+                let entry = &expected[&from];
+                let size = match staged_files.get(&from) {
+                    Some(staged) => staged.size,
+                    None => self.root_path.join(&to).metadata().map(|metadata| metadata.len()).unwrap_or(0),
+                };
+                
+                // A file moved twice before committing still comes from its first path
+                let origin = staged_files.get(&from)
+                    .and_then(|staged| staged.renamed_from.clone())
+                    .or_else(|| Some(from.clone()).filter(|from| head.contains_key(from)))
+                    .filter(|origin| *origin != to);
+                
+                if head.contains_key(&from) {
+                    self.index.stage_deletion(from.clone()).await?;
+                } else {
+                    self.index.remove_file(&from).await?;
+                }
+                self.index.add_file_with_mode(to.clone(), entry.hash.clone(), size, entry.mode).await?;
+                self.index.set_renamed_from(&to, origin).await?;
+                if let Some(intention) = staged_files.get(&from).and_then(|staged| staged.intention.clone()) {
+                    self.index.set_intention(&to, Some(intention)).await?;
+                }
+                moves.push((from, to));
+            }
+        }
+        self.save_index()?;
+        
+        info!("Moved {} files", moves.len());
+        Ok(moves)
+    }
+    
+    fn save_index(&self) -> Result<()> {
+        self.index.save(&self.root_path.join(FLOWVERSION_DIR).join("index.json"))
     }
     
     pub async fn commit_with_intention(&self, intention: Intention) -> Result<CommitId> {
         debug!("Creating commit with intention: {}", intention.goal);
         
         // Validate intention
+        let mut intention = intention;
         intention.validate()?;
         
         // Get staged changes from index
         let staged_files = self.index.get_staged_files().await?;
+        let merge_head = self.merge_head()?;
         
-        if staged_files.is_empty() {
+        if staged_files.is_empty() && merge_head.is_none() {
             warn!("No files staged for commit");
             return Err(FlowError::InvalidRepoState("No files staged for commit".to_string()));
         }
         
-        // Create file changes
-        let mut changes = Vec::new();
-        for (path, entry) in staged_files {
-            let change = FileChange::new_added(path, entry.hash);
-            changes.push(change);
+        if merge_head.is_some() {
+            // Conflicted paths must be resolved and re-added before concluding the merge
+            let unresolved: Vec<String> = self.merge_conflicts()?
+                .into_iter()
+                .filter(|path| !staged_files.contains_key(path))
+                .collect();
+            
+            if !unresolved.is_empty() {
+                return Err(FlowError::MergeConflict(unresolved));
+            }
+        }
+        
+        // Get parent commits (current HEAD, plus the merged head while merging)
+        let mut parent_commits = self.get_current_head_commits().await?;
+        if let Some(merge_head) = merge_head.clone() {
+            parent_commits.push(merge_head);
+            
+            // A merge commit's intention points back at what both sides were for
+            for parent in &parent_commits {
+                let parent_intention = self.object_store.load_commit(parent).await?.intention.id;
+                if !intention.related_intentions.contains(&parent_intention) {
+                    intention.related_intentions.push(parent_intention);
+                }
+            }
+        }
+        
+        let path_intentions: HashMap<String, String> = staged_files.iter()
+            .filter_map(|(path, entry)| entry.intention.clone().map(|goal| (path.clone(), goal)))
+            .collect();
+        
+        // Start from the parent's full snapshot so unstaged files are carried over
+        let head = self.get_head_snapshot().await?;
+        let (snapshot, changes) = Self::apply_changeset(&head, staged_files);
+        
+        if changes.is_empty() && merge_head.is_none() {
+            warn!("Staged files are identical to HEAD");
+            return Err(FlowError::InvalidRepoState("No changes staged for commit".to_string()));
         }
         
-        // Create tree object
-        let tree_hash = self.create_tree_from_changes(&changes).await?;
+        let mut sub_intentions = Self::sub_intentions(&changes, &path_intentions, intention.confidence);
+        for sub in &sub_intentions {
+            intention.related_intentions.push(sub.intention.id);
+        }
+        
+        // Tag with the repository's taxonomy now that the changed paths are known
+        let taxonomy = TagTaxonomy::new(&self.config.tags)?;
+        let paths: Vec<String> = changes.iter().map(|change| change.path.clone()).collect();
+        intention.retag(&taxonomy, &paths);
+        for sub in &mut sub_intentions {
+            sub.intention.retag(&taxonomy, &sub.paths);
+        }
         
-        // Get parent commits (current HEAD)
-        let parent_commits = self.get_current_head_commits().await?;
+        // Create tree objects for the whole project
+        let tree_hash = self.write_tree(&snapshot).await?;
         
         // Create commit
-        let commit = FlowCommit::new(intention, changes, tree_hash, parent_commits);
+        let mut commit = FlowCommit::new(intention, changes, tree_hash, parent_commits)
+            .with_sub_intentions(sub_intentions);
+        // With `sign_commits` the user's key is only loaded, or created, once a commit is signed
+        let key = match &self.signing_key {
+            Some(key) => Some(key.clone()),
+            None if self.config.user.sign_commits => Some(KeyStore::user()?.load_or_generate()?),
+            None => None,
+        };
+        if let Some(key) = &key {
+            commit.sign(key)?;
+        }
         
         // Store commit
         self.object_store.store_commit(&commit).await?;
@@ -139,11 +609,64 @@ impl Repository {
         
         // Clear index
         self.index.clear().await?;
+        self.save_index()?;
+        self.clear_merge_state()?;
         
         info!("Commit created: {}", commit.id);
         Ok(commit.id)
     }
     
+    /// Groups changed paths by the intention staged with them, one
+    /// sub-intention per distinct goal, in path order. A renamed file may
+    /// carry the intention on either its old or its new path.
+    fn sub_intentions(changes: &[FileChange], path_intentions: &HashMap<String, String>, confidence: f32) -> Vec<SubIntention> {
+        let mut sub_intentions: Vec<SubIntention> = Vec::new();
+        
+        for change in changes {
+            let goal = match &change.change_type {
+                ChangeType::Renamed { from } => path_intentions.get(&change.path).or_else(|| path_intentions.get(from)),
+                _ => path_intentions.get(&change.path),
+            };
+            let Some(goal) = goal else { continue };
+            
+            match sub_intentions.iter_mut().find(|sub| sub.intention.goal == *goal) {
+                Some(sub) => sub.paths.push(change.path.clone()),
+                None => sub_intentions.push(SubIntention {
+                    intention: Intention::new(goal.clone(), None, None, confidence),
+                    paths: vec![change.path.clone()],
+                }),
+            }
+        }
+        
+        for sub in &mut sub_intentions {
+            sub.intention.id = sub.content_id();
+        }
+        
+        sub_intentions
+    }
+    
+    /// How many commits in the log carry each tag, on their intention or a
+    /// sub-intention; most used first.
+    pub async fn tag_counts(&self) -> Result<Vec<(String, usize)>> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        
+        for commit in self.get_log().await? {
+            let mut tags: Vec<&String> = commit.intention.tags.iter()
+                .chain(commit.sub_intentions.iter().flat_map(|sub| &sub.intention.tags))
+                .collect();
+            tags.sort();
+            tags.dedup();
+            
+            for tag in tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(counts)
+    }
+    
     pub async fn get_log(&self) -> Result<Vec<FlowCommit>> {
         debug!("Retrieving commit log");
         
@@ -168,118 +691,2933 @@ impl Repository {
         Ok(commits)
     }
     
-    async fn create_tree_from_changes(&self, changes: &[FileChange]) -> Result<Hash> {
-        // For now, create a simple hash from all file hashes
-        let mut data = Vec::new();
-        for change in changes {
-            data.extend_from_slice(change.content_hash.as_str().as_bytes());
-            data.extend_from_slice(change.path.as_bytes());
-        }
-        Ok(Hash::new(&data))
+    /// Intentions of the newest commits on the current stream that changed
+    /// any of `paths`, newest first.
+    pub async fn recent_intentions_for(&self, paths: &[String], limit: usize) -> Result<Vec<Intention>> {
+        let paths: HashSet<&str> = paths.iter().map(String::as_str).collect();
+        let touches = |change: &FileChange| {
+            paths.contains(change.path.as_str())
+                || matches!(&change.change_type, ChangeType::Renamed { from } if paths.contains(from.as_str()))
+        };
+        
+        Ok(self.get_log().await?
+            .into_iter()
+            .filter(|commit| commit.changes.iter().any(touches))
+            .take(limit)
+            .map(|commit| commit.intention)
+            .collect())
     }
     
-    async fn get_current_head_commits(&self) -> Result<Vec<CommitId>> {
-        match self.get_head_commit_id().await? {
-            Some(id) => Ok(vec![id]),
-            None => Ok(Vec::new()),
+    pub async fn get_status(&self) -> Result<RepositoryStatus> {
+        debug!("Computing repository status");
+        
+        let head = self.get_head_snapshot().await?;
+        let staged_files = self.index.get_staged_files().await?;
+        let unmerged = self.merge_conflicts()?
+            .into_iter()
+            .filter(|path| !staged_files.contains_key(path))
+            .collect();
+        
+        // What the next commit would record: HEAD with the index on top
+        let (expected, staged) = Self::apply_changeset(&head, staged_files);
+        let working_tree = self.scan_working_tree(&expected)?;
+        
+        let (stream, variant) = match self.current_variant()? {
+            Some((stream, variant)) => (Some(stream), Some(variant)),
+            None => (self.current_stream_name()?, None),
+        };
+        
+        let mut status = RepositoryStatus {
+            stream,
+            variant,
+            staged,
+            merging: self.merge_head()?,
+            unmerged,
+            ..Default::default()
+        };
+        
+        for (path, entry) in &expected {
+            if status.unmerged.contains(path) {
+                continue;
+            }
+            
+            match working_tree.get(path) {
+                Some(current) if current.hash != entry.hash => status.modified.push(path.clone()),
+                Some(_) => {}
+                None => status.deleted.push(path.clone()),
+            }
         }
+        
+        status.untracked = working_tree.keys()
+            .filter(|path| !expected.contains_key(*path) && !status.unmerged.contains(*path))
+            .cloned()
+            .collect();
+        
+        Ok(status)
     }
     
-    async fn get_head_commit_id(&self) -> Result<Option<CommitId>> {
-        let head_path = self.root_path.join(FLOWVERSION_DIR).join("HEAD");
+    /// Resolves `HEAD`, a full commit id or an unambiguous id prefix
+    /// (at least 4 hex characters) to a commit id.
+    pub async fn resolve_commit(&self, spec: &str) -> Result<CommitId> {
+        let spec = spec.trim();
         
-        if !head_path.exists() {
-            return Ok(None);
+        // Stream names take precedence over id prefixes
+        if Stream::validate_name(spec).is_ok() && self.stream_store.exists(spec) {
+            return self.stream_store.read_head(spec)?
+                .ok_or_else(|| FlowError::InvalidRepoState(format!("Stream {} has no commits yet", spec)));
         }
         
-        let head_content = std::fs::read_to_string(&head_path)?;
+        // <stream>@<variant> names a quantum variant, archived or not
+        if let Some((stream, variant)) = spec.rsplit_once('@') {
+            if self.stream_store.exists(stream) && QuantumState::validate_variant_name(variant).is_ok() {
+                return self.variant_head(stream, variant)?
+                    .ok_or_else(|| FlowError::InvalidQuantumFeature(format!("{} has no commits", spec)));
+            }
+        }
         
-        if head_content.starts_with("ref: ") {
-            // Symbolic reference
-            let ref_path = head_content.trim_start_matches("ref: ").trim();
-            let ref_file = self.root_path.join(FLOWVERSION_DIR).join(ref_path);
-            
-            if ref_file.exists() {
-                let commit_id_str = std::fs::read_to_string(&ref_file)?;
-                let uuid = uuid::Uuid::parse_str(commit_id_str.trim())
-                    .map_err(|_| FlowError::InvalidCommitId(commit_id_str))?;
-                Ok(Some(CommitId::from_uuid(uuid)))
-            } else {
-                Ok(None)
+        if spec == "HEAD" {
+            return self.get_head_commit_id().await?
+                .ok_or_else(|| FlowError::InvalidRepoState("HEAD has no commits yet".to_string()));
+        }
+        
+        if let Ok(commit_id) = CommitId::parse(spec) {
+            if self.object_store.commit_exists(&commit_id).await {
+                return Ok(commit_id);
             }
-        } else {
-            // Direct commit ID
-            let uuid = uuid::Uuid::parse_str(head_content.trim())
-                .map_err(|_| FlowError::InvalidCommitId(head_content))?;
-            Ok(Some(CommitId::from_uuid(uuid)))
+            return Err(FlowError::ObjectNotFound(spec.to_string()));
+        }
+        
+        if spec.len() < 4 || !spec.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(FlowError::InvalidCommitId(spec.to_string()));
+        }
+        
+        let prefix = spec.to_ascii_lowercase();
+        let mut matches: Vec<CommitId> = self.object_store.list_commits().await?
+            .into_iter()
+            .filter(|commit_id| commit_id.as_str().starts_with(&prefix))
+            .collect();
+        
+        match matches.len() {
+            0 => Err(FlowError::ObjectNotFound(spec.to_string())),
+            1 => Ok(matches.remove(0)),
+            n => Err(FlowError::InvalidCommitId(format!("{} is ambiguous ({} commits match)", spec, n))),
         }
     }
     
-    async fn update_head(&self, commit_id: &CommitId) -> Result<()> {
-        let head_path = self.root_path.join(FLOWVERSION_DIR).join("HEAD");
-        let head_content = std::fs::read_to_string(&head_path)?;
+    pub async fn load_commit(&self, commit_id: &CommitId) -> Result<FlowCommit> {
+        self.object_store.load_commit(commit_id).await
+    }
+    
+    pub async fn load_blob_content(&self, hash: &Hash) -> Result<Vec<u8>> {
+        Ok(self.object_store.load_blob(hash).await?.content)
+    }
+    
+    pub async fn create_stream(
+        &self,
+        name: &str,
+        mode: StreamMode,
+        description: Option<String>,
+        from: Option<&str>,
+    ) -> Result<Stream> {
+        Stream::validate_name(name)?;
         
-        if head_content.starts_with("ref: ") {
-            // Update the reference
-            let ref_path = head_content.trim_start_matches("ref: ").trim();
-            let ref_file = self.root_path.join(FLOWVERSION_DIR).join(ref_path);
-            
-            // Create directory if it doesn't exist
-            if let Some(parent) = ref_file.parent() {
-                std::fs::create_dir_all(parent)?;
+        if self.stream_store.exists(name) {
+            return Err(FlowError::StreamAlreadyExists(name.to_string()));
+        }
+        
+        let head = match from {
+            Some(spec) => Some(self.resolve_commit(spec).await?),
+            None => self.get_head_commit_id().await?,
+        };
+        
+        let mut stream = Stream::new(name.to_string(), mode);
+        stream.head_commit = head;
+        if let Some(description) = description {
+            stream.set_description(description);
+        }
+        
+        self.stream_store.save(&stream)?;
+        info!("Created stream {}", name);
+        Ok(stream)
+    }
+    
+    pub fn list_streams(&self) -> Result<Vec<Stream>> {
+        let mut streams = self.stream_store.list()?;
+        
+        // Before the first commit HEAD's stream may have neither ref nor metadata
+        if let Some(current) = self.current_stream_name()? {
+            if !streams.iter().any(|stream| stream.name == current) {
+                streams.push(Stream::new(current, StreamMode::Linear));
+                streams.sort_by(|a, b| a.name.cmp(&b.name));
             }
-            
-            std::fs::write(&ref_file, commit_id.to_string())?;
-        } else {
-            // Update HEAD directly
-            std::fs::write(&head_path, commit_id.to_string())?;
         }
         
-        Ok(())
+        Ok(streams)
     }
     
-    pub fn get_config(&self) -> &Config {
-        &self.config
+    #[cfg(test)]
+    pub fn load_stream(&self, name: &str) -> Result<Stream> {
+        self.stream_store.load(name)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
     
-    #[tokio::test]
-    async fn test_repository_init() {
-        let temp_dir = TempDir::new().unwrap();
-        let path = temp_dir.path();
+    /// Points HEAD at another stream and rewrites the working tree to match.
+    pub async fn switch_stream(&self, name: &str) -> Result<()> {
+        Stream::validate_name(name)?;
         
-        assert!(!Repository::is_initialized(path).unwrap());
+        if !self.stream_store.exists(name) {
+            return Err(FlowError::StreamNotFound(name.to_string()));
+        }
         
-        Repository::init(path, "test-repo", "local").await.unwrap();
+        if self.current_stream_name()?.as_deref() == Some(name) {
+            debug!("Already on stream {}", name);
+            return Ok(());
+        }
         
-        assert!(Repository::is_initialized(path).unwrap());
+        let target = self.stream_store.read_head(name)?;
+        self.switch_head(Some(&format!("refs/streams/{}", name)), target, false).await?;
         
-        let flow_dir = path.join(".flowversion");
-        assert!(flow_dir.join("objects").exists());
-        assert!(flow_dir.join("refs").exists());
-        assert!(flow_dir.join("config.json").exists());
-        assert!(flow_dir.join("index.json").exists());
-        assert!(flow_dir.join("HEAD").exists());
+        info!("Switched to stream {}", name);
+        Ok(())
     }
     
-    #[tokio::test]
-    async fn test_repository_open() {
-        let temp_dir = TempDir::new().unwrap();
-        let path = temp_dir.path();
+    pub async fn delete_stream(&self, name: &str, force: bool) -> Result<()> {
+        let stream = self.stream_store.load(name)?;
+        let quantum_state = QuantumState::from_metadata(&stream.metadata)?
+            .filter(|state| !state.is_collapsed());
         
-        // Should fail before init
-        assert!(Repository::open(path).await.is_err());
+        if let (false, Some(_)) = (force, &quantum_state) {
+            return Err(FlowError::InvalidRepoState(format!(
+                "Quantum stream {} has not been collapsed (use --force to delete anyway)",
+                name
+            )));
+        }
         
-        // Initialize and open
-        Repository::init(path, "test-repo", "local").await.unwrap();
-        let repo = Repository::open(path).await.unwrap();
+        if self.current_variant()?.is_some_and(|(stream, _)| stream == name) {
+            return Err(FlowError::InvalidRepoState(format!("Cannot delete the current stream {}", name)));
+        }
         
-        assert_eq!(repo.config.name, "test-repo");
-        assert_eq!(repo.config.ai_mode, "local");
+        if self.current_stream_name()?.as_deref() == Some(name) {
+            return Err(FlowError::InvalidRepoState(format!("Cannot delete the current stream {}", name)));
+        }
+        
+        if let (false, Some(stream_head)) = (force, &stream.head_commit) {
+            let merged = match self.get_head_commit_id().await? {
+                Some(head) => self.is_ancestor(stream_head, &head).await?,
+                None => false,
+            };
+            
+            if !merged {
+                return Err(FlowError::InvalidRepoState(format!(
+                    "Stream {} is not merged into the current stream (use --force to delete anyway)",
+                    name
+                )));
+            }
+        }
+        
+        for variant in quantum_state.iter().flat_map(|state| state.variants.keys()) {
+            self.stream_store.remove_variant(name, variant)?;
+        }
+        
+        self.stream_store.delete(name)?;
+        info!("Deleted stream {}", name);
+        Ok(())
+    }
+    
+    /// Creates a quantum stream whose variants all start at the new stream's
+    /// head (HEAD, or `from`).
+    pub async fn create_quantum_stream(
+        &self,
+        name: &str,
+        variants: &[String],
+        description: Option<String>,
+        from: Option<&str>,
+    ) -> Result<Stream> {
+        // Validate the variants before anything is written
+        let mut state = QuantumState::new(None, variants)?;
+        
+        let mut stream = self.create_stream(name, StreamMode::Quantum, description, from).await?;
+        state.base = stream.head_commit.clone();
+        
+        if let Some(base) = &state.base {
+            for variant in state.variants.keys() {
+                self.stream_store.write_variant_head(name, variant, base)?;
+            }
+        }
+        
+        state.store_in(&mut stream.metadata)?;
+        self.stream_store.save(&stream)?;
+        
+        info!("Created quantum stream {} with {} variants", name, variants.len());
+        Ok(stream)
+    }
+    
+    /// Loads a quantum stream together with its variant state.
+    pub fn load_quantum_stream(&self, name: &str) -> Result<(Stream, QuantumState)> {
+        let stream = self.stream_store.load(name)?;
+        
+        match QuantumState::from_metadata(&stream.metadata)? {
+            Some(state) => Ok((stream, state)),
+            None => Err(FlowError::InvalidQuantumFeature(format!("{} is not a quantum stream", name))),
+        }
+    }
+    
+    /// Current head of a variant. After a collapse the chosen variant is the
+    /// head recorded at collapse time and rejected ones come from the archive.
+    pub fn variant_head(&self, name: &str, variant: &str) -> Result<Option<CommitId>> {
+        if let Some(head) = self.stream_store.read_variant_head(name, variant)? {
+            return Ok(Some(head));
+        }
+        
+        let (_, state) = self.load_quantum_stream(name)?;
+        match state.collapsed {
+            Some(collapse) if collapse.variant == variant => Ok(collapse.head),
+            _ => self.stream_store.read_archived_variant_head(name, variant),
+        }
+    }
+    
+    /// Points HEAD at a variant of an uncollapsed quantum stream so that
+    /// commits advance that variant only.
+    pub async fn switch_variant(&self, name: &str, variant: &str) -> Result<()> {
+        let (_, state) = self.load_quantum_stream(name)?;
+        
+        if state.is_collapsed() {
+            return Err(FlowError::QuantumStateCollapsed);
+        }
+        state.variant(variant)?;
+        
+        if self.current_variant()? == Some((name.to_string(), variant.to_string())) {
+            debug!("Already on variant {}@{}", name, variant);
+            return Ok(());
+        }
+        
+        let target = self.stream_store.read_variant_head(name, variant)?;
+        self.switch_head(Some(&StreamStore::variant_ref(name, variant)), target, false).await?;
+        
+        info!("Switched to variant {}@{}", name, variant);
+        Ok(())
+    }
+    
+    /// Attaches test reports (JUnit XML or JSON) to a variant as evidence.
+    /// The reports themselves are kept as blobs for later audit.
+    pub async fn observe_variant(&self, name: &str, variant: &str, reports: &[PathBuf]) -> Result<Vec<Evidence>> {
+        let (mut stream, mut state) = self.load_quantum_stream(name)?;
+        
+        if state.is_collapsed() {
+            return Err(FlowError::QuantumStateCollapsed);
+        }
+        state.variant(variant)?;
+        
+        let head = self.stream_store.read_variant_head(name, variant)?;
+        let mut observed = Vec::new();
+        
+        for report in reports {
+            let content = std::fs::read_to_string(report)
+                .map_err(|_| FlowError::FileNotFound(report.display().to_string()))?;
+            let (format, summary) = quantum::parse_test_report(&content)?;
+            
+            let hash = Hash::new(content.as_bytes());
+            self.object_store.store_blob(&hash, content.into_bytes()).await?;
+            
+            observed.push(Evidence {
+                source: report.display().to_string(),
+                format,
+                report: hash,
+                commit: head.clone(),
+                passed: summary.passed,
+                failed: summary.failed,
+                skipped: summary.skipped,
+                recorded_at: chrono::Utc::now(),
+            });
+        }
+        
+        state.variant_mut(variant)?.evidence.extend(observed.iter().cloned());
+        state.store_in(&mut stream.metadata)?;
+        self.stream_store.save(&stream)?;
+        
+        Ok(observed)
+    }
+    
+    /// Collapses a quantum stream onto one variant, or onto the variant with
+    /// the best evidence when none is named. The stream becomes linear with
+    /// the chosen head; rejected variant heads are archived.
+    pub async fn collapse_quantum_stream(&self, name: &str, variant: Option<&str>) -> Result<Collapse> {
+        let (mut stream, mut state) = self.load_quantum_stream(name)?;
+        
+        if state.is_collapsed() {
+            return Err(FlowError::QuantumStateCollapsed);
+        }
+        
+        let chosen = match variant {
+            Some(variant) => {
+                state.variant(variant)?;
+                variant.to_string()
+            }
+            None => state.best_variant()?,
+        };
+        let chosen_head = self.stream_store.read_variant_head(name, &chosen)?;
+        
+        // Leave the variant refs only after HEAD has moved off them
+        if self.current_variant()?.is_some_and(|(stream, _)| stream == name) {
+            self.switch_head(Some(&format!("refs/streams/{}", name)), chosen_head.clone(), false).await?;
+        }
+        
+        let rejected: Vec<String> = state.variants.keys()
+            .filter(|variant| **variant != chosen)
+            .cloned()
+            .collect();
+        for variant in &rejected {
+            self.stream_store.archive_variant(name, variant)?;
+        }
+        self.stream_store.remove_variant(name, &chosen)?;
+        
+        let collapse = Collapse {
+            variant: chosen,
+            head: chosen_head.clone(),
+            rejected,
+            collapsed_at: chrono::Utc::now(),
+        };
+        state.collapsed = Some(collapse.clone());
+        state.store_in(&mut stream.metadata)?;
+        
+        stream.mode = StreamMode::Linear;
+        stream.head_commit = chosen_head;
+        self.stream_store.save(&stream)?;
+        
+        info!("Collapsed {} onto variant {}", name, collapse.variant);
+        Ok(collapse)
+    }
+    
+    /// Points HEAD at `reference` (or detaches it at `target` when there is
+    /// no reference) and rewrites the working tree to `target`.
+    ///
+    /// Refuses with `WorkingDirectoryNotClean` if staged or unstaged edits
+    /// exist, or if an untracked file would be overwritten. With `force`,
+    /// local edits, the index and any in-progress merge are discarded.
+    async fn switch_head(&self, reference: Option<&str>, target: Option<CommitId>, force: bool) -> Result<()> {
+        if self.merge_head()?.is_some() && !force {
+            return Err(FlowError::InvalidRepoState(
+                "Cannot switch streams during a merge (commit it or run merge --abort)".to_string()
+            ));
+        }
+        
+        let target_id = target.clone();
+        let target = match target {
+            Some(commit_id) => {
+                let commit = self.object_store.load_commit(&commit_id).await?;
+                self.get_commit_snapshot(&commit).await?
+            }
+            None => Snapshot::new(),
+        };
+        
+        let current = if force {
+            // Start from what is actually on disk for every path either side
+            // tracks, so local edits are overwritten and stale files removed
+            let tracked = self.get_index_snapshot().await?;
+            self.scan_working_tree(&tracked)?
+                .into_iter()
+                .filter(|(path, _)| tracked.contains_key(path) || target.contains_key(path))
+                .collect()
+        } else {
+            let status = self.get_status().await?;
+            if !status.staged.is_empty() || !status.modified.is_empty() || !status.deleted.is_empty() {
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            
+            if status.untracked.iter().any(|path| target.contains_key(path)) {
+                warn!("Untracked files would be overwritten by switching to {}", reference.unwrap_or("a detached HEAD"));
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            
+            self.get_head_snapshot().await?
+        };
+        
+        self.materialize_snapshot(&current, &target).await?;
+        
+        if force {
+            self.index.clear().await?;
+            self.save_index()?;
+            self.clear_merge_state()?;
+        }
+        
+        match (reference, target_id) {
+            (Some(reference), _) => self.set_head_ref(reference),
+            (None, Some(commit_id)) => {
+                write_atomic(&self.root_path.join(FLOWVERSION_DIR).join("HEAD"), format!("{}\n", commit_id))?;
+                Ok(())
+            }
+            (None, None) => Err(FlowError::InvalidRepoState("Cannot detach HEAD without a commit".to_string())),
+        }
+    }
+    
+    /// Checks out a stream (attaching HEAD to it) or any other revision
+    /// (detaching HEAD at it). Returns the stream name when one was checked out.
+    pub async fn checkout(&self, spec: &str, force: bool) -> Result<Option<String>> {
+        if Stream::validate_name(spec).is_ok() && self.stream_store.exists(spec) {
+            if !force && self.current_stream_name()?.as_deref() == Some(spec) {
+                debug!("Already on stream {}", spec);
+                return Ok(Some(spec.to_string()));
+            }
+            
+            let target = self.stream_store.read_head(spec)?;
+            self.switch_head(Some(&format!("refs/streams/{}", spec)), target, force).await?;
+            info!("Checked out stream {}", spec);
+            return Ok(Some(spec.to_string()));
+        }
+        
+        let commit_id = self.resolve_commit(spec).await?;
+        self.switch_head(None, Some(commit_id.clone()), force).await?;
+        info!("HEAD detached at {}", commit_id);
+        Ok(None)
+    }
+    
+    /// Restores files matching `pathspecs` in the working tree from the
+    /// index, or from `source` when given. Files the source does not have
+    /// are removed. Local edits that would be lost need `force`.
+    ///
+    /// Returns the paths that were written or removed.
+    pub async fn restore_paths(&self, pathspecs: &[String], source: Option<&str>, force: bool) -> Result<Vec<String>> {
+        let expected = self.get_index_snapshot().await?;
+        let target = match source {
+            Some(spec) => {
+                let commit = self.object_store.load_commit(&self.resolve_commit(spec).await?).await?;
+                self.get_commit_snapshot(&commit).await?
+            }
+            None => expected.clone(),
+        };
+        let working = self.scan_working_tree(&expected)?;
+        
+        let mut selected = std::collections::BTreeSet::new();
+        for pathspec in pathspecs {
+            let pathspec = Pathspec::parse(pathspec)?;
+            
+            let matched: Vec<String> = target.keys().chain(expected.keys()).filter(|path| pathspec.matches(path)).cloned().collect();
+            if matched.is_empty() {
+                return Err(FlowError::FileNotFound(pathspec.as_str().to_string()));
+            }
+            selected.extend(matched);
+        }
+        
+        // A local edit is lost if the file on disk matches neither the
+        // committed/staged state nor what it would be restored to
+        let hash_of = |snapshot: &Snapshot, path: &String| snapshot.get(path).map(|entry| entry.hash.clone());
+        let at_risk: Vec<&String> = selected.iter()
+            .filter(|path| {
+                let on_disk = hash_of(&working, path);
+                on_disk.is_some() && on_disk != hash_of(&expected, path) && on_disk != hash_of(&target, path)
+            })
+            .collect();
+        
+        if !at_risk.is_empty() && !force {
+            for path in &at_risk {
+                warn!("Uncommitted changes in {} would be overwritten (use --force)", path);
+            }
+            return Err(FlowError::WorkingDirectoryNotClean);
+        }
+        
+        let from: Snapshot = working.into_iter().filter(|(path, _)| selected.contains(path)).collect();
+        let to: Snapshot = target.into_iter().filter(|(path, _)| selected.contains(path)).collect();
+        
+        let restored: Vec<String> = selected.into_iter()
+            .filter(|path| from.get(path) != to.get(path))
+            .collect();
+        self.materialize_snapshot(&from, &to).await?;
+        
+        Ok(restored)
+    }
+    
+    /// Packs every reachable object and prunes unreachable ones older than
+    /// `grace`.
+    pub async fn gc(&self, grace: Duration) -> Result<GcReport> {
+        let reachable = self.reachable_objects().await?;
+        debug!("{} reachable objects", reachable.len());
+        
+        let report = self.object_store.gc(&reachable, grace).await?;
+        info!("Packed {} objects, pruned {}", report.packed, report.pruned);
+        Ok(report)
+    }
+    
+    /// Objects reachable from refs, HEAD, an in-progress merge, the index,
+    /// quantum test evidence and the operation log, each with a name hint
+    /// for delta packing.
+    async fn reachable_objects(&self) -> Result<HashMap<(ObjectKind, String), String>> {
+        let mut reachable = HashMap::new();
+        let mut pending = Vec::new();
+        
+        for (name, content) in self.list_refs()? {
+            match CommitId::parse(&content) {
+                Ok(commit_id) => pending.push(commit_id),
+                Err(_) => warn!("Ignoring unreadable ref {}", name),
+            }
+        }
+        
+        // Staged content and test reports are blobs no commit refers to yet
+        for (path, entry) in self.index.get_staged_files().await? {
+            if !entry.deleted {
+                let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+                reachable.insert((ObjectKind::Blob, entry.hash.to_string()), name);
+            }
+        }
+        for (_, state) in self.quantum_states()? {
+            for evidence in state.variants.values().flat_map(|variant| &variant.evidence) {
+                reachable.insert((ObjectKind::Blob, evidence.report.to_string()), evidence.source.clone());
+            }
+        }
+        
+        // Logged states keep what they refer to, so any operation can be restored
+        for (_, hash, state) in self.logged_states().await? {
+            reachable.insert((ObjectKind::Blob, hash.to_string()), String::new());
+            
+            if let Some(state) = state {
+                pending.extend(state.commits().cloned());
+                for (path, entry) in state.index.iter().filter(|(_, entry)| !entry.deleted) {
+                    let name = path.rsplit('/').next().unwrap_or(path).to_string();
+                    reachable.insert((ObjectKind::Blob, entry.hash.to_string()), name);
+                }
+            }
+        }
+        
+        let mut trees = Vec::new();
+        while let Some(commit_id) = pending.pop() {
+            if reachable.insert((ObjectKind::Commit, commit_id.to_string()), String::new()).is_some() {
+                continue;
+            }
+            
+            let commit = self.object_store.load_commit(&commit_id).await?;
+            pending.extend(commit.parent_commits);
+            trees.push(commit.tree_hash);
+        }
+        
+        while let Some(tree_hash) = trees.pop() {
+            if reachable.insert((ObjectKind::Tree, tree_hash.to_string()), String::new()).is_some() {
+                continue;
+            }
+            
+            for (name, entry) in self.object_store.load_tree(&tree_hash).await?.entries {
+                match entry {
+                    TreeEntry::Blob { hash, .. } => {
+                        reachable.insert((ObjectKind::Blob, hash.to_string()), name);
+                    }
+                    TreeEntry::Tree { hash } => trees.push(hash),
+                }
+            }
+        }
+        
+        Ok(reachable)
+    }
+    
+    /// Quantum state of every quantum stream, read from metadata only so
+    /// that damaged refs do not get in the way.
+    fn quantum_states(&self) -> Result<Vec<(String, QuantumState)>> {
+        let mut states = Vec::new();
+        
+        for name in self.stream_store.names()? {
+            let stream = self.stream_store.load_metadata(&name)?;
+            if let Some(state) = QuantumState::from_metadata(&stream.metadata)? {
+                states.push((name, state));
+            }
+        }
+        
+        Ok(states)
+    }
+    
+    /// Every ref holding a commit id, as (name, raw content): stream heads,
+    /// quantum variant heads (active and archived), a detached HEAD and
+    /// MERGE_HEAD. Names are relative to the repository directory.
+    fn list_refs(&self) -> Result<Vec<(String, String)>> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        let mut refs = Vec::new();
+        
+        for entry in WalkDir::new(flow_dir.join("refs")).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() || entry.path().extension().is_some_and(|ext| ext == "json") {
+                continue;
+            }
+            
+            let name = entry.path()
+                .strip_prefix(&flow_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            refs.push((name, std::fs::read_to_string(entry.path())?));
+        }
+        
+        for name in ["HEAD", MERGE_HEAD_FILE] {
+            let path = flow_dir.join(name);
+            if path.exists() {
+                let content = std::fs::read_to_string(&path)?;
+                if !content.starts_with("ref: ") {
+                    refs.push((name.to_string(), content));
+                }
+            }
+        }
+        
+        Ok(refs)
+    }
+    
+    /// Checks refs and every stored object: that everything reachable is
+    /// present and readable, that content hashes to its id, and which
+    /// objects nothing refers to.
+    ///
+    /// With `repair`, refs that are unreadable or point at a missing or
+    /// broken commit are reset to the newest intact commit in their reflog.
+    pub async fn fsck(&self, repair: bool) -> Result<FsckReport> {
+        let mut report = FsckReport::default();
+        let mut pending = Vec::new();
+        
+        // Refs first, so repaired ones are walked from their new target
+        for (name, content) in self.list_refs()? {
+            report.refs_checked += 1;
+            let subject = format!("ref {}", name);
+            
+            let issue = match CommitId::parse(&content) {
+                Err(_) => FsckIssue::new(IssueKind::Corrupt, subject, format!("does not hold a commit id: {:?}", content.trim())),
+                Ok(commit_id) => match self.check_object(ObjectKind::Commit, commit_id.as_str()).await.1 {
+                    None => {
+                        pending.push((ObjectKind::Commit, commit_id.to_string(), subject));
+                        continue;
+                    }
+                    Some((kind, detail)) => FsckIssue::new(kind, subject, format!("points to commit {}: {}", commit_id, detail)),
+                },
+            };
+            
+            if repair {
+                match self.repair_ref(&name).await? {
+                    Some(commit_id) => {
+                        info!("Repaired {} to {}", name, commit_id);
+                        pending.push((ObjectKind::Commit, commit_id.to_string(), format!("ref {}", name)));
+                        report.repaired.push((name, commit_id));
+                        continue;
+                    }
+                    None => report.unrepaired.push(name),
+                }
+            }
+            report.issues.push(issue);
+        }
+        
+        // Staged content and test reports are referenced outside of commits
+        for (path, entry) in self.index.get_staged_files().await? {
+            if !entry.deleted {
+                pending.push((ObjectKind::Blob, entry.hash.to_string(), format!("index entry {}", path)));
+            }
+        }
+        for (stream, state) in self.quantum_states()? {
+            for (variant, evidence) in state.variants.iter().flat_map(|(name, v)| v.evidence.iter().map(move |e| (name, e))) {
+                pending.push((ObjectKind::Blob, evidence.report.to_string(), format!("evidence for {}@{}", stream, variant)));
+            }
+        }
+        for (label, hash, state) in self.logged_states().await? {
+            pending.push((ObjectKind::Blob, hash.to_string(), label.clone()));
+            
+            if let Some(state) = state {
+                pending.extend(state.commits().map(|commit_id| (ObjectKind::Commit, commit_id.to_string(), label.clone())));
+                for (path, entry) in state.index.iter().filter(|(_, entry)| !entry.deleted) {
+                    pending.push((ObjectKind::Blob, entry.hash.to_string(), format!("index entry {} in {}", path, label)));
+                }
+            }
+        }
+        
+        let mut reached = HashSet::new();
+        while let Some((kind, id, referrer)) = pending.pop() {
+            if !reached.insert((kind, id.clone())) {
+                continue;
+            }
+            
+            let (children, problem) = self.check_object(kind, &id).await;
+            report.objects_checked += 1;
+            
+            if let Some((issue_kind, detail)) = problem {
+                report.issues.push(FsckIssue::new(issue_kind, format!("{} {}", kind, id), format!("{} (referenced by {})", detail, referrer)));
+            }
+            
+            let referrer = format!("{} {}", kind, &id[..8.min(id.len())]);
+            pending.extend(children.into_iter().map(|(kind, id)| (kind, id, referrer.clone())));
+        }
+        
+        // Whatever is left is unreachable; only its tips count as dangling
+        let mut unreached = Vec::new();
+        let mut referenced = HashSet::new();
+        for (kind, id) in self.object_store.list_objects().await? {
+            if reached.contains(&(kind, id.clone())) {
+                continue;
+            }
+            
+            let (children, problem) = self.check_object(kind, &id).await;
+            report.objects_checked += 1;
+            
+            if let Some((issue_kind, detail)) = problem {
+                report.issues.push(FsckIssue::new(issue_kind, format!("{} {}", kind, id), detail));
+            }
+            referenced.extend(children);
+            unreached.push((kind, id));
+        }
+        
+        for (kind, id) in unreached {
+            if !referenced.contains(&(kind, id.clone())) {
+                report.issues.push(FsckIssue::new(IssueKind::Dangling, format!("{} {}", kind, id), "not reachable from any ref"));
+            }
+        }
+        
+        for pack in self.object_store.corrupt_packs()? {
+            let name = pack.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            report.issues.push(FsckIssue::new(IssueKind::Corrupt, format!("pack {}", name), "content does not match its checksum"));
+        }
+        
+        report.issues.sort_by(|a, b| (a.kind, &a.subject).cmp(&(b.kind, &b.subject)));
+        Ok(report)
+    }
+    
+    /// Loads and verifies one object, returning the objects it refers to and
+    /// the problem found, if any.
+    async fn check_object(&self, kind: ObjectKind, id: &str) -> (Vec<(ObjectKind, String)>, Option<(IssueKind, String)>) {
+        let classify = |e: FlowError| match e {
+            FlowError::ObjectNotFound(_) => (IssueKind::Missing, "not in the object store".to_string()),
+            e => (IssueKind::Corrupt, e.to_string()),
+        };
+        let mismatch = |actual: &str| Some((IssueKind::HashMismatch, format!("content hashes to {}", actual)));
+        
+        match kind {
+            ObjectKind::Blob => match self.object_store.load_blob(&Hash::from_string(id.to_string())).await {
+                Err(e) => (Vec::new(), Some(classify(e))),
+                Ok(blob) => {
+                    let actual = Hash::new(&blob.content);
+                    (Vec::new(), if actual.as_str() == id { None } else { mismatch(actual.as_str()) })
+                }
+            },
+            ObjectKind::Tree => match self.object_store.load_tree(&Hash::from_string(id.to_string())).await {
+                Err(e) => (Vec::new(), Some(classify(e))),
+                Ok(mut tree) => {
+                    let children = tree.entries.values()
+                        .map(|entry| match entry {
+                            TreeEntry::Blob { hash, .. } => (ObjectKind::Blob, hash.to_string()),
+                            TreeEntry::Tree { hash } => (ObjectKind::Tree, hash.to_string()),
+                        })
+                        .collect();
+                    
+                    let recorded = tree.id.clone();
+                    tree.calculate_hash();
+                    let problem = if tree.id.as_str() == id && recorded == tree.id { None } else { mismatch(tree.id.as_str()) };
+                    (children, problem)
+                }
+            },
+            ObjectKind::Commit => {
+                let commit = match CommitId::parse(id) {
+                    Ok(commit_id) => self.object_store.load_commit(&commit_id).await,
+                    Err(e) => Err(e),
+                };
+                
+                match commit {
+                    Err(e) => (Vec::new(), Some(classify(e))),
+                    Ok(commit) => {
+                        let mut children: Vec<_> = commit.parent_commits.iter()
+                            .map(|parent| (ObjectKind::Commit, parent.to_string()))
+                            .collect();
+                        children.push((ObjectKind::Tree, commit.tree_hash.to_string()));
+                        
+                        let actual = commit.calculate_commit_hash();
+                        let problem = if commit.id.as_str() == id && actual.as_str() == id { None } else { mismatch(actual.as_str()) };
+                        (children, problem)
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Points a broken ref at the newest commit in its reflog that is still
+    /// intact. Returns `None` if there is no reflog or no usable entry.
+    async fn repair_ref(&self, name: &str) -> Result<Option<CommitId>> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        let reflog = Reflog::new(&flow_dir.join(LOGS_DIR));
+        
+        for entry in reflog.read(name)?.into_iter().rev() {
+            let candidate = match entry.new {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            
+            if self.check_object(ObjectKind::Commit, candidate.as_str()).await.1.is_none() {
+                write_atomic(&flow_dir.join(name), candidate.to_string())?;
+                return Ok(Some(candidate));
+            }
+        }
+        
+        Ok(None)
+    }
+    
+    /// Records what the current command changed as one operation: appends
+    /// to the reflog of every ref that moved (and HEAD's) and to the
+    /// operation log. Nothing is recorded when nothing changed.
+    ///
+    /// Only repositories opened with `open_locked` can record operations.
+    pub async fn record_operation(&self, description: &str) -> Result<Option<Operation>> {
+        self.record(description, None).await
+    }
+    
+    async fn record(&self, description: &str, undoes: Option<u64>) -> Result<Option<Operation>> {
+        let before = self.op_start
+            .lock()
+            .map_err(|_| FlowError::InternalError("Operation state lock poisoned".to_string()))?
+            .clone()
+            .ok_or_else(|| FlowError::InvalidRepoState("Operations can only be recorded while holding the repository lock".to_string()))?;
+        let after = self.capture_state().await?;
+        
+        // An undo is logged even if it changed nothing, so it is not undone again
+        if before == after && undoes.is_none() {
+            return Ok(None);
+        }
+        
+        let logs_path = self.root_path.join(FLOWVERSION_DIR).join(LOGS_DIR);
+        let reflog = Reflog::new(&logs_path);
+        
+        let names: BTreeSet<&String> = before.refs.keys().chain(after.refs.keys()).collect();
+        for name in names {
+            let (old, new) = (before.refs.get(name), after.refs.get(name));
+            if old != new {
+                reflog.append(name, &ReflogEntry::new(old.cloned(), new.cloned(), description))?;
+            }
+        }
+        
+        let (old_head, new_head) = (before.head_commit(), after.head_commit());
+        if before.head != after.head || old_head != new_head {
+            reflog.append("HEAD", &ReflogEntry::new(old_head, new_head, description))?;
+        }
+        
+        let operation = OpLog::new(&logs_path).append(
+            description,
+            self.store_state(&before).await?,
+            self.store_state(&after).await?,
+            undoes,
+        )?;
+        debug!("Recorded operation {}: {}", operation.id, description);
+        
+        *self.op_start
+            .lock()
+            .map_err(|_| FlowError::InternalError("Operation state lock poisoned".to_string()))? = Some(after);
+        Ok(Some(operation))
+    }
+    
+    /// Every recorded operation, oldest first.
+    pub fn operations(&self) -> Result<Vec<Operation>> {
+        OpLog::new(&self.root_path.join(FLOWVERSION_DIR).join(LOGS_DIR)).read()
+    }
+    
+    /// Rolls refs, HEAD, merge state, the index and the working tree back to
+    /// before the latest operation not yet undone, and returns that
+    /// operation. Repeated undos walk further back.
+    pub async fn undo(&self, force: bool) -> Result<Operation> {
+        let oplog = OpLog::new(&self.root_path.join(FLOWVERSION_DIR).join(LOGS_DIR));
+        let operation = oplog.last_undoable()?
+            .ok_or_else(|| FlowError::InvalidRepoState("Nothing to undo".to_string()))?;
+        
+        let state = self.load_state(&operation.before).await?;
+        self.restore_state(&state, force).await?;
+        self.record(&format!("undo operation {}", operation.id), Some(operation.id)).await?;
+        
+        info!("Undid operation {}: {}", operation.id, operation.operation);
+        Ok(operation)
+    }
+    
+    /// Restores the state right after operation `id`; the restore is itself
+    /// recorded, so it can be undone.
+    pub async fn restore_operation(&self, id: u64, force: bool) -> Result<Operation> {
+        let oplog = OpLog::new(&self.root_path.join(FLOWVERSION_DIR).join(LOGS_DIR));
+        let operation = oplog.get(id)?
+            .ok_or_else(|| FlowError::OperationNotFound(id.to_string()))?;
+        
+        let state = self.load_state(&operation.after).await?;
+        self.restore_state(&state, force).await?;
+        self.record(&format!("restore operation {}", id), None).await?;
+        
+        Ok(operation)
+    }
+    
+    /// Reads HEAD, refs, stream metadata, merge state and the index. Refs
+    /// that do not hold a commit id are left out.
+    async fn capture_state(&self) -> Result<RepoState> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        let head_path = flow_dir.join("HEAD");
+        let mut state = RepoState {
+            head: if head_path.exists() { std::fs::read_to_string(&head_path)? } else { String::new() },
+            ..Default::default()
+        };
+        
+        for entry in WalkDir::new(flow_dir.join("refs")).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            
+            let name = entry.path()
+                .strip_prefix(&flow_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            let content = std::fs::read_to_string(entry.path())?;
+            
+            if entry.path().extension().is_some_and(|ext| ext == "json") {
+                state.metadata.insert(name, content);
+            } else {
+                match CommitId::parse(&content) {
+                    Ok(commit_id) => {
+                        state.refs.insert(name, commit_id);
+                    }
+                    Err(_) => warn!("Leaving unreadable ref {} out of the operation log", name),
+                }
+            }
+        }
+        
+        state.merge_head = self.merge_head().unwrap_or_else(|e| {
+            warn!("Leaving unreadable {} out of the operation log: {}", MERGE_HEAD_FILE, e);
+            None
+        });
+        state.merge_conflicts = self.merge_conflicts()?;
+        state.index = self.index.get_staged_files().await?.into_iter().collect();
+        
+        Ok(state)
+    }
+    
+    async fn store_state(&self, state: &RepoState) -> Result<Hash> {
+        let content = serde_json::to_vec(state)?;
+        let hash = Hash::new(&content);
+        self.object_store.store_blob(&hash, content).await?;
+        Ok(hash)
+    }
+    
+    async fn load_state(&self, hash: &Hash) -> Result<RepoState> {
+        let blob = self.object_store.load_blob(hash).await?;
+        Ok(serde_json::from_slice(&blob.content)?)
+    }
+    
+    /// Every distinct state in the operation log, labelled with the first
+    /// operation that refers to it; `None` where the state cannot be read.
+    async fn logged_states(&self) -> Result<Vec<(String, Hash, Option<RepoState>)>> {
+        let mut seen = HashSet::new();
+        let mut states = Vec::new();
+        
+        for operation in self.operations()? {
+            for hash in [operation.before, operation.after] {
+                if !seen.insert(hash.clone()) {
+                    continue;
+                }
+                
+                let state = match self.load_state(&hash).await {
+                    Ok(state) => Some(state),
+                    Err(e) => {
+                        warn!("Cannot read state {} of operation {}: {}", hash, operation.id, e);
+                        None
+                    }
+                };
+                states.push((format!("operation {}", operation.id), hash, state));
+            }
+        }
+        
+        Ok(states)
+    }
+    
+    /// Makes `state` the current one, then rewrites refs, metadata, HEAD,
+    /// merge state and the index to match it.
+    ///
+    /// Only paths whose content differs between the current HEAD and the
+    /// state's HEAD are updated in the working tree (to what HEAD plus the
+    /// index describe), so undoing an `add` leaves files alone. Local edits
+    /// to those paths are refused unless `force`, which resets every
+    /// tracked path instead.
+    async fn restore_state(&self, state: &RepoState, force: bool) -> Result<()> {
+        let target_head = match state.head_commit() {
+            Some(commit_id) => {
+                let commit = self.object_store.load_commit(&commit_id).await?;
+                self.get_commit_snapshot(&commit).await?
+            }
+            None => Snapshot::new(),
+        };
+        let target = Self::apply_changeset(&target_head, state.index.clone().into_iter().collect()).0;
+        
+        let (current, target) = if force {
+            let tracked = self.get_index_snapshot().await?;
+            let current = self.scan_working_tree(&tracked)?
+                .into_iter()
+                .filter(|(path, _)| tracked.contains_key(path) || target.contains_key(path))
+                .collect();
+            (current, target)
+        } else {
+            let current_head = self.get_head_snapshot().await?;
+            let moved: HashSet<&String> = current_head.keys()
+                .chain(target_head.keys())
+                .filter(|path| current_head.get(*path) != target_head.get(*path))
+                .collect();
+            
+            let status = self.get_status().await?;
+            if status.modified.iter().chain(&status.deleted).any(|path| moved.contains(path)) {
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            if status.untracked.iter().any(|path| moved.contains(path) && target.contains_key(path)) {
+                warn!("Untracked files would be overwritten by restoring an earlier operation");
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            
+            let only_moved = |snapshot: Snapshot| -> Snapshot {
+                snapshot.into_iter().filter(|(path, _)| moved.contains(path)).collect()
+            };
+            (only_moved(self.get_index_snapshot().await?), only_moved(target))
+        };
+        
+        self.materialize_snapshot(&current, &target).await?;
+        self.write_state(state)?;
+        
+        self.index.replace(state.index.clone().into_iter().collect()).await?;
+        self.save_index()
+    }
+    
+    /// Rewrites refs, stream metadata, HEAD and merge state to `state`.
+    fn write_state(&self, state: &RepoState) -> Result<()> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        let refs_dir = flow_dir.join("refs");
+        let streams_dir = refs_dir.join("streams");
+        
+        for entry in WalkDir::new(&refs_dir).min_depth(1).contents_first(true).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_dir() {
+                // Only succeeds once the directory is empty
+                if entry.path() != streams_dir {
+                    let _ = std::fs::remove_dir(entry.path());
+                }
+                continue;
+            }
+            
+            let name = entry.path()
+                .strip_prefix(&flow_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            if !state.refs.contains_key(&name) && !state.metadata.contains_key(&name) {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        
+        let files = state.refs.iter()
+            .map(|(name, commit_id)| (name, commit_id.to_string()))
+            .chain(state.metadata.iter().map(|(name, content)| (name, content.clone())));
+        for (name, content) in files {
+            let path = flow_dir.join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_atomic(&path, content)?;
+        }
+        
+        write_atomic(&flow_dir.join("HEAD"), &state.head)?;
+        
+        match &state.merge_head {
+            Some(merge_head) => self.write_merge_state(merge_head, &state.merge_conflicts),
+            None => self.clear_merge_state(),
+        }
+    }
+    
+    /// Whether `ancestor` is reachable from `descendant` through parent links.
+    pub async fn is_ancestor(&self, ancestor: &CommitId, descendant: &CommitId) -> Result<bool> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([descendant.clone()]);
+        
+        while let Some(commit_id) = queue.pop_front() {
+            if &commit_id == ancestor {
+                return Ok(true);
+            }
+            
+            if !seen.insert(commit_id.clone()) {
+                continue;
+            }
+            
+            let commit = self.object_store.load_commit(&commit_id).await?;
+            queue.extend(commit.parent_commits);
+        }
+        
+        Ok(false)
+    }
+    
+    /// Best common ancestor of two commits: a common ancestor that is not
+    /// itself an ancestor of another common ancestor. Criss-cross histories
+    /// can have several; the most recent one is used.
+    pub async fn merge_base(&self, a: &CommitId, b: &CommitId) -> Result<Option<CommitId>> {
+        let ours = self.ancestors(vec![a.clone()]).await?;
+        let theirs = self.ancestors(vec![b.clone()]).await?;
+        let common: Vec<CommitId> = ours.intersection(&theirs).cloned().collect();
+        
+        let mut parents = Vec::new();
+        for commit_id in &common {
+            parents.extend(self.object_store.load_commit(commit_id).await?.parent_commits);
+        }
+        let dominated = self.ancestors(parents).await?;
+        
+        let mut best: Option<FlowCommit> = None;
+        for commit_id in common.iter().filter(|id| !dominated.contains(*id)) {
+            let commit = self.object_store.load_commit(commit_id).await?;
+            if best.as_ref().is_none_or(|current| commit.timestamp > current.timestamp) {
+                best = Some(commit);
+            }
+        }
+        
+        Ok(best.map(|commit| commit.id))
+    }
+    
+    /// Every commit reachable from `starts`, including the starts themselves.
+    async fn ancestors(&self, starts: Vec<CommitId>) -> Result<HashSet<CommitId>> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from(starts);
+        
+        while let Some(commit_id) = queue.pop_front() {
+            if seen.insert(commit_id.clone()) {
+                queue.extend(self.object_store.load_commit(&commit_id).await?.parent_commits);
+            }
+        }
+        
+        Ok(seen)
+    }
+    
+    /// Merges another stream (or commit) into the current stream.
+    ///
+    /// Fast-forwards when possible. Otherwise every file is merged three
+    /// ways against the merge base; clean results are staged and recorded
+    /// as a two-parent commit. If any file conflicts, conflict markers are
+    /// written to the working tree, the merge is left in progress and
+    /// `MergeConflict` is returned; committing after `flow add` on the
+    /// resolved files concludes it.
+    pub async fn merge_stream(&self, source: &str, goal: Option<String>) -> Result<MergeOutcome> {
+        if self.merge_head()?.is_some() {
+            return Err(FlowError::InvalidRepoState(
+                "A merge is already in progress (commit it or run with --abort)".to_string()
+            ));
+        }
+        
+        let status = self.get_status().await?;
+        if !status.staged.is_empty() || !status.modified.is_empty() || !status.deleted.is_empty() {
+            return Err(FlowError::WorkingDirectoryNotClean);
+        }
+        
+        let theirs_id = self.resolve_commit(source).await?;
+        let theirs_commit = self.object_store.load_commit(&theirs_id).await?;
+        let theirs = self.get_commit_snapshot(&theirs_commit).await?;
+        
+        let would_overwrite_untracked = |target: &Snapshot| {
+            status.untracked.iter().any(|path| target.contains_key(path))
+        };
+        
+        let ours_id = match self.get_head_commit_id().await? {
+            Some(id) => id,
+            None => {
+                // Nothing committed yet: adopt the source history as-is
+                if would_overwrite_untracked(&theirs) {
+                    return Err(FlowError::WorkingDirectoryNotClean);
+                }
+                self.materialize_snapshot(&Snapshot::new(), &theirs).await?;
+                self.update_head(&theirs_id).await?;
+                return Ok(MergeOutcome::FastForward(theirs_id));
+            }
+        };
+        
+        if self.is_ancestor(&theirs_id, &ours_id).await? {
+            return Ok(MergeOutcome::UpToDate);
+        }
+        
+        let ours_commit = self.object_store.load_commit(&ours_id).await?;
+        let ours = self.get_commit_snapshot(&ours_commit).await?;
+        
+        if self.is_ancestor(&ours_id, &theirs_id).await? {
+            if would_overwrite_untracked(&theirs) {
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            self.materialize_snapshot(&ours, &theirs).await?;
+            self.update_head(&theirs_id).await?;
+            info!("Fast-forwarded to {}", theirs_id);
+            return Ok(MergeOutcome::FastForward(theirs_id));
+        }
+        
+        let base = match self.merge_base(&ours_id, &theirs_id).await? {
+            Some(base_id) => {
+                let base_commit = self.object_store.load_commit(&base_id).await?;
+                self.get_commit_snapshot(&base_commit).await?
+            }
+            None => Snapshot::new(),
+        };
+        
+        let ours_label = self.current_stream_name()?.unwrap_or_else(|| "HEAD".to_string());
+        
+        let mut paths: Vec<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+        paths.sort();
+        paths.dedup();
+        
+        let mut conflicts = Vec::new();
+        for path in paths {
+            let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+            
+            // Unchanged on their side, or changed identically on both
+            if t == b || o == t {
+                continue;
+            }
+            
+            if status.untracked.contains(path) {
+                warn!("Untracked file {} would be overwritten by merge", path);
+                return Err(FlowError::WorkingDirectoryNotClean);
+            }
+            
+            // Changed only on their side
+            if o == b {
+                self.apply_merged_entry(path, t).await?;
+                continue;
+            }
+            
+            match (o, t) {
+                (Some(o), Some(t)) => {
+                    let base_content = match b {
+                        Some(entry) => self.load_blob_content(&entry.hash).await?,
+                        None => Vec::new(),
+                    };
+                    let ours_content = self.load_blob_content(&o.hash).await?;
+                    let theirs_content = self.load_blob_content(&t.hash).await?;
+                    
+                    let texts = (
+                        Self::as_text(&base_content),
+                        Self::as_text(&ours_content),
+                        Self::as_text(&theirs_content),
+                    );
+                    let (base_text, ours_text, theirs_text) = match texts {
+                        (Some(base_text), Some(ours_text), Some(theirs_text)) => (base_text, ours_text, theirs_text),
+                        _ => {
+                            // Binary files cannot be merged line by line; keep ours
+                            conflicts.push(path.clone());
+                            continue;
+                        }
+                    };
+                    
+                    let result = merge3(base_text, ours_text, theirs_text, &ours_label, source);
+                    let mode = if b.map(|entry| entry.mode) == Some(o.mode) { t.mode } else { o.mode };
+                    
+                    if result.is_clean() {
+                        let hash = Hash::new(result.content.as_bytes());
+                        self.object_store.store_blob(&hash, result.content.into_bytes()).await?;
+                        self.apply_merged_entry(path, Some(&SnapshotEntry { hash, mode })).await?;
+                    } else {
+                        debug!("Conflict in {}", path);
+                        let full_path = self.root_path.join(path);
+                        std::fs::write(&full_path, result.content)?;
+                        conflicts.push(path.clone());
+                    }
+                }
+                (None, Some(t)) => {
+                    // Deleted here, modified there: leave their version for review
+                    let full_path = self.root_path.join(path);
+                    if let Some(parent) = full_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&full_path, self.load_blob_content(&t.hash).await?)?;
+                    set_file_mode(&full_path, t.mode)?;
+                    conflicts.push(path.clone());
+                }
+                // Modified here, deleted there: our version stays in place
+                _ => conflicts.push(path.clone()),
+            }
+        }
+        
+        self.save_index()?;
+        self.write_merge_state(&theirs_id, &conflicts)?;
+        
+        if !conflicts.is_empty() {
+            warn!("Merge of {} stopped with {} conflicted files", source, conflicts.len());
+            return Err(FlowError::MergeConflict(conflicts));
+        }
+        
+        let mut intention = Intention::new(
+            goal.unwrap_or_else(|| format!("Merge {} into {}", source, ours_label)),
+            Some(format!(
+                "Combines \"{}\" with \"{}\"",
+                ours_commit.intention.goal, theirs_commit.intention.goal
+            )),
+            None,
+            ours_commit.intention.confidence.min(theirs_commit.intention.confidence),
+        );
+        intention.add_tags(vec!["merge".to_string()]);
+        
+        let commit_id = self.commit_with_intention(intention).await?;
+        info!("Merged {} into {}", source, ours_label);
+        Ok(MergeOutcome::Merged(commit_id))
+    }
+    
+    /// Abandons an in-progress merge, restoring the working tree and index
+    /// to HEAD for every path the merge touched.
+    pub async fn abort_merge(&self) -> Result<()> {
+        if self.merge_head()?.is_none() {
+            return Err(FlowError::InvalidRepoState("No merge in progress".to_string()));
+        }
+        
+        let head = self.get_head_snapshot().await?;
+        let mut touched: Vec<String> = self.index.get_staged_files().await?.into_keys().collect();
+        touched.extend(self.merge_conflicts()?);
+        
+        // A placeholder "from" entry forces every touched path to be rewritten
+        // from HEAD, or removed if HEAD does not track it
+        let from: Snapshot = touched.iter()
+            .map(|path| (path.clone(), SnapshotEntry { hash: Hash::zero(), mode: 0 }))
+            .collect();
+        let to: Snapshot = touched.iter()
+            .filter_map(|path| head.get(path).map(|entry| (path.clone(), entry.clone())))
+            .collect();
+        self.materialize_snapshot(&from, &to).await?;
+        
+        self.index.clear().await?;
+        self.save_index()?;
+        self.clear_merge_state()?;
+        
+        info!("Merge aborted");
+        Ok(())
+    }
+    
+    /// The commit being merged into HEAD, if a merge is in progress.
+    pub fn merge_head(&self) -> Result<Option<CommitId>> {
+        let path = self.root_path.join(FLOWVERSION_DIR).join(MERGE_HEAD_FILE);
+        
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        Ok(Some(CommitId::parse(&std::fs::read_to_string(&path)?)?))
+    }
+    
+    /// Paths that conflicted in the in-progress merge.
+    pub fn merge_conflicts(&self) -> Result<Vec<String>> {
+        let path = self.root_path.join(FLOWVERSION_DIR).join(MERGE_CONFLICTS_FILE);
+        
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        Ok(std::fs::read_to_string(&path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+    
+    fn write_merge_state(&self, merge_head: &CommitId, conflicts: &[String]) -> Result<()> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        write_atomic(&flow_dir.join(MERGE_HEAD_FILE), merge_head.to_string())?;
+        
+        let mut listing = conflicts.join("\n");
+        if !listing.is_empty() {
+            listing.push('\n');
+        }
+        write_atomic(&flow_dir.join(MERGE_CONFLICTS_FILE), listing)?;
+        Ok(())
+    }
+    
+    fn clear_merge_state(&self) -> Result<()> {
+        let flow_dir = self.root_path.join(FLOWVERSION_DIR);
+        
+        for file in [MERGE_HEAD_FILE, MERGE_CONFLICTS_FILE] {
+            let path = flow_dir.join(file);
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Writes a merged file (or its removal) to the working tree and stages it.
+    async fn apply_merged_entry(&self, path: &str, entry: Option<&SnapshotEntry>) -> Result<()> {
+        let full_path = self.root_path.join(path);
+        
+        match entry {
+            Some(entry) => {
+                if let Some(parent) = full_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                
+                let content = self.load_blob_content(&entry.hash).await?;
+                let size = content.len() as u64;
+                std::fs::write(&full_path, content)?;
+                set_file_mode(&full_path, entry.mode)?;
+                
+                self.index.add_file_with_mode(path.to_string(), entry.hash.clone(), size, entry.mode).await
+            }
+            None => {
+                if full_path.exists() {
+                    std::fs::remove_file(&full_path)?;
+                    self.prune_empty_dirs(&full_path);
+                }
+                
+                self.index.stage_deletion(path.to_string()).await
+            }
+        }
+    }
+    
+    fn as_text(content: &[u8]) -> Option<&str> {
+        if content.contains(&0) {
+            return None;
+        }
+        std::str::from_utf8(content).ok()
+    }
+    
+    /// Rewrites the working tree from the `from` snapshot to the `to`
+    /// snapshot: files only in `from` are removed, everything that differs
+    /// is written from the object store with its recorded mode.
+    pub async fn materialize_snapshot(&self, from: &Snapshot, to: &Snapshot) -> Result<()> {
+        for path in from.keys().filter(|path| !to.contains_key(*path)) {
+            let full_path = self.root_path.join(path);
+            
+            if full_path.exists() {
+                debug!("Removing {}", path);
+                std::fs::remove_file(&full_path)?;
+                self.prune_empty_dirs(&full_path);
+            }
+        }
+        
+        for (path, entry) in to {
+            let full_path = self.root_path.join(path);
+            
+            if from.get(path) == Some(entry) && full_path.exists() {
+                continue;
+            }
+            
+            debug!("Writing {}", path);
+            if let Some(parent) = full_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            
+            let content = self.load_blob_content(&entry.hash).await?;
+            std::fs::write(&full_path, content)?;
+            set_file_mode(&full_path, entry.mode)?;
+        }
+        
+        Ok(())
+    }
+    
+    fn prune_empty_dirs(&self, removed_file: &Path) {
+        let mut dir = removed_file.parent();
+        
+        while let Some(current) = dir {
+            if current == self.root_path || std::fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+    
+    fn set_head_ref(&self, reference: &str) -> Result<()> {
+        write_atomic(
+            &self.root_path.join(FLOWVERSION_DIR).join("HEAD"),
+            format!("ref: {}\n", reference),
+        )?;
+        Ok(())
+    }
+    
+    /// The project state the next commit would record: HEAD plus the index.
+    pub async fn get_index_snapshot(&self) -> Result<Snapshot> {
+        let head = self.get_head_snapshot().await?;
+        let staged_files = self.index.get_staged_files().await?;
+        Ok(Self::apply_changeset(&head, staged_files).0)
+    }
+    
+    /// Classifies every path that differs between two snapshots.
+    pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> Vec<FileChange> {
+        let mut changes = Vec::new();
+        
+        for (path, entry) in new {
+            let mut change = match old.get(path) {
+                None => FileChange::new_added(path.clone(), entry.hash.clone()),
+                Some(previous) if previous.hash != entry.hash || previous.mode != entry.mode => {
+                    FileChange::new_modified(path.clone(), entry.hash.clone(), previous.hash.clone())
+                }
+                Some(_) => continue,
+            };
+            change.mode = entry.mode;
+            changes.push(change);
+        }
+        
+        for (path, entry) in old {
+            if !new.contains_key(path) {
+                let mut change = FileChange::new_deleted(path.clone(), entry.hash.clone());
+                change.mode = entry.mode;
+                changes.push(change);
+            }
+        }
+        
+        Self::detect_renames(changes)
+    }
+    
+    /// Applies the staged index entries to a parent snapshot.
+    ///
+    /// Returns the resulting snapshot together with the changes it
+    /// introduces, classified against the parent. A deletion and an
+    /// addition with identical content are recorded as a rename.
+    pub fn apply_changeset(
+        parent: &Snapshot,
+        staged: HashMap<String, IndexEntry>,
+    ) -> (Snapshot, Vec<FileChange>) {
+        let mut snapshot = parent.clone();
+        let mut changes = Vec::new();
+        
+        // Moves staged by `flow mv`, origin to destination; they count only
+        // while the origin's removal is staged too
+        let moves: HashMap<String, String> = staged.iter()
+            .filter(|(_, entry)| !entry.deleted)
+            .filter_map(|(path, entry)| entry.renamed_from.clone().map(|from| (from, path.clone())))
+            .filter(|(from, _)| parent.contains_key(from) && staged.get(from).is_some_and(|entry| entry.deleted))
+            .collect();
+        
+        let mut staged: Vec<_> = staged.into_iter().collect();
+        staged.sort_by(|a, b| a.0.cmp(&b.0));
+        
+        for (path, entry) in staged {
+            if entry.deleted {
+                if let Some(previous) = snapshot.remove(&path) {
+                    if !moves.contains_key(&path) {
+                        changes.push(FileChange::new_deleted(path, previous.hash));
+                    }
+                }
+                continue;
+            }
+            
+            let origin = entry.renamed_from.as_ref()
+                .filter(|from| moves.get(*from) == Some(&path))
+                .and_then(|from| parent.get(from).map(|previous| (from, previous)));
+            
+            let mut change = match (origin, parent.get(&path)) {
+                (Some((from, previous)), _) => {
+                    let mut renamed = FileChange::new_renamed(from.clone(), path.clone(), entry.hash.clone());
+                    renamed.previous_hash = Some(previous.hash.clone());
+                    renamed
+                }
+                (None, None) => FileChange::new_added(path.clone(), entry.hash.clone()),
+                (None, Some(previous)) if previous.hash != entry.hash || previous.mode != entry.mode => {
+                    FileChange::new_modified(path.clone(), entry.hash.clone(), previous.hash.clone())
+                }
+                // Staged content identical to the parent is not a change
+                (None, Some(_)) => continue,
+            };
+            change.mode = entry.mode;
+            changes.push(change);
+            
+            snapshot.insert(path, SnapshotEntry { hash: entry.hash, mode: entry.mode });
+        }
+        
+        (snapshot, Self::detect_renames(changes))
+    }
+    
+    fn detect_renames(changes: Vec<FileChange>) -> Vec<FileChange> {
+        let (deletions, mut others): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|change| matches!(change.change_type, ChangeType::Deleted));
+        
+        let mut result = Vec::new();
+        for deletion in deletions {
+            let previous_hash = deletion.previous_hash.clone();
+            let target = others.iter().position(|change| {
+                matches!(change.change_type, ChangeType::Added)
+                    && Some(&change.content_hash) == previous_hash.as_ref()
+            });
+            
+            match target {
+                Some(index) => {
+                    let added = others.remove(index);
+                    let mut renamed = FileChange::new_renamed(deletion.path, added.path, added.content_hash);
+                    renamed.mode = added.mode;
+                    result.push(renamed);
+                }
+                None => result.push(deletion),
+            }
+        }
+        
+        result.extend(others);
+        result.sort_by(|a, b| a.path.cmp(&b.path));
+        result
+    }
+    
+    /// Hashes every file in the working tree that is not ignored, plus the
+    /// files of `tracked` that are: ignore rules only apply to untracked
+    /// files.
+    pub fn scan_working_tree(&self, tracked: &Snapshot) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::new();
+        
+        let mut files = list_tracked_files(&self.root_path, &self.ignore_rules()?)?;
+        files.extend(tracked.keys().map(PathBuf::from).filter(|path| self.root_path.join(path).is_file()));
+        
+        for relative_path in files {
+            let key = normalize_path(&relative_path).to_string_lossy().to_string();
+            if snapshot.contains_key(&key) {
+                continue;
+            }
+            
+            let full_path = self.root_path.join(&relative_path);
+            let content = std::fs::read(&full_path)?;
+            let metadata = full_path.metadata()?;
+            
+            snapshot.insert(key, SnapshotEntry { hash: Hash::new(&content), mode: file_mode(&metadata) });
+        }
+        
+        Ok(snapshot)
+    }
+    
+    /// Ignore rules of the working tree: `.flowignore` files, the global
+    /// ignore file and built-in defaults.
+    pub fn ignore_rules(&self) -> Result<IgnoreRules> {
+        IgnoreRules::load(&self.root_path)
+    }
+    
+    /// The rule deciding whether `path` is ignored, if any. Tracked paths
+    /// are never ignored, so they give `None`.
+    pub async fn check_ignore(&self, path: &str) -> Result<Option<IgnoreMatch>> {
+        let path = normalize_repo_path(path);
+        let is_dir = self.root_path.join(&path).is_dir();
+        
+        let tracked = self.get_index_snapshot().await?;
+        let prefix = format!("{}/", path);
+        if tracked.contains_key(&path) || (is_dir && tracked.keys().any(|tracked| tracked.starts_with(&prefix))) {
+            return Ok(None);
+        }
+        
+        Ok(self.ignore_rules()?.explain(&path, is_dir))
+    }
+    
+    /// Name of the stream HEAD points at, or `None` when HEAD is detached
+    /// or on a quantum variant.
+    pub fn current_stream_name(&self) -> Result<Option<String>> {
+        Ok(self.head_ref()?
+            .and_then(|reference| reference.strip_prefix("refs/streams/").map(|name| name.to_string())))
+    }
+    
+    /// Quantum stream and variant HEAD points at, if any.
+    pub fn current_variant(&self) -> Result<Option<(String, String)>> {
+        Ok(self.head_ref()?.and_then(|reference| StreamStore::parse_variant_ref(&reference)))
+    }
+    
+    /// The ref a symbolic HEAD points at.
+    fn head_ref(&self) -> Result<Option<String>> {
+        let head_path = self.root_path.join(FLOWVERSION_DIR).join("HEAD");
+        
+        if !head_path.exists() {
+            return Ok(None);
+        }
+        
+        let head_content = std::fs::read_to_string(&head_path)?;
+        Ok(head_content
+            .trim()
+            .strip_prefix("ref: ")
+            .map(|reference| reference.to_string()))
+    }
+    
+    /// Persists the nested trees for a snapshot and returns the root hash.
+    pub async fn write_tree(&self, snapshot: &Snapshot) -> Result<Hash> {
+        let (root_hash, trees) = TreeObject::build_from_snapshot(snapshot);
+        
+        for tree in &trees {
+            self.object_store.store_tree(tree).await?;
+        }
+        
+        debug!("Wrote {} tree objects, root {}", trees.len(), root_hash);
+        Ok(root_hash)
+    }
+    
+    /// Flattens a stored tree into a path -> file snapshot.
+    pub async fn read_tree(&self, tree_hash: &Hash) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::new();
+        let mut pending = vec![(String::new(), tree_hash.clone())];
+        
+        while let Some((prefix, hash)) = pending.pop() {
+            let tree = self.object_store.load_tree(&hash).await?;
+            
+            for (name, entry) in tree.entries {
+                let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+                
+                match entry {
+                    TreeEntry::Blob { hash, mode } => {
+                        snapshot.insert(path, SnapshotEntry { hash, mode });
+                    }
+                    TreeEntry::Tree { hash } => pending.push((path, hash)),
+                }
+            }
+        }
+        
+        Ok(snapshot)
+    }
+    
+    /// Returns the full project state recorded by a commit.
+    pub async fn get_commit_snapshot(&self, commit: &FlowCommit) -> Result<Snapshot> {
+        self.read_tree(&commit.tree_hash).await
+    }
+    
+    /// Returns the full project state at HEAD, or an empty snapshot before
+    /// the first commit.
+    pub async fn get_head_snapshot(&self) -> Result<Snapshot> {
+        match self.get_head_commit_id().await? {
+            Some(commit_id) => {
+                let commit = self.object_store.load_commit(&commit_id).await?;
+                self.get_commit_snapshot(&commit).await
+            }
+            None => Ok(Snapshot::new()),
+        }
+    }
+    
+    async fn get_current_head_commits(&self) -> Result<Vec<CommitId>> {
+        match self.get_head_commit_id().await? {
+            Some(id) => Ok(vec![id]),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    async fn get_head_commit_id(&self) -> Result<Option<CommitId>> {
+        let head_path = self.root_path.join(FLOWVERSION_DIR).join("HEAD");
+        
+        if !head_path.exists() {
+            return Ok(None);
+        }
+        
+        let head_content = std::fs::read_to_string(&head_path)?;
+        
+        if head_content.starts_with("ref: ") {
+            // Symbolic reference
+            let ref_path = head_content.trim_start_matches("ref: ").trim();
+            let ref_file = self.root_path.join(FLOWVERSION_DIR).join(ref_path);
+            
+            if ref_file.exists() {
+                let commit_id_str = std::fs::read_to_string(&ref_file)?;
+                Ok(Some(CommitId::parse(&commit_id_str)?))
+            } else {
+                Ok(None)
+            }
+        } else {
+            // Direct commit ID
+            Ok(Some(CommitId::parse(&head_content)?))
+        }
+    }
+    
+    async fn update_head(&self, commit_id: &CommitId) -> Result<()> {
+        let head_path = self.root_path.join(FLOWVERSION_DIR).join("HEAD");
+        let head_content = std::fs::read_to_string(&head_path)?;
+        
+        if head_content.starts_with("ref: ") {
+            // Update the reference
+            let ref_path = head_content.trim_start_matches("ref: ").trim();
+            let ref_file = self.root_path.join(FLOWVERSION_DIR).join(ref_path);
+            
+            // Create directory if it doesn't exist
+            if let Some(parent) = ref_file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            
+            write_atomic(&ref_file, commit_id.to_string())?;
+        } else {
+            // Update HEAD directly
+            write_atomic(&head_path, commit_id.to_string())?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Signs subsequent commits with the user's key, generating it if needed.
+    pub fn enable_signing(&mut self) -> Result<()> {
+        if self.signing_key.is_none() {
+            self.signing_key = Some(KeyStore::user()?.load_or_generate()?);
+        }
+        Ok(())
+    }
+    
+    #[cfg(test)]
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+    
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+    
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[tokio::test]
+    async fn test_repository_init() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        assert!(!Repository::is_initialized(path).unwrap());
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        
+        assert!(Repository::is_initialized(path).unwrap());
+        
+        let flow_dir = path.join(".flowversion");
+        assert!(flow_dir.join("objects").exists());
+        assert!(flow_dir.join("refs").exists());
+        assert!(flow_dir.join("config.json").exists());
+        assert!(flow_dir.join("index.json").exists());
+        assert!(flow_dir.join("HEAD").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_repository_open() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        // Should fail before init
+        assert!(Repository::open(path).await.is_err());
+        
+        // Initialize and open
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        
+        assert_eq!(repo.config.name, "test-repo");
+        assert_eq!(repo.config.ai_mode, "local");
+    }
+    
+    #[tokio::test]
+    async fn test_status_sections() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        std::fs::write(path.join("committed.txt"), "v1").unwrap();
+        std::fs::write(path.join("removed.txt"), "gone soon").unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        repo.add_file("committed.txt").await.unwrap();
+        repo.add_file("removed.txt").await.unwrap();
+        repo.commit_with_intention(Intention::new("Initial".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        let status = repo.get_status().await.unwrap();
+        assert!(status.is_clean());
+        assert_eq!(status.stream.as_deref(), Some("main"));
+        
+        std::fs::write(path.join("staged.txt"), "new").unwrap();
+        repo.add_file("staged.txt").await.unwrap();
+        std::fs::write(path.join("committed.txt"), "v2").unwrap();
+        std::fs::remove_file(path.join("removed.txt")).unwrap();
+        std::fs::write(path.join("untracked.txt"), "?").unwrap();
+        
+        let status = repo.get_status().await.unwrap();
+        assert_eq!(status.staged.len(), 1);
+        assert_eq!(status.staged[0].path, "staged.txt");
+        assert!(matches!(status.staged[0].change_type, ChangeType::Added));
+        assert_eq!(status.modified, vec!["committed.txt".to_string()]);
+        assert_eq!(status.deleted, vec!["removed.txt".to_string()]);
+        assert_eq!(status.untracked, vec!["untracked.txt".to_string()]);
+    }
+    
+    #[tokio::test]
+    async fn test_add_paths_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "old.txt", "old", "First").await;
+        commit_file(&repo, "kept.txt", "kept", "Second").await;
+        
+        std::fs::create_dir_all(path.join("src/core")).unwrap();
+        std::fs::write(path.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(path.join("src/core/mod.rs"), "mod x;").unwrap();
+        std::fs::write(path.join("notes.md"), "notes").unwrap();
+        std::fs::write(path.join("build.log"), "noise").unwrap();
+        std::fs::write(path.join(".flowignore"), "*.log\n").unwrap();
+        std::fs::write(path.join("kept.txt"), "changed").unwrap();
+        std::fs::remove_file(path.join("old.txt")).unwrap();
+        
+        let paths = |staged: Vec<StagedPath>| -> Vec<(String, bool)> {
+            staged.into_iter().map(|change| (change.path, change.removed)).collect()
+        };
+        
+        // A dry run lists without staging
+        let planned = repo.add_paths(&["src".to_string()], AddMode::Paths, None, true).await.unwrap();
+        assert_eq!(paths(planned), vec![("src/core/mod.rs".to_string(), false), ("src/main.rs".to_string(), false)]);
+        assert!(repo.get_status().await.unwrap().staged.is_empty());
+        
+        // Tracked files only, including deletions
+        let staged = repo.add_paths(&[], AddMode::Update, None, false).await.unwrap();
+        assert_eq!(paths(staged), vec![("kept.txt".to_string(), false), ("old.txt".to_string(), true)]);
+        
+        let staged = repo.add_paths(&["*.rs".to_string()], AddMode::Paths, None, false).await.unwrap();
+        assert_eq!(staged.len(), 2);
+        
+        // Ignored files are skipped, and refused when named
+        assert!(matches!(
+            repo.add_paths(&["build.log".to_string()], AddMode::Paths, None, false).await,
+            Err(FlowError::PathIgnored(_))
+        ));
+        assert!(matches!(
+            repo.add_paths(&["missing".to_string()], AddMode::Paths, None, false).await,
+            Err(FlowError::FileNotFound(_))
+        ));
+        let staged = repo.add_paths(&[], AddMode::All, None, false).await.unwrap();
+        assert_eq!(paths(staged), vec![(".flowignore".to_string(), false), ("notes.md".to_string(), false)]);
+        
+        let status = repo.get_status().await.unwrap();
+        assert_eq!(status.staged.len(), 6);
+        assert!(status.untracked.is_empty() && status.modified.is_empty() && status.deleted.is_empty());
+        
+        // A staged new file deleted again simply drops out of the index
+        std::fs::remove_file(path.join("notes.md")).unwrap();
+        let staged = repo.add_paths(&["notes.md".to_string()], AddMode::Paths, None, false).await.unwrap();
+        assert_eq!(paths(staged), vec![("notes.md".to_string(), true)]);
+        assert!(repo.get_status().await.unwrap().staged.iter().all(|change| change.path != "notes.md"));
+    }
+    
+    #[tokio::test]
+    async fn test_move_paths_records_renames() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(path.join("src/untracked.txt"), "scratch").unwrap();
+        commit_file(&repo, "src/lib.rs", "pub mod a;\n", "Add lib").await;
+        commit_file(&repo, "notes.txt", "notes\n", "Add notes").await;
+        
+        // Directories move whole, untracked files included
+        let moved = repo.move_paths(&["src".to_string()], "core", false).await.unwrap();
+        assert_eq!(moved, vec![("src/lib.rs".to_string(), "core/lib.rs".to_string())]);
+        assert!(!path.join("src").exists());
+        assert!(path.join("core/untracked.txt").exists());
+        
+        // Moved twice and edited: still one rename from the committed path
+        repo.move_paths(&["notes.txt".to_string()], "todo.txt", false).await.unwrap();
+        repo.move_paths(&["todo.txt".to_string()], "core", false).await.unwrap();
+        std::fs::write(path.join("core/todo.txt"), "notes\nmore\n").unwrap();
+        repo.add_file("core/todo.txt").await.unwrap();
+        
+        assert!(matches!(
+            repo.move_paths(&["core/lib.rs".to_string()], "core/todo.txt", false).await,
+            Err(FlowError::InvalidRepoState(_))
+        ));
+        assert!(matches!(
+            repo.move_paths(&["core/untracked.txt".to_string()], "x.txt", false).await,
+            Err(FlowError::InvalidRepoState(_))
+        ));
+        
+        let commit_id = repo.commit_with_intention(Intention::new("Reorganize".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        let changes = repo.load_commit(&commit_id).await.unwrap().changes;
+        let renames: Vec<(&str, &str, bool)> = changes.iter()
+            .map(|change| match &change.change_type {
+                ChangeType::Renamed { from } => (from.as_str(), change.path.as_str(), change.previous_hash.as_ref() != Some(&change.content_hash)),
+                other => panic!("unexpected change {:?}", other),
+            })
+            .collect();
+        assert_eq!(renames, vec![("src/lib.rs", "core/lib.rs", false), ("notes.txt", "core/todo.txt", true)]);
+    }
+    
+    #[tokio::test]
+    async fn test_move_paths_rejects_overlapping_moves() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        std::fs::create_dir_all(path.join("a")).unwrap();
+        std::fs::create_dir_all(path.join("b")).unwrap();
+        std::fs::create_dir_all(path.join("d")).unwrap();
+        commit_file(&repo, "a/x", "from a", "Add a/x").await;
+        commit_file(&repo, "b/x", "from b", "Add b/x").await;
+        
+        assert!(matches!(
+            repo.move_paths(&["a/x".to_string(), "b/x".to_string()], "d", false).await,
+            Err(FlowError::InvalidRepoState(_))
+        ));
+        
+        for sources in [["a", "a/x"], ["a/x", "a"]] {
+            let sources: Vec<String> = sources.iter().map(|source| source.to_string()).collect();
+            assert!(matches!(repo.move_paths(&sources, "d", false).await, Err(FlowError::InvalidRepoState(_))));
+        }
+        
+        // Nothing moved on disk or in the index
+        assert_eq!(std::fs::read_to_string(path.join("a/x")).unwrap(), "from a");
+        assert_eq!(std::fs::read_to_string(path.join("b/x")).unwrap(), "from b");
+        assert!(!path.join("d/x").exists());
+        assert!(repo.get_status().await.unwrap().staged.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_remove_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        std::fs::create_dir_all(path.join("docs")).unwrap();
+        commit_file(&repo, "docs/a.md", "a", "Add a").await;
+        commit_file(&repo, "docs/b.md", "b", "Add b").await;
+        commit_file(&repo, "keep.txt", "keep", "Add keep").await;
+        
+        // Directories need --recursive, and local edits need --force
+        assert!(matches!(
+            repo.remove_paths(&["docs".to_string()], false, false, false).await,
+            Err(FlowError::InvalidRepoState(_))
+        ));
+        std::fs::write(path.join("docs/b.md"), "edited").unwrap();
+        assert!(matches!(
+            repo.remove_paths(&["docs".to_string()], false, true, false).await,
+            Err(FlowError::WorkingDirectoryNotClean)
+        ));
+        assert!(path.join("docs/a.md").exists());
+        
+        let removed = repo.remove_paths(&["docs".to_string()], false, true, true).await.unwrap();
+        assert_eq!(removed, vec!["docs/a.md".to_string(), "docs/b.md".to_string()]);
+        assert!(!path.join("docs").exists());
+        
+        // --cached only untracks
+        repo.remove_paths(&["keep.txt".to_string()], true, false, false).await.unwrap();
+        assert!(path.join("keep.txt").exists());
+        
+        let status = repo.get_status().await.unwrap();
+        assert_eq!(status.staged.len(), 3);
+        assert!(status.staged.iter().all(|change| matches!(change.change_type, ChangeType::Deleted)));
+        assert_eq!(status.untracked, vec!["keep.txt".to_string()]);
+    }
+    
+    #[tokio::test]
+    async fn test_add_intentions_become_sub_intentions() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "old.md", "old", "First").await;
+        
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(path.join("src/lexer.rs"), "lex").unwrap();
+        std::fs::write(path.join("src/parser.rs"), "parse").unwrap();
+        std::fs::write(path.join("README.md"), "readme").unwrap();
+        std::fs::remove_file(path.join("old.md")).unwrap();
+        
+        repo.add_paths(&["src".to_string()], AddMode::Paths, Some("Add the parser"), false).await.unwrap();
+        repo.add_paths(&["*.md".to_string()], AddMode::All, Some("Rewrite the docs"), false).await.unwrap();
+        
+        // Re-staging edited content keeps the intention
+        std::fs::write(path.join("src/parser.rs"), "parse more").unwrap();
+        repo.add_paths(&["src/parser.rs".to_string()], AddMode::Paths, None, false).await.unwrap();
+        
+        assert!(matches!(
+            repo.add_paths(&["src".to_string()], AddMode::Paths, Some("  "), false).await,
+            Err(FlowError::InvalidIntention(_))
+        ));
+        
+        let commit_id = repo.commit_with_intention(Intention::new("Parser".to_string(), None, None, 0.7))
+            .await
+            .unwrap();
+        let commit = repo.load_commit(&commit_id).await.unwrap();
+        
+        let subs: Vec<(&str, Vec<&str>)> = commit.sub_intentions.iter()
+            .map(|sub| (sub.intention.goal.as_str(), sub.paths.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(subs, vec![
+            ("Rewrite the docs", vec!["README.md", "old.md"]),
+            ("Add the parser", vec!["src/lexer.rs", "src/parser.rs"]),
+        ]);
+        let ids: Vec<_> = commit.sub_intentions.iter().map(|sub| sub.intention.id).collect();
+        assert_eq!(commit.intention.related_intentions, ids);
+        assert!(commit.sub_intentions.iter().all(|sub| sub.intention.id == sub.content_id()));
+        assert!(commit.verify_id());
+        assert_eq!(commit.sub_intentions[0].intention.confidence, 0.7);
+        
+        // The index starts over after the commit
+        std::fs::write(path.join("README.md"), "readme v2").unwrap();
+        let next = commit_file(&repo, "README.md", "readme v2", "Tweak").await;
+        assert!(repo.load_commit(&next).await.unwrap().sub_intentions.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_recent_intentions_for() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "a.txt", "1", "Create a").await;
+        commit_file(&repo, "b.txt", "1", "Create b").await;
+        commit_file(&repo, "a.txt", "2", "Revise a").await;
+        repo.move_paths(&["a.txt".to_string()], "c.txt", false).await.unwrap();
+        repo.commit_with_intention(Intention::new("Rename a".to_string(), None, None, 0.8)).await.unwrap();
+        
+        let goals = |intentions: Vec<Intention>| -> Vec<String> {
+            intentions.into_iter().map(|intention| intention.goal).collect()
+        };
+        assert_eq!(goals(repo.recent_intentions_for(&["a.txt".to_string()], 2).await.unwrap()), vec!["Rename a", "Revise a"]);
+        assert_eq!(goals(repo.recent_intentions_for(&["b.txt".to_string()], 5).await.unwrap()), vec!["Create b"]);
+        assert!(repo.recent_intentions_for(&["new.txt".to_string()], 5).await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_flowignore_hides_untracked_files_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "app.log", "tracked", "Track a log").await;
+        
+        std::fs::create_dir_all(path.join("logs")).unwrap();
+        std::fs::write(path.join(".flowignore"), "*.log\nlogs/\n").unwrap();
+        std::fs::write(path.join("debug.log"), "noise").unwrap();
+        std::fs::write(path.join("logs/today.txt"), "noise").unwrap();
+        std::fs::write(path.join("targets.rs"), "kept").unwrap();
+        
+        let status = repo.get_status().await.unwrap();
+        assert_eq!(status.untracked, vec![".flowignore".to_string(), "targets.rs".to_string()]);
+        assert!(status.deleted.is_empty());
+        
+        // Edits to a tracked file still show although a rule matches it
+        std::fs::write(path.join("app.log"), "edited").unwrap();
+        assert_eq!(repo.get_status().await.unwrap().modified, vec!["app.log".to_string()]);
+        
+        let rule = repo.check_ignore("logs/today.txt").await.unwrap().unwrap();
+        assert_eq!((rule.pattern.as_str(), rule.line, rule.matched_path.as_str()), ("logs/", 2, "logs"));
+        assert_eq!(repo.check_ignore("app.log").await.unwrap(), None);
+        assert_eq!(repo.check_ignore("targets.rs").await.unwrap(), None);
+    }
+    
+    #[tokio::test]
+    async fn test_staging_survives_reopen_and_classifies_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        std::fs::write(path.join("keep.txt"), "v1").unwrap();
+        std::fs::write(path.join("drop.txt"), "bye").unwrap();
+        std::fs::write(path.join("old_name.txt"), "moving").unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        for file in ["keep.txt", "drop.txt", "old_name.txt"] {
+            repo.add_file(file).await.unwrap();
+        }
+        repo.commit_with_intention(Intention::new("Initial".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        std::fs::write(path.join("keep.txt"), "v2").unwrap();
+        std::fs::write(path.join("fresh.txt"), "new").unwrap();
+        std::fs::remove_file(path.join("drop.txt")).unwrap();
+        std::fs::rename(path.join("old_name.txt"), path.join("new_name.txt")).unwrap();
+        for file in ["keep.txt", "fresh.txt", "drop.txt", "old_name.txt", "new_name.txt"] {
+            repo.add_file(file).await.unwrap();
+        }
+        
+        // A separate invocation sees the staged changes
+        let repo = Repository::open(path).await.unwrap();
+        let commit_id = repo
+            .commit_with_intention(Intention::new("Second".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        let commit = repo.object_store.load_commit(&commit_id).await.unwrap();
+        let kinds: Vec<(String, String)> = commit.changes.iter()
+            .map(|c| (c.path.clone(), format!("{:?}", c.change_type)))
+            .collect();
+        assert_eq!(kinds, vec![
+            ("drop.txt".to_string(), "Deleted".to_string()),
+            ("fresh.txt".to_string(), "Added".to_string()),
+            ("keep.txt".to_string(), "Modified".to_string()),
+            ("new_name.txt".to_string(), "Renamed { from: \"old_name.txt\" }".to_string()),
+        ]);
+        
+        let snapshot = repo.get_head_snapshot().await.unwrap();
+        let paths: Vec<&str> = snapshot.keys().map(|p| p.as_str()).collect();
+        assert_eq!(paths, vec!["fresh.txt", "keep.txt", "new_name.txt"]);
+        
+        // The index is empty again after committing
+        let repo = Repository::open(path).await.unwrap();
+        assert!(repo.get_status().await.unwrap().is_clean());
+    }
+    
+    #[tokio::test]
+    async fn test_resolve_commit_by_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        std::fs::write(path.join("file.txt"), "content").unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        repo.add_file("file.txt").await.unwrap();
+        let commit_id = repo
+            .commit_with_intention(Intention::new("Initial".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        assert_eq!(repo.resolve_commit("HEAD").await.unwrap(), commit_id);
+        assert_eq!(repo.resolve_commit(commit_id.as_str()).await.unwrap(), commit_id);
+        assert_eq!(repo.resolve_commit(&commit_id.as_str()[..8]).await.unwrap(), commit_id);
+        assert_eq!(repo.resolve_commit(&commit_id.as_str()[..8].to_uppercase()).await.unwrap(), commit_id);
+        
+        assert!(repo.resolve_commit("abc").await.is_err());
+        assert!(repo.resolve_commit("zzzzzzzz").await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_stream_create_switch_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        std::fs::write(path.join("shared.txt"), "base").unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        repo.add_file("shared.txt").await.unwrap();
+        repo.commit_with_intention(Intention::new("Base".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        let stream = repo
+            .create_stream("feature/x", StreamMode::Parallel, Some("Experiment".to_string()), None)
+            .await
+            .unwrap();
+        assert!(stream.head_commit.is_some());
+        assert!(matches!(
+            repo.create_stream("feature/x", StreamMode::Linear, None, None).await,
+            Err(FlowError::StreamAlreadyExists(_))
+        ));
+        assert!(matches!(
+            repo.create_stream("bad name", StreamMode::Linear, None, None).await,
+            Err(FlowError::InvalidStreamName(_))
+        ));
+        
+        repo.switch_stream("feature/x").await.unwrap();
+        assert_eq!(repo.current_stream_name().unwrap().as_deref(), Some("feature/x"));
+        
+        std::fs::create_dir_all(path.join("feature")).unwrap();
+        std::fs::write(path.join("feature/only.txt"), "feature").unwrap();
+        repo.add_file("feature/only.txt").await.unwrap();
+        repo.commit_with_intention(Intention::new("Feature work".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        // Local edits block the switch
+        std::fs::write(path.join("shared.txt"), "edited").unwrap();
+        assert!(matches!(repo.switch_stream("main").await, Err(FlowError::WorkingDirectoryNotClean)));
+        std::fs::write(path.join("shared.txt"), "base").unwrap();
+        
+        repo.switch_stream("main").await.unwrap();
+        assert!(!path.join("feature/only.txt").exists());
+        assert!(!path.join("feature").exists());
+        assert_eq!(std::fs::read_to_string(path.join("shared.txt")).unwrap(), "base");
+        
+        repo.switch_stream("feature/x").await.unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("feature/only.txt")).unwrap(), "feature");
+        repo.switch_stream("main").await.unwrap();
+        
+        let names: Vec<String> = repo.list_streams().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["feature/x".to_string(), "main".to_string()]);
+        
+        // Unmerged work needs --force
+        assert!(repo.delete_stream("feature/x", false).await.is_err());
+        assert!(repo.delete_stream("main", true).await.is_err());
+        repo.delete_stream("feature/x", true).await.unwrap();
+        assert!(matches!(repo.switch_stream("feature/x").await, Err(FlowError::StreamNotFound(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_commit_tags_follow_repository_taxonomy() {
+        use crate::core::tags::TagRule;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let config_path = path.join(FLOWVERSION_DIR).join("config.json");
+        let mut config = Config::load(&config_path).unwrap();
+        config.tags.push(TagRule {
+            tag: "frontend".to_string(),
+            words: Vec::new(),
+            patterns: Vec::new(),
+            paths: vec!["web/**".to_string()],
+        });
+        config.save(&config_path).unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "notes.txt", "notes", "Handle the address book").await;
+        
+        std::fs::create_dir_all(path.join("web")).unwrap();
+        std::fs::write(path.join("web/app.js"), "app").unwrap();
+        repo.add_file("web/app.js").await.unwrap();
+        let mut intention = Intention::new("Fix the login page".to_string(), None, None, 0.8);
+        intention.add_tags(vec!["hotfix".to_string()]);
+        let commit_id = repo.commit_with_intention(intention).await.unwrap();
+        
+        let commit = repo.load_commit(&commit_id).await.unwrap();
+        assert_eq!(commit.intention.tags, vec!["bugfix", "security", "frontend", "hotfix"]);
+        
+        assert_eq!(repo.tag_counts().await.unwrap(), vec![
+            ("bugfix".to_string(), 1),
+            ("frontend".to_string(), 1),
+            ("general".to_string(), 1),
+            ("hotfix".to_string(), 1),
+            ("security".to_string(), 1),
+        ]);
+    }
+    
+    async fn commit_file(repo: &Repository, path: &str, content: &str, goal: &str) -> CommitId {
+        std::fs::write(repo.root_path().join(path), content).unwrap();
+        repo.add_file(path).await.unwrap();
+        repo.commit_with_intention(Intention::new(goal.to_string(), None, None, 0.8))
+            .await
+            .unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_merge_stream_records_two_parent_commit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let base = commit_file(&repo, "shared.txt", "one\ntwo\nthree\n", "Base").await;
+        
+        repo.create_stream("feature", StreamMode::Linear, None, None).await.unwrap();
+        repo.switch_stream("feature").await.unwrap();
+        let theirs = commit_file(&repo, "shared.txt", "one\ntwo\nTHREE\n", "Shout three").await;
+        commit_file(&repo, "feature.txt", "feature", "Add feature file").await;
+        
+        repo.switch_stream("main").await.unwrap();
+        assert_eq!(repo.merge_base(&base, &theirs).await.unwrap(), Some(base.clone()));
+        let ours = commit_file(&repo, "shared.txt", "ONE\ntwo\nthree\n", "Shout one").await;
+        assert_eq!(repo.merge_base(&ours, &theirs).await.unwrap(), Some(base));
+        
+        let merge_id = match repo.merge_stream("feature", None).await.unwrap() {
+            MergeOutcome::Merged(id) => id,
+            other => panic!("expected a merge commit, got {:?}", other),
+        };
+        
+        let merge = repo.load_commit(&merge_id).await.unwrap();
+        assert!(merge.is_merge_commit());
+        assert_eq!(merge.parent_commits[0], ours);
+        assert_eq!(merge.intention.goal, "Merge feature into main");
+        
+        let ours_intention = repo.load_commit(&ours).await.unwrap().intention.id;
+        let feature_head = repo.resolve_commit("feature").await.unwrap();
+        let theirs_intention = repo.load_commit(&feature_head).await.unwrap().intention.id;
+        assert!(merge.intention.related_intentions.contains(&ours_intention));
+        assert!(merge.intention.related_intentions.contains(&theirs_intention));
+        
+        assert_eq!(std::fs::read_to_string(path.join("shared.txt")).unwrap(), "ONE\ntwo\nTHREE\n");
+        assert_eq!(std::fs::read_to_string(path.join("feature.txt")).unwrap(), "feature");
+        assert!(repo.get_status().await.unwrap().is_clean());
+        
+        assert_eq!(repo.merge_stream("feature", None).await.unwrap(), MergeOutcome::UpToDate);
+        
+        // The feature stream can now fast-forward to the merge
+        repo.switch_stream("feature").await.unwrap();
+        assert_eq!(
+            repo.merge_stream("main", None).await.unwrap(),
+            MergeOutcome::FastForward(merge_id)
+        );
+    }
+    
+    #[tokio::test]
+    async fn test_merge_stream_conflict_and_resolution() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        commit_file(&repo, "shared.txt", "a\nb\nc\n", "Base").await;
+        
+        repo.create_stream("feature", StreamMode::Linear, None, None).await.unwrap();
+        repo.switch_stream("feature").await.unwrap();
+        commit_file(&repo, "shared.txt", "a\ntheirs\nc\n", "Their edit").await;
+        
+        repo.switch_stream("main").await.unwrap();
+        let ours = commit_file(&repo, "shared.txt", "a\nours\nc\n", "Our edit").await;
+        
+        let result = repo.merge_stream("feature", None).await;
+        assert!(matches!(&result, Err(FlowError::MergeConflict(paths)) if paths == &vec!["shared.txt".to_string()]));
+        assert_eq!(
+            std::fs::read_to_string(path.join("shared.txt")).unwrap(),
+            "a\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> feature\nc\n"
+        );
+        
+        let status = repo.get_status().await.unwrap();
+        assert!(status.merging.is_some());
+        assert_eq!(status.unmerged, vec!["shared.txt".to_string()]);
+        assert!(status.modified.is_empty());
+        
+        // Unresolved conflicts block the commit; aborting restores HEAD
+        assert!(matches!(
+            repo.commit_with_intention(Intention::new("Merge".to_string(), None, None, 0.8)).await,
+            Err(FlowError::MergeConflict(_))
+        ));
+        repo.abort_merge().await.unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("shared.txt")).unwrap(), "a\nours\nc\n");
+        assert!(repo.get_status().await.unwrap().is_clean());
+        
+        // Resolving and committing concludes the merge
+        assert!(repo.merge_stream("feature", None).await.is_err());
+        std::fs::write(path.join("shared.txt"), "a\nboth\nc\n").unwrap();
+        repo.add_file("shared.txt").await.unwrap();
+        let merge_id = repo
+            .commit_with_intention(Intention::new("Merge feature".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        let merge = repo.load_commit(&merge_id).await.unwrap();
+        assert_eq!(merge.parent_commits.len(), 2);
+        assert_eq!(merge.parent_commits[0], ours);
+        assert_eq!(merge.intention.related_intentions.len(), 2);
+        assert!(repo.merge_head().unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_quantum_stream_observe_and_collapse() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let base = commit_file(&repo, "cache.txt", "none\n", "Base").await;
+        
+        let variants = vec!["lru".to_string(), "lfu".to_string()];
+        repo.create_quantum_stream("exp/cache", &variants, None, None).await.unwrap();
+        assert!(matches!(
+            repo.switch_variant("exp/cache", "arc").await,
+            Err(FlowError::InvalidQuantumFeature(_))
+        ));
+        
+        repo.switch_variant("exp/cache", "lru").await.unwrap();
+        assert_eq!(repo.get_status().await.unwrap().variant.as_deref(), Some("lru"));
+        let lru = commit_file(&repo, "cache.txt", "lru\n", "Use an LRU cache").await;
+        
+        repo.switch_variant("exp/cache", "lfu").await.unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("cache.txt")).unwrap(), "none\n");
+        let lfu = commit_file(&repo, "cache.txt", "lfu\n", "Use an LFU cache").await;
+        
+        assert_eq!(repo.resolve_commit("exp/cache@lru").await.unwrap(), lru);
+        assert_eq!(repo.resolve_commit("exp/cache").await.unwrap(), base);
+        
+        std::fs::write(path.join("lru.json"), r#"{"passed": 10, "failed": 0}"#).unwrap();
+        std::fs::write(
+            path.join("lfu.xml"),
+            r#"<testsuite><testcase name="a"/><testcase name="b"><failure/></testcase></testsuite>"#,
+        ).unwrap();
+        repo.observe_variant("exp/cache", "lru", &[path.join("lru.json")]).await.unwrap();
+        let evidence = repo.observe_variant("exp/cache", "lfu", &[path.join("lfu.xml")]).await.unwrap();
+        assert_eq!(evidence[0].commit, Some(lfu.clone()));
+        assert_eq!(evidence[0].failed, 1);
+        
+        // Unnamed collapse picks the best-tested variant and leaves the variant refs
+        let collapse = repo.collapse_quantum_stream("exp/cache", None).await.unwrap();
+        assert_eq!(collapse.variant, "lru");
+        assert_eq!(collapse.rejected, vec!["lfu".to_string()]);
+        assert_eq!(repo.current_stream_name().unwrap().as_deref(), Some("exp/cache"));
+        assert_eq!(std::fs::read_to_string(path.join("cache.txt")).unwrap(), "lru\n");
+        
+        let (stream, state) = repo.load_quantum_stream("exp/cache").unwrap();
+        assert!(matches!(stream.mode, StreamMode::Linear));
+        assert_eq!(stream.head_commit, Some(lru.clone()));
+        assert_eq!(state.variant("lfu").unwrap().evidence.len(), 1);
+        
+        // Rejected work stays reachable for audit
+        assert_eq!(repo.variant_head("exp/cache", "lru").unwrap(), Some(lru));
+        assert_eq!(repo.variant_head("exp/cache", "lfu").unwrap(), Some(lfu.clone()));
+        assert_eq!(repo.resolve_commit("exp/cache@lfu").await.unwrap(), lfu);
+        
+        assert!(matches!(
+            repo.collapse_quantum_stream("exp/cache", Some("lfu")).await,
+            Err(FlowError::QuantumStateCollapsed)
+        ));
+        assert!(matches!(
+            repo.observe_variant("exp/cache", "lfu", &[path.join("lfu.xml")]).await,
+            Err(FlowError::QuantumStateCollapsed)
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_signed_commit_detects_tampering() {
+        use crate::storage::encoding::ObjectKind;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let mut repo = Repository::open(path).await.unwrap();
+        repo.set_signing_key(SigningKey::from_bytes(&[3u8; 32]));
+        
+        let commit_id = commit_file(&repo, "signed.txt", "content", "Signed change").await;
+        assert!(repo.load_commit(&commit_id).await.unwrap().verify_signature());
+        
+        // Rewrite the goal directly in the stored commit file
+        let commit_file = path.join(FLOWVERSION_DIR)
+            .join("objects")
+            .join("commits")
+            .join(&commit_id.as_str()[..2])
+            .join(&commit_id.as_str()[2..]);
+        let stored = crate::storage::encoding::decode(&std::fs::read(&commit_file).unwrap(), ObjectKind::Commit).unwrap();
+        let stored = String::from_utf8(stored).unwrap();
+        assert!(stored.contains("Signed change"));
+        let forged = stored.replace("Signed change", "Forged change");
+        std::fs::write(&commit_file, crate::storage::encoding::encode(ObjectKind::Commit, forged.as_bytes()).unwrap()).unwrap();
+        
+        let tampered = repo.load_commit(&commit_id).await.unwrap();
+        assert!(matches!(tampered.signature_status(), crate::core::commit::SignatureStatus::Bad(_)));
+    }
+    
+    #[tokio::test]
+    async fn test_sign_commits_creates_the_key_only_to_sign() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        let key_dir = TempDir::new().unwrap();
+        std::env::set_var("FLOWVERSION_CONFIG_DIR", key_dir.path());
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let config_path = path.join(FLOWVERSION_DIR).join("config.json");
+        let mut config = Config::load(&config_path).unwrap();
+        config.user.sign_commits = true;
+        config.save(&config_path).unwrap();
+        
+        // Reading does not need the key
+        let repo = Repository::open(path).await.unwrap();
+        repo.get_status().await.unwrap();
+        assert!(KeyStore::new(key_dir.path()).load().unwrap().is_none());
+        
+        let commit_id = commit_file(&repo, "signed.txt", "content", "Signed change").await;
+        assert!(repo.load_commit(&commit_id).await.unwrap().verify_signature());
+        assert!(KeyStore::new(key_dir.path()).load().unwrap().is_some());
+    }
+    
+    #[tokio::test]
+    async fn test_checkout_commit_and_force() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let first = commit_file(&repo, "a.txt", "one", "First").await;
+        commit_file(&repo, "b.txt", "two", "Second").await;
+        
+        // Detaching at an older commit removes files it does not have
+        assert_eq!(repo.checkout(&first.as_str()[..8], false).await.unwrap(), None);
+        assert_eq!(repo.current_stream_name().unwrap(), None);
+        assert_eq!(repo.get_head_commit_id().await.unwrap(), Some(first));
+        assert!(!path.join("b.txt").exists());
+        
+        std::fs::write(path.join("a.txt"), "local edit").unwrap();
+        assert!(matches!(repo.checkout("main", false).await, Err(FlowError::WorkingDirectoryNotClean)));
+        
+        assert_eq!(repo.checkout("main", true).await.unwrap().as_deref(), Some("main"));
+        assert_eq!(std::fs::read_to_string(path.join("a.txt")).unwrap(), "one");
+        assert_eq!(std::fs::read_to_string(path.join("b.txt")).unwrap(), "two");
+        assert!(repo.get_status().await.unwrap().is_clean());
+    }
+    
+    #[tokio::test]
+    async fn test_gc_keeps_history_and_staged_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let base = "unchanged line\n".repeat(100);
+        commit_file(&repo, "notes.txt", &format!("{}first\n", base), "First").await;
+        let head = commit_file(&repo, "notes.txt", &format!("{}second\n", base), "Second").await;
+        
+        // Staged but not committed, and an object nothing refers to
+        std::fs::write(path.join("notes.txt"), format!("{}staged\n", base)).unwrap();
+        repo.add_file("notes.txt").await.unwrap();
+        let orphan = Hash::new(b"orphan");
+        repo.object_store.store_blob(&orphan, b"orphan".to_vec()).await.unwrap();
+        
+        let report = repo.gc(Duration::ZERO).await.unwrap();
+        assert_eq!(report.pruned, 1);
+        assert_eq!(report.deltas, 2);
+        assert!(!repo.object_store.blob_exists(&orphan).await);
+        
+        // History, trees and the staged blob all read back from the pack
+        let log = repo.get_log().await.unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].id, head);
+        let snapshot = repo.get_commit_snapshot(&log[1]).await.unwrap();
+        assert!(String::from_utf8(repo.load_blob_content(&snapshot["notes.txt"].hash).await.unwrap()).unwrap().ends_with("first\n"));
+        let staged = repo.index.get_file("notes.txt").await.unwrap().unwrap();
+        assert!(repo.load_blob_content(&staged.hash).await.is_ok());
+        
+        let stats = repo.object_store.get_stats().await.unwrap();
+        assert_eq!((stats.pack_count, stats.packed_count), (1, report.packed as u64));
+    }
+    
+    #[tokio::test]
+    async fn test_fsck_reports_and_repairs() {
+        use crate::core::fsck::{IssueKind, EXIT_DANGLING, EXIT_HASH_MISMATCH, EXIT_MISSING};
+        use crate::storage::reflog::ReflogEntry;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        let flow_dir = path.join(FLOWVERSION_DIR);
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        let first = commit_file(&repo, "a.txt", "one", "First").await;
+        let second = commit_file(&repo, "b.txt", "two", "Second").await;
+        
+        let report = repo.fsck(false).await.unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!(report.objects_checked, 6);
+        
+        // Swap a blob's content, drop a tree and leave an orphan behind
+        let blob = Hash::new(b"two");
+        let blob_path = flow_dir.join("objects/blobs").join(&blob.as_str()[..2]).join(&blob.as_str()[2..]);
+        std::fs::write(&blob_path, crate::storage::encoding::encode(ObjectKind::Blob, b"tampered").unwrap()).unwrap();
+        let first_tree = repo.load_commit(&first).await.unwrap().tree_hash;
+        std::fs::remove_file(flow_dir.join("objects/trees").join(&first_tree.as_str()[..2]).join(&first_tree.as_str()[2..])).unwrap();
+        repo.object_store.store_blob(&Hash::new(b"orphan"), b"orphan".to_vec()).await.unwrap();
+        
+        let report = repo.fsck(false).await.unwrap();
+        assert_eq!(report.count(IssueKind::HashMismatch), 1);
+        assert_eq!(report.count(IssueKind::Missing), 1);
+        assert_eq!(report.count(IssueKind::Dangling), 1);
+        assert_eq!(report.exit_code(), EXIT_MISSING | EXIT_HASH_MISMATCH | EXIT_DANGLING);
+        assert!(report.issues.iter().any(|issue| issue.subject == format!("tree {}", first_tree)));
+        
+        // A broken ref without a reflog cannot be repaired
+        std::fs::write(flow_dir.join("refs/streams/main"), "garbage").unwrap();
+        let report = repo.fsck(true).await.unwrap();
+        assert_eq!(report.unrepaired, vec!["refs/streams/main".to_string()]);
+        assert!(report.issues.iter().any(|issue| issue.kind == IssueKind::Corrupt && issue.subject == "ref refs/streams/main"));
+        
+        // With one, it goes back to the newest commit that is still intact
+        let reflog = Reflog::new(&flow_dir.join(LOGS_DIR));
+        reflog.append("refs/streams/main", &ReflogEntry::new(None, Some(first.clone()), "commit")).unwrap();
+        reflog.append("refs/streams/main", &ReflogEntry::new(Some(first), Some(second.clone()), "commit")).unwrap();
+        reflog.append("refs/streams/main", &ReflogEntry::new(Some(second.clone()), Some(CommitId::from_hash(Hash::new(b"gone"))), "commit")).unwrap();
+        
+        let report = repo.fsck(true).await.unwrap();
+        assert_eq!(report.repaired, vec![("refs/streams/main".to_string(), second.clone())]);
+        assert_eq!(repo.get_head_commit_id().await.unwrap(), Some(second));
+        assert!(!report.issues.iter().any(|issue| issue.subject.starts_with("ref ")));
+    }
+    
+    #[tokio::test]
+    async fn test_open_locked_excludes_other_writers() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open_locked(path).await.unwrap();
+        
+        assert!(matches!(Repository::open_locked(path).await, Err(FlowError::RepositoryLocked(_))));
+        // Readers are not blocked
+        Repository::open(path).await.unwrap();
+        
+        commit_file(&repo, "a.txt", "one", "Locked commit").await;
+        drop(repo);
+        
+        let repo = Repository::open_locked(path).await.unwrap();
+        assert_eq!(repo.get_log().await.unwrap().len(), 1);
+        // Atomic writes leave no temporary files behind
+        assert!(!std::fs::read_dir(path.join(FLOWVERSION_DIR)).unwrap()
+            .any(|entry| entry.unwrap().file_name().to_string_lossy().contains(".tmp-")));
+    }
+    
+    #[tokio::test]
+    async fn test_open_migrates_legacy_commits_under_the_lock() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let commit = FlowCommit::new(Intention::new("Legacy".to_string(), None, None, 0.8), vec![], Hash::new(b"legacy"), vec![]);
+        let legacy_id = uuid::Uuid::new_v4().to_string();
+        let mut value = serde_json::to_value(&commit).unwrap();
+        value["id"] = serde_json::json!(legacy_id);
+        let commits_dir = path.join(FLOWVERSION_DIR).join("objects").join("commits");
+        std::fs::create_dir_all(&commits_dir).unwrap();
+        std::fs::write(commits_dir.join(format!("{}.json", legacy_id)), serde_json::to_vec(&value).unwrap()).unwrap();
+        std::fs::write(path.join(FLOWVERSION_DIR).join("refs").join("streams").join("main"), &legacy_id).unwrap();
+        
+        // A reader cannot migrate while another process holds the lock
+        let lock = RepoLock::acquire(&path.join(FLOWVERSION_DIR)).unwrap();
+        assert!(matches!(Repository::open(path).await, Err(FlowError::RepositoryLocked(_))));
+        drop(lock);
+        
+        let repo = Repository::open(path).await.unwrap();
+        assert_eq!(repo.get_log().await.unwrap()[0].intention.goal, "Legacy");
+        assert!(!migration::has_legacy_commits(&path.join(FLOWVERSION_DIR)).unwrap());
+        // The lock taken for the migration is released again
+        Repository::open_locked(path).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_undo_and_restore_operations() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open_locked(path).await.unwrap();
+        
+        let first = commit_file(&repo, "a.txt", "one", "First").await;
+        assert_eq!(repo.record_operation("commit: First").await.unwrap().unwrap().id, 1);
+        std::fs::write(path.join("b.txt"), "two").unwrap();
+        repo.add_file("b.txt").await.unwrap();
+        repo.record_operation("add b.txt").await.unwrap();
+        let second = repo.commit_with_intention(Intention::new("Second".to_string(), None, None, 0.8)).await.unwrap();
+        repo.record_operation("commit: Second").await.unwrap();
+        repo.create_stream("feature", StreamMode::Linear, None, None).await.unwrap();
+        repo.record_operation("stream create feature").await.unwrap();
+        repo.switch_stream("feature").await.unwrap();
+        repo.record_operation("stream switch feature").await.unwrap();
+        assert_eq!(repo.record_operation("nothing").await.unwrap(), None);
+        
+        let reflog = Reflog::new(&path.join(FLOWVERSION_DIR).join(LOGS_DIR));
+        let main_log = reflog.read("refs/streams/main").unwrap();
+        assert_eq!(main_log.len(), 2);
+        assert_eq!((main_log[1].old.clone(), main_log[1].new.clone()), (Some(first.clone()), Some(second.clone())));
+        assert_eq!(main_log[1].operation, "commit: Second");
+        assert_eq!(reflog.read("refs/streams/feature").unwrap().len(), 1);
+        assert_eq!(reflog.read("HEAD").unwrap().len(), 3);
+        
+        // Each undo walks one operation further back
+        assert_eq!(repo.undo(false).await.unwrap().operation, "stream switch feature");
+        assert_eq!(repo.current_stream_name().unwrap().as_deref(), Some("main"));
+        assert_eq!(repo.undo(false).await.unwrap().operation, "stream create feature");
+        assert!(matches!(repo.load_stream("feature"), Err(FlowError::StreamNotFound(_))));
+        assert_eq!(repo.undo(false).await.unwrap().operation, "commit: Second");
+        assert_eq!(repo.get_head_commit_id().await.unwrap(), Some(first.clone()));
+        
+        // The undone commit's file is staged again, not lost
+        let status = repo.get_status().await.unwrap();
+        assert_eq!(status.staged.iter().map(|change| change.path.as_str()).collect::<Vec<_>>(), vec!["b.txt"]);
+        assert!(status.modified.is_empty());
+        
+        // Undoing an add leaves the working tree alone
+        std::fs::write(path.join("a.txt"), "edited").unwrap();
+        repo.add_file("a.txt").await.unwrap();
+        repo.record_operation("add a.txt").await.unwrap();
+        repo.undo(false).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("a.txt")).unwrap(), "edited");
+        assert_eq!(repo.get_status().await.unwrap().modified, vec!["a.txt".to_string()]);
+        
+        // Local edits are refused only on paths the restore would change
+        std::fs::write(path.join("b.txt"), "local").unwrap();
+        assert!(matches!(repo.restore_operation(5, false).await, Err(FlowError::WorkingDirectoryNotClean)));
+        std::fs::write(path.join("b.txt"), "two").unwrap();
+        repo.restore_operation(5, false).await.unwrap();
+        assert_eq!(repo.current_stream_name().unwrap().as_deref(), Some("feature"));
+        assert_eq!(repo.get_head_commit_id().await.unwrap(), Some(second.clone()));
+        let status = repo.get_status().await.unwrap();
+        assert!(status.staged.is_empty());
+        assert_eq!(status.modified, vec!["a.txt".to_string()]);
+        assert!(matches!(repo.restore_operation(99, false).await, Err(FlowError::OperationNotFound(_))));
+        
+        // A restore can be undone too
+        assert_eq!(repo.undo(false).await.unwrap().operation, "restore operation 5");
+        assert_eq!(repo.get_head_commit_id().await.unwrap(), Some(first));
+        
+        // Commits only the operation log refers to survive gc
+        repo.gc(Duration::ZERO).await.unwrap();
+        repo.load_commit(&second).await.unwrap();
+        assert!(repo.fsck(false).await.unwrap().is_clean());
+    }
+    
+    #[tokio::test]
+    async fn test_restore_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        let repo = Repository::open(path).await.unwrap();
+        
+        std::fs::create_dir_all(path.join("bin")).unwrap();
+        std::fs::write(path.join("bin/run.sh"), "#!/bin/sh\n").unwrap();
+        set_file_mode(&path.join("bin/run.sh"), 0o755).unwrap();
+        repo.add_file("bin/run.sh").await.unwrap();
+        let first = commit_file(&repo, "notes.txt", "v1", "First").await;
+        commit_file(&repo, "notes.txt", "v2", "Second").await;
+        std::fs::write(path.join("bin/extra.sh"), "extra").unwrap();
+        repo.add_file("bin/extra.sh").await.unwrap();
+        repo.commit_with_intention(Intention::new("Extra".to_string(), None, None, 0.8)).await.unwrap();
+        
+        // A deleted file comes back from the index with its mode
+        std::fs::remove_file(path.join("bin/run.sh")).unwrap();
+        assert_eq!(repo.restore_paths(&["bin".to_string()], None, false).await.unwrap(), vec!["bin/run.sh".to_string()]);
+        let metadata = path.join("bin/run.sh").metadata().unwrap();
+        assert_eq!(file_mode(&metadata), 0o755);
+        
+        // Restoring from an older commit removes what it did not have
+        repo.restore_paths(&["bin".to_string()], Some(&first.as_str()[..8]), false).await.unwrap();
+        assert!(!path.join("bin/extra.sh").exists());
+        
+        // Local edits are only discarded with force
+        std::fs::write(path.join("notes.txt"), "scratch").unwrap();
+        assert!(matches!(
+            repo.restore_paths(&["notes.txt".to_string()], Some(&first.as_str()[..8]), false).await,
+            Err(FlowError::WorkingDirectoryNotClean)
+        ));
+        repo.restore_paths(&["notes.txt".to_string()], Some(&first.as_str()[..8]), true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path.join("notes.txt")).unwrap(), "v1");
+        
+        assert!(matches!(
+            repo.restore_paths(&["missing.txt".to_string()], None, false).await,
+            Err(FlowError::FileNotFound(_))
+        ));
+    }
+    
+    #[test]
+    fn test_diff_snapshots() {
+        let entry = |content: &[u8]| SnapshotEntry { hash: Hash::new(content), mode: 0o644 };
+        
+        let mut old = Snapshot::new();
+        old.insert("same.txt".to_string(), entry(b"same"));
+        old.insert("changed.txt".to_string(), entry(b"v1"));
+        old.insert("gone.txt".to_string(), entry(b"gone"));
+        old.insert("before.txt".to_string(), entry(b"moved"));
+        
+        let mut new = Snapshot::new();
+        new.insert("same.txt".to_string(), entry(b"same"));
+        new.insert("changed.txt".to_string(), entry(b"v2"));
+        new.insert("after.txt".to_string(), entry(b"moved"));
+        new.insert("added.txt".to_string(), entry(b"added"));
+        
+        let changes: Vec<(String, String)> = Repository::diff_snapshots(&old, &new).iter()
+            .map(|c| (c.path.clone(), format!("{:?}", c.change_type)))
+            .collect();
+        
+        assert_eq!(changes, vec![
+            ("added.txt".to_string(), "Added".to_string()),
+            ("after.txt".to_string(), "Renamed { from: \"before.txt\" }".to_string()),
+            ("changed.txt".to_string(), "Modified".to_string()),
+            ("gone.txt".to_string(), "Deleted".to_string()),
+        ]);
+    }
+    
+    #[tokio::test]
+    async fn test_commit_snapshots_whole_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path();
+        
+        Repository::init(path, "test-repo", "local").await.unwrap();
+        std::fs::create_dir_all(path.join("src")).unwrap();
+        std::fs::write(path.join("README.md"), "readme").unwrap();
+        std::fs::write(path.join("src/main.rs"), "fn main() {}").unwrap();
+        
+        let repo = Repository::open(path).await.unwrap();
+        repo.add_file("README.md").await.unwrap();
+        repo.add_file("./src/main.rs").await.unwrap();
+        repo.commit_with_intention(Intention::new("Initial".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        // A second commit staging a single file must keep the other one
+        std::fs::write(path.join("README.md"), "updated readme").unwrap();
+        repo.add_file("README.md").await.unwrap();
+        repo.commit_with_intention(Intention::new("Update readme".to_string(), None, None, 0.8))
+            .await
+            .unwrap();
+        
+        let snapshot = repo.get_head_snapshot().await.unwrap();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot["README.md"].hash, Hash::new(b"updated readme"));
+        assert_eq!(snapshot["src/main.rs"].hash, Hash::new(b"fn main() {}"));
+        
+        let log = repo.get_log().await.unwrap();
+        let first = repo.get_commit_snapshot(&log[1]).await.unwrap();
+        assert_eq!(first["README.md"].hash, Hash::new(b"readme"));
     }
 }
\ No newline at end of file
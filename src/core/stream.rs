@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use crate::core::objects::{StreamId, CommitId};
+use crate::error::{FlowError, Result};
 use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,11 +10,35 @@ pub enum StreamMode {
     Quantum,    // Quantum superposition of features
 }
 
+impl std::str::FromStr for StreamMode {
+    type Err = FlowError;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(StreamMode::Linear),
+            "parallel" => Ok(StreamMode::Parallel),
+            "quantum" => Ok(StreamMode::Quantum),
+            other => Err(FlowError::ConfigError(format!("Unknown stream mode: {}", other))),
+        }
+    }
+}
+
+impl std::fmt::Display for StreamMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamMode::Linear => write!(f, "linear"),
+            StreamMode::Parallel => write!(f, "parallel"),
+            StreamMode::Quantum => write!(f, "quantum"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Stream {
     pub id: StreamId,
     pub name: String,
     pub mode: StreamMode,
+    #[serde(skip)] // stored in the stream's ref file, not in its metadata
     pub head_commit: Option<CommitId>,
     pub created_at: DateTime<Utc>,
     pub description: Option<String>,
@@ -37,6 +62,7 @@ impl Stream {
         Self::new("main".to_string(), StreamMode::Linear)
     }
     
+    #[cfg(test)]
     pub fn set_head(&mut self, commit_id: CommitId) {
         self.head_commit = Some(commit_id);
     }
@@ -45,9 +71,42 @@ impl Stream {
         self.description = Some(description);
     }
     
+    #[cfg(test)]
     pub fn is_main(&self) -> bool {
         self.name == "main"
     }
+    
+    /// Stream names become paths under `refs/streams/`, so they follow
+    /// roughly the same rules as git ref names.
+    pub fn validate_name(name: &str) -> Result<()> {
+        let invalid = |reason: &str| Err(FlowError::InvalidStreamName(format!("{} ({})", name, reason)));
+        
+        if name.is_empty() {
+            return invalid("name is empty");
+        }
+        
+        if name.len() > 100 {
+            return invalid("longer than 100 characters");
+        }
+        
+        if name == "HEAD" {
+            return invalid("reserved name");
+        }
+        
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')) {
+            return invalid("only letters, digits, '-', '_', '.' and '/' are allowed");
+        }
+        
+        if name.split('/').any(|component| component.is_empty() || component.starts_with('.')) {
+            return invalid("empty path component or component starting with '.'");
+        }
+        
+        if name.ends_with(".json") || name.ends_with(".lock") {
+            return invalid("must not end with .json or .lock");
+        }
+        
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -72,10 +131,32 @@ mod tests {
         assert!(stream.is_main());
     }
     
+    #[test]
+    fn test_validate_name() {
+        assert!(Stream::validate_name("main").is_ok());
+        assert!(Stream::validate_name("feature/auth-v2").is_ok());
+        assert!(Stream::validate_name("release_1.0").is_ok());
+        
+        for invalid in ["", "HEAD", "with space", "../escape", "a//b", "/lead", "trail/", ".hidden", "x.json"] {
+            assert!(
+                matches!(Stream::validate_name(invalid), Err(FlowError::InvalidStreamName(_))),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+    }
+    
+    #[test]
+    fn test_stream_mode_parsing() {
+        assert!(matches!("quantum".parse::<StreamMode>().unwrap(), StreamMode::Quantum));
+        assert_eq!(StreamMode::Parallel.to_string(), "parallel");
+        assert!("sideways".parse::<StreamMode>().is_err());
+    }
+    
     #[test]
     fn test_stream_operations() {
         let mut stream = Stream::new("test".to_string(), StreamMode::Linear);
-        let commit_id = CommitId::new();
+        let commit_id = CommitId::from_hash(crate::core::objects::Hash::new(b"commit"));
         
         stream.set_head(commit_id.clone());
         stream.set_description("Test stream".to_string());
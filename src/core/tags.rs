@@ -0,0 +1,185 @@
+use crate::error::{FlowError, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// One tag of a repository's taxonomy (`tags` in the config) and when it
+/// applies: any of its words or patterns in an intention's text, or any
+/// changed path matching one of its globs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRule {
+    pub tag: String,
+    /// Whole words or phrases, case-insensitive
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<String>,
+    /// Regular expressions, case-insensitive
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patterns: Vec<String>,
+    /// Globs over changed paths, e.g. `**/migrations/**`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+impl TagRule {
+    fn words(tag: &str, words: &[&str]) -> Self {
+        Self {
+            tag: tag.to_string(),
+            words: words.iter().map(|word| word.to_string()).collect(),
+            patterns: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+    
+    fn with_paths(mut self, paths: &[&str]) -> Self {
+        self.paths = paths.iter().map(|path| path.to_string()).collect();
+        self
+    }
+    
+    /// The taxonomy new repositories start with.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::words("bugfix", &["bug", "bugs", "fix", "fixes", "fixed", "fixing", "error", "errors", "crash"]),
+            Self::words("feature", &["feature", "features", "add", "adds", "added", "adding", "implement", "implements", "implemented", "implementing"]),
+            Self::words("refactor", &["refactor", "refactors", "refactored", "refactoring", "cleanup", "clean up", "optimize", "optimizes", "optimized", "optimizing"]),
+            Self::words("test", &["test", "tests", "tested", "testing"])
+                .with_paths(&["tests/**", "**/*_test.*", "**/test_*"]),
+            Self::words("documentation", &["doc", "docs", "document", "documentation", "comment", "comments", "readme"])
+                .with_paths(&["docs/**", "*.md"]),
+            Self::words("security", &["security", "secure", "auth", "authentication", "authorization", "login", "vulnerability"]),
+            Self::words("performance", &["performance", "speed", "speedup", "fast", "faster", "slow"]),
+            Self::words("ui", &["ui", "interface", "design"]),
+            Self::words("api", &["api", "apis", "endpoint", "endpoints", "service", "services"]),
+            Self::words("database", &["database", "db", "sql", "migration", "migrations", "schema"])
+                .with_paths(&["**/migrations/**", "*.sql"]),
+        ]
+    }
+}
+
+struct CompiledRule {
+    tag: String,
+    text: Vec<Regex>,
+    paths: GlobSet,
+}
+
+/// Compiled tag rules.
+pub struct TagTaxonomy {
+    rules: Vec<CompiledRule>,
+}
+
+impl TagTaxonomy {
+    pub fn new(rules: &[TagRule]) -> Result<Self> {
+        let invalid = |rule: &TagRule, e: &dyn std::fmt::Display| {
+            FlowError::ConfigError(format!("Invalid rule for tag {}: {}", rule.tag, e))
+        };
+        
+        let mut compiled = Vec::new();
+        for rule in rules {
+            validate_tag(&rule.tag).map_err(|e| invalid(rule, &e))?;
+            
+            let mut text = Vec::new();
+            if !rule.words.is_empty() {
+                let words: Vec<String> = rule.words.iter()
+                    .map(|word| regex::escape(word.trim()).replace(' ', r"\s+"))
+                    .collect();
+                let words = format!(r"(?i)\b(?:{})\b", words.join("|"));
+                text.push(Regex::new(&words).map_err(|e| invalid(rule, &e))?);
+            }
+            for pattern in &rule.patterns {
+                text.push(Regex::new(&format!("(?i){}", pattern)).map_err(|e| invalid(rule, &e))?);
+            }
+            
+            let mut paths = GlobSetBuilder::new();
+            for path in &rule.paths {
+                paths.add(Glob::new(path).map_err(|e| invalid(rule, &e))?);
+            }
+            let paths = paths.build().map_err(|e| invalid(rule, &e))?;
+            
+            compiled.push(CompiledRule { tag: rule.tag.clone(), text, paths });
+        }
+        
+        Ok(Self { rules: compiled })
+    }
+    
+    /// The built-in taxonomy, compiled once.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<TagTaxonomy> = OnceLock::new();
+        BUILTIN.get_or_init(|| Self::new(&TagRule::defaults()).expect("built-in tag rules compile"))
+    }
+    
+    /// Tags for an intention's text and the paths it changed, in rule order;
+    /// `general` when no rule applies.
+    pub fn tags_for(&self, text: &str, paths: &[String]) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for rule in &self.rules {
+            let matched = rule.text.iter().any(|regex| regex.is_match(text))
+                || paths.iter().any(|path| rule.paths.is_match(path));
+            if matched && !tags.contains(&rule.tag) {
+                tags.push(rule.tag.clone());
+            }
+        }
+        
+        if tags.is_empty() {
+            tags.push("general".to_string());
+        }
+        
+        tags
+    }
+}
+
+/// Tags are single words so they can be listed and queried unambiguously.
+pub fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(FlowError::InvalidIntention(format!(
+            "Invalid tag '{}': tags are single words without commas", tag
+        )));
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rule(tag: &str) -> TagRule {
+        TagRule { tag: tag.to_string(), words: Vec::new(), patterns: Vec::new(), paths: Vec::new() }
+    }
+    
+    #[test]
+    fn test_words_match_whole_words() {
+        let taxonomy = TagTaxonomy::builtin();
+        
+        assert_eq!(taxonomy.tags_for("Handle an empty address", &[]), vec!["general"]);
+        assert_eq!(taxonomy.tags_for("Collect user feedback", &[]), vec!["general"]);
+        assert_eq!(taxonomy.tags_for("Added DB pooling", &[]), vec!["feature", "database"]);
+        assert_eq!(taxonomy.tags_for("Clean  up the parser", &[]), vec!["refactor"]);
+    }
+    
+    #[test]
+    fn test_patterns_and_paths() {
+        let mut cve = rule("security");
+        cve.patterns.push(r"\bcve-\d{4}-\d+".to_string());
+        let mut database = rule("database");
+        database.paths.push("**/migrations/**".to_string());
+        let taxonomy = TagTaxonomy::new(&[cve, database]).unwrap();
+        
+        assert_eq!(taxonomy.tags_for("Patch CVE-2024-1234", &[]), vec!["security"]);
+        assert_eq!(
+            taxonomy.tags_for("Rename column", &["db/migrations/002_rename.sql".to_string()]),
+            vec!["database"]
+        );
+        assert_eq!(taxonomy.tags_for("Rename column", &["src/db.rs".to_string()]), vec!["general"]);
+    }
+    
+    #[test]
+    fn test_invalid_rules() {
+        let mut broken = rule("broken");
+        broken.patterns.push("(unclosed".to_string());
+        assert!(matches!(TagTaxonomy::new(&[broken]), Err(FlowError::ConfigError(_))));
+        
+        assert!(TagTaxonomy::new(&[rule("two words")]).is_err());
+        assert!(validate_tag("hotfix").is_ok());
+        assert!(validate_tag("a,b").is_err());
+    }
+}
@@ -15,6 +15,15 @@ pub enum FlowError {
     #[error("Working directory is not clean")]
     WorkingDirectoryNotClean,
     
+    #[error("Repository is locked: {0}")]
+    RepositoryLocked(String),
+    
+    #[error("Stale repository lock: {0}")]
+    StaleLock(String),
+    
+    #[error("Operation not found: {0}")]
+    OperationNotFound(String),
+    
     // Object storage errors
     #[error("Object not found: {0}")]
     ObjectNotFound(String),
@@ -25,12 +34,24 @@ pub enum FlowError {
     #[error("Invalid object hash: {0}")]
     InvalidObjectHash(String),
     
+    #[error("Corrupt object: {0}")]
+    CorruptObject(String),
+    
+    #[error("Integrity check found problems: {summary}")]
+    IntegrityCheckFailed { code: i32, summary: String },
+    
     // File system errors
     #[error("File not found: {0}")]
     FileNotFound(String),
     
-    #[error("Permission denied: {0}")]
-    PermissionDenied(String),
+    #[error("Path is ignored (see flow check-ignore): {0}")]
+    PathIgnored(String),
+    
+    #[error("Invalid pathspec: {0}")]
+    InvalidPathspec(String),
+    
+    #[error("None of the given paths are ignored")]
+    NothingIgnored,
     
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
@@ -40,9 +61,6 @@ pub enum FlowError {
     SerializationError(#[from] serde_json::Error),
     
     // AI service errors
-    #[error("AI service unavailable")]
-    AIServiceUnavailable,
-    
     #[error("AI analysis failed: {0}")]
     AIAnalysisFailed(String),
     
@@ -57,9 +75,6 @@ pub enum FlowError {
     #[error("Merge conflict in files: {0:?}")]
     MergeConflict(Vec<String>),
     
-    #[error("Cannot resolve conflict automatically")]
-    ConflictResolutionFailed,
-    
     // Stream (branch) errors
     #[error("Stream not found: {0}")]
     StreamNotFound(String),
@@ -77,6 +92,13 @@ pub enum FlowError {
     #[error("Invalid quantum feature: {0}")]
     InvalidQuantumFeature(String),
     
+    // Signing errors
+    #[error("Signing key error: {0}")]
+    SigningKeyError(String),
+    
+    #[error("Signature verification failed: {0}")]
+    SignatureVerificationFailed(String),
+    
     // Validation errors
     #[error("Invalid intention: {0}")]
     InvalidIntention(String),
@@ -84,6 +106,9 @@ pub enum FlowError {
     #[error("Invalid confidence score: {0} (must be between 0.0 and 1.0)")]
     InvalidConfidenceScore(f32),
     
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    
     #[error("Invalid commit ID: {0}")]
     InvalidCommitId(String),
     
@@ -91,9 +116,6 @@ pub enum FlowError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
     
-    #[error("Missing configuration: {0}")]
-    MissingConfig(String),
-    
     // Generic errors
     #[error("Internal error: {0}")]
     InternalError(String),
@@ -106,23 +128,14 @@ pub enum FlowError {
 }
 
 impl FlowError {
-    pub fn is_recoverable(&self) -> bool {
-        match self {
-            FlowError::AIServiceUnavailable => true,
-            FlowError::NetworkError(_) => true,
-            FlowError::ConfigError(_) => true,
-            _ => false,
-        }
-    }
-    
     pub fn exit_code(&self) -> i32 {
         match self {
             FlowError::RepoNotInitialized => 128,
             FlowError::RepoAlreadyExists => 128,
             FlowError::FileNotFound(_) => 1,
-            FlowError::PermissionDenied(_) => 13,
             FlowError::MergeConflict(_) => 1,
             FlowError::OperationCancelled => 130,
+            FlowError::IntegrityCheckFailed { code, .. } => *code,
             _ => 1,
         }
     }
@@ -1,7 +1,7 @@
 use clap::{Parser, Subcommand};
 use tracing::{info, error};
-use tracing_subscriber;
 
+mod ai;
 mod cli;
 mod core;
 mod error;
@@ -36,18 +36,68 @@ enum Commands {
     },
     /// Add files to staging area with optional intention
     Add {
-        /// Files to add
+        /// Files, directories or glob patterns to add
+        #[arg(required_unless_present_any = ["all", "update"])]
         files: Vec<String>,
         
-        /// Specify intention for the changes
+        /// Why these files change; recorded as a sub-intention of the next commit
         #[arg(long)]
         intention: Option<String>,
+        
+        /// Stage all changes, including new files and deletions
+        #[arg(short = 'A', long, conflicts_with = "update")]
+        all: bool,
+        
+        /// Stage changes to tracked files only
+        #[arg(short, long)]
+        update: bool,
+        
+        /// List what would be staged without staging it
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Remove tracked files from the working tree and the index
+    Rm {
+        /// Files, directories or glob patterns to remove
+        #[arg(required = true)]
+        paths: Vec<String>,
+        
+        /// Only untrack the files, keeping them in the working tree
+        #[arg(long)]
+        cached: bool,
+        
+        /// Allow removing directories
+        #[arg(short, long)]
+        recursive: bool,
+        
+        /// Remove files even if they have staged or local changes
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Move or rename a tracked file or directory
+    Mv {
+        /// Files or directories to move
+        #[arg(required = true)]
+        sources: Vec<String>,
+        
+        /// New path, or an existing directory to move the sources into
+        destination: String,
+        
+        /// Overwrite an existing destination file
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Show the working tree status
+    Status {
+        /// Machine-readable output (two status columns followed by the path)
+        #[arg(long)]
+        porcelain: bool,
     },
     /// Create an intent-based commit
     Commit {
         /// Main goal/intention for this commit
-        #[arg(long)]
-        intention: String,
+        #[arg(long, required_unless_present = "ai_suggest")]
+        intention: Option<String>,
         
         /// Context or background for the change
         #[arg(long)]
@@ -61,9 +111,21 @@ enum Commands {
         #[arg(long)]
         confidence: Option<f32>,
         
-        /// Let AI suggest the intention
+        /// Tag the intention, on top of those the repository's taxonomy derives (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        
+        /// Let AI suggest the intention from the staged diff; given fields override the suggestion
         #[arg(long)]
         ai_suggest: bool,
+        
+        /// Accept the suggested intention without asking
+        #[arg(short, long, requires = "ai_suggest")]
+        yes: bool,
+        
+        /// Sign the commit with your Ed25519 key (generated on first use)
+        #[arg(short = 'S', long)]
+        sign: bool,
     },
     /// Show commit history
     Log {
@@ -78,11 +140,98 @@ enum Commands {
         /// Show intentions in the log
         #[arg(long)]
         intentions: bool,
+        
+        /// Verify and show each commit's signature
+        #[arg(long)]
+        show_signature: bool,
+        
+        /// Only commits carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        
+        /// Only commits whose author contains this text
+        #[arg(long)]
+        author: Option<String>,
+        
+        /// Only commits from this date on (YYYY-MM-DD, RFC 3339, or an age like 3d)
+        #[arg(long)]
+        since: Option<String>,
+        
+        /// Only commits up to this date
+        #[arg(long)]
+        until: Option<String>,
+        
+        /// Only commits with at least this confidence (0.0-1.0)
+        #[arg(long)]
+        min_confidence: Option<f32>,
+        
+        /// Only commits whose goal matches this regex (case-insensitive)
+        #[arg(long)]
+        goal: Option<String>,
+        
+        /// Only commits that changed this path, following renames
+        #[arg(long)]
+        path: Option<String>,
+        
+        /// Filter expression, e.g. 'tag:bugfix and confidence<0.5'
+        ///
+        /// Terms are tag:, author:, goal:, path:, since:, until: and
+        /// confidence with :, <, <=, > or >=, combined with and, or, not
+        /// and parentheses. Combined with the other filters by and.
+        #[arg(long, short = 'q')]
+        query: Option<String>,
     },
+    /// Count how many commits carry each intention tag
+    Tags,
     /// Show changes in a commit
     Show {
-        /// Commit ID to show
+        /// Commit ID to show (full id, unique prefix or HEAD)
         commit_id: String,
+        
+        /// Show a per-file summary instead of the patch
+        #[arg(long)]
+        stat: bool,
+        
+        /// Only list the changed paths
+        #[arg(long)]
+        name_only: bool,
+        
+        /// Emit the commit and its changes as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Switch the working tree to a stream, or detach HEAD at a commit
+    Checkout {
+        /// Stream name or commit
+        target: String,
+        
+        /// Discard local changes that would be overwritten
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Restore working tree files from the index or a commit
+    Restore {
+        /// Files or directories to restore
+        #[arg(required = true)]
+        paths: Vec<String>,
+        
+        /// Commit or stream to restore from (defaults to the index)
+        #[arg(short, long)]
+        source: Option<String>,
+        
+        /// Discard local changes that would be overwritten
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Create, list, switch, merge and delete streams
+    Stream {
+        #[command(subcommand)]
+        action: StreamCommands,
+    },
+    /// Hold several candidate implementations in one stream and pick one
+    Quantum {
+        #[command(subcommand)]
+        action: QuantumCommands,
     },
     /// Show differences between commits or working directory
     Diff {
@@ -91,6 +240,213 @@ enum Commands {
         
         /// Second commit ID (optional)
         commit2: Option<String>,
+        
+        /// Compare the index against HEAD (or against the given commit)
+        #[arg(long, alias = "cached")]
+        staged: bool,
+        
+        /// Show a per-file summary instead of the patch
+        #[arg(long)]
+        stat: bool,
+        
+        /// Number of context lines around each change
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        context: usize,
+        
+        /// Ignore whitespace when comparing lines
+        #[arg(short = 'w', long)]
+        ignore_whitespace: bool,
+        
+        /// Ignore case when comparing lines
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        
+        /// Diff algorithm (myers, patience)
+        #[arg(long, default_value = "myers")]
+        algorithm: String,
+    },
+    /// Pack loose objects and prune unreachable ones
+    Gc {
+        /// Keep unreachable objects younger than this many days
+        #[arg(long, default_value_t = 14)]
+        grace_days: u64,
+    },
+    /// Verify refs and objects
+    ///
+    /// Exit status is a bitmask of what was found: 2 missing, 4 corrupt,
+    /// 8 hash mismatch, 16 dangling.
+    Fsck {
+        /// Reset broken refs to the newest intact commit in their reflog
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Show which ignore rule decides each path
+    ///
+    /// Prints `source:line:pattern<TAB>path` for every path a rule matches,
+    /// including negations. Exits with 1 when no path is ignored.
+    CheckIgnore {
+        /// Paths to check
+        #[arg(required = true)]
+        paths: Vec<String>,
+        
+        /// Also list paths no rule matches, as `::<TAB>path`
+        #[arg(short, long)]
+        non_matching: bool,
+    },
+    /// Roll refs, the index and the working tree back to before the last operation
+    Undo {
+        /// Discard unstaged changes that would be lost
+        #[arg(long)]
+        force: bool,
+    },
+    /// Inspect and restore the operation log
+    Op {
+        #[command(subcommand)]
+        action: OpCommands,
+    },
+    /// AI assistance for staged changes, from the provider set by ai_mode
+    Ai {
+        #[command(subcommand)]
+        action: AiCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum AiCommands {
+    /// Summarize staged changes: functions, imports and risk per file
+    Analyze {
+        /// Only analyze staged changes under these paths
+        paths: Vec<String>,
+        
+        /// Emit the analysis as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Suggest reviewers for the staged changes from who changed those files before
+    SuggestReviewers,
+}
+
+#[derive(Subcommand)]
+enum OpCommands {
+    /// List recorded operations, newest first
+    Log {
+        /// Show at most this many operations
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Restore refs, the index and the working tree to just after an operation
+    Restore {
+        /// Operation id, as shown by `flow op log`
+        id: u64,
+        
+        /// Discard unstaged changes that would be lost
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum QuantumCommands {
+    /// Create a quantum stream with named variants starting at HEAD (or at --from)
+    Create {
+        /// Stream name
+        name: String,
+        
+        /// Variant name (repeat for each variant, at least two)
+        #[arg(long = "variant", required = true)]
+        variants: Vec<String>,
+        
+        /// What the stream is for
+        #[arg(long)]
+        description: Option<String>,
+        
+        /// Commit or stream to start from
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Point HEAD at a variant so new commits advance it
+    Switch {
+        /// Quantum stream name
+        name: String,
+        
+        /// Variant name
+        variant: String,
+    },
+    /// Attach test results (JUnit XML or JSON) to a variant
+    Observe {
+        /// Quantum stream name
+        name: String,
+        
+        /// Variant name
+        variant: String,
+        
+        /// Test report files
+        #[arg(required = true)]
+        reports: Vec<std::path::PathBuf>,
+    },
+    /// Show variants, their heads and gathered evidence
+    Status {
+        /// Quantum stream name
+        name: String,
+    },
+    /// Keep one variant as the stream's linear head and archive the rest
+    Collapse {
+        /// Quantum stream name
+        name: String,
+        
+        /// Variant to keep (defaults to the one with the best evidence)
+        variant: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StreamCommands {
+    /// Create a new stream at HEAD (or at --from)
+    Create {
+        /// Stream name (e.g. feature/auth)
+        name: String,
+        
+        /// Stream mode (linear, parallel, quantum)
+        #[arg(long, default_value = "linear")]
+        mode: String,
+        
+        /// What the stream is for
+        #[arg(long)]
+        description: Option<String>,
+        
+        /// Commit or stream to start from
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// List all streams
+    List,
+    /// Switch HEAD and the working tree to another stream
+    Switch {
+        /// Stream name
+        name: String,
+    },
+    /// Delete a stream
+    Delete {
+        /// Stream name
+        name: String,
+        
+        /// Delete even if the stream is not merged into the current one
+        #[arg(long)]
+        force: bool,
+    },
+    /// Merge another stream (or commit) into the current stream
+    Merge {
+        /// Stream or commit to merge
+        #[arg(required_unless_present = "abort")]
+        source: Option<String>,
+        
+        /// Goal for the merge commit (defaults to "Merge <source> into <stream>")
+        #[arg(long)]
+        intention: Option<String>,
+        
+        /// Abandon an in-progress merge
+        #[arg(long, conflicts_with_all = ["source", "intention"])]
+        abort: bool,
     },
 }
 
@@ -102,6 +458,7 @@ async fn main() -> Result<()> {
     let log_level = if cli.verbose { "debug" } else { "info" };
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::new(log_level))
+        .with_writer(std::io::stderr) // keep stdout parseable (e.g. status --porcelain)
         .init();
     
     info!("FlowVersion starting");
@@ -114,7 +471,7 @@ async fn main() -> Result<()> {
         Err(e) => {
             error!("Command failed: {}", e);
             eprintln!("Error: {}", e);
-            std::process::exit(1);
+            std::process::exit(e.exit_code());
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
+use crate::core::tags::TagRule;
 use crate::error::{FlowError, Result};
+use crate::utils::fs::write_atomic;
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,12 +11,19 @@ pub struct Config {
     pub version: String,
     pub user: UserConfig,
     pub ai: AIConfig,
+    /// Tag taxonomy applied to commit intentions; repositories created
+    /// before it existed use the built-in rules
+    #[serde(default = "TagRule::defaults")]
+    pub tags: Vec<TagRule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserConfig {
     pub name: String,
     pub email: Option<String>,
+    /// Sign every commit with the user's Ed25519 key
+    #[serde(default)]
+    pub sign_commits: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +32,34 @@ pub struct AIConfig {
     pub provider: String,
     pub api_key: Option<String>,
     pub model: Option<String>,
+    /// Root of an OpenAI-compatible API, e.g. `http://localhost:11434/v1`
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Per-request timeout
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Retries after timeouts, connection failures, 429 and 5xx responses
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles with each further one
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+}
+
+fn default_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
 }
 
 impl Config {
@@ -40,13 +77,19 @@ impl Config {
             user: UserConfig {
                 name: user_name,
                 email: None,
+                sign_commits: false,
             },
             ai: AIConfig {
                 enabled: ai_enabled,
                 provider: if ai_enabled { ai_mode } else { "none".to_string() },
                 api_key: None,
                 model: None,
+                base_url: default_base_url(),
+                timeout_secs: default_timeout_secs(),
+                max_retries: default_max_retries(),
+                retry_backoff_ms: default_retry_backoff_ms(),
             },
+            tags: TagRule::defaults(),
         }
     }
     
@@ -68,25 +111,9 @@ impl Config {
             std::fs::create_dir_all(parent)?;
         }
         
-        std::fs::write(path, content)?;
+        write_atomic(path, content)?;
         Ok(())
     }
-    
-    pub fn set_user_email(&mut self, email: String) {
-        self.user.email = Some(email);
-    }
-    
-    pub fn set_ai_api_key(&mut self, api_key: String) {
-        self.ai.api_key = Some(api_key);
-    }
-    
-    pub fn set_ai_model(&mut self, model: String) {
-        self.ai.model = Some(model);
-    }
-    
-    pub fn is_ai_enabled(&self) -> bool {
-        self.ai.enabled && self.ai.api_key.is_some()
-    }
 }
 
 #[cfg(test)]
@@ -119,12 +146,25 @@ mod tests {
         assert_eq!(original_config.ai.enabled, loaded_config.ai.enabled);
     }
     
+    #[test]
+    fn test_ai_connection_defaults() {
+        // Configs written before the connection settings existed
+        let json = r#"{"name": "old", "ai_mode": "openai", "version": "0.1.0",
+            "user": {"name": "me", "email": null},
+            "ai": {"enabled": true, "provider": "openai", "api_key": null, "model": null}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        
+        assert_eq!(config.ai.base_url, "https://api.openai.com/v1");
+        assert_eq!(config.ai.timeout_secs, 30);
+        assert_eq!(config.ai.max_retries, 2);
+        assert_eq!(config.tags, TagRule::defaults());
+    }
+    
     #[test]
     fn test_local_mode_disables_ai() {
         let config = Config::new("test-repo".to_string(), "local".to_string());
         
         assert!(!config.ai.enabled);
         assert_eq!(config.ai.provider, "none");
-        assert!(!config.is_ai_enabled());
     }
 }
\ No newline at end of file
@@ -0,0 +1,217 @@
+use crate::error::{FlowError, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression as ZlibLevel};
+use std::io::{Read, Write};
+
+/// Every encoded object starts with this magic.
+pub const MAGIC: &[u8; 4] = b"FLOW";
+const FORMAT_VERSION: u8 = 1;
+/// magic (4) + version + kind + compression + reserved + logical size (u64 LE)
+pub const HEADER_LEN: usize = 16;
+
+/// Payloads smaller than this are not worth compressing.
+const COMPRESSION_THRESHOLD: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zlib,
+}
+
+/// Fixed-size header in front of every object's payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectHeader {
+    pub kind: ObjectKind,
+    pub compression: Compression,
+    /// Size of the decoded payload
+    pub logical_size: u64,
+}
+
+impl ObjectKind {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            ObjectKind::Blob => 1,
+            ObjectKind::Tree => 2,
+            ObjectKind::Commit => 3,
+        }
+    }
+    
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(ObjectKind::Blob),
+            2 => Some(ObjectKind::Tree),
+            3 => Some(ObjectKind::Commit),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectKind::Blob => write!(f, "blob"),
+            ObjectKind::Tree => write!(f, "tree"),
+            ObjectKind::Commit => write!(f, "commit"),
+        }
+    }
+}
+
+impl Compression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zlib => 1,
+        }
+    }
+    
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Zlib),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a payload, zlib-compressing it when that actually saves space.
+pub fn encode(kind: ObjectKind, payload: &[u8]) -> Result<Vec<u8>> {
+    let compressed = if payload.len() >= COMPRESSION_THRESHOLD {
+        let mut encoder = ZlibEncoder::new(Vec::new(), ZlibLevel::default());
+        encoder.write_all(payload)?;
+        Some(encoder.finish()?).filter(|compressed| compressed.len() < payload.len())
+    } else {
+        None
+    };
+    
+    let (compression, body) = match &compressed {
+        Some(compressed) => (Compression::Zlib, compressed.as_slice()),
+        None => (Compression::None, payload),
+    };
+    
+    let mut encoded = Vec::with_capacity(HEADER_LEN + body.len());
+    encoded.extend_from_slice(MAGIC);
+    encoded.push(FORMAT_VERSION);
+    encoded.push(kind.to_byte());
+    encoded.push(compression.to_byte());
+    encoded.push(0);
+    encoded.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    encoded.extend_from_slice(body);
+    
+    Ok(encoded)
+}
+
+/// Whether `bytes` use this encoding (as opposed to a legacy JSON object).
+pub fn is_encoded(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn read_header(bytes: &[u8]) -> Result<ObjectHeader> {
+    if bytes.len() < HEADER_LEN || !is_encoded(bytes) {
+        return Err(FlowError::CorruptObject("missing object header".to_string()));
+    }
+    
+    if bytes[4] != FORMAT_VERSION {
+        return Err(FlowError::CorruptObject(format!("unsupported object format version {}", bytes[4])));
+    }
+    
+    let kind = ObjectKind::from_byte(bytes[5])
+        .ok_or_else(|| FlowError::CorruptObject(format!("unknown object kind {}", bytes[5])))?;
+    let compression = Compression::from_byte(bytes[6])
+        .ok_or_else(|| FlowError::CorruptObject(format!("unknown compression {}", bytes[6])))?;
+    
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&bytes[8..HEADER_LEN]);
+    
+    Ok(ObjectHeader {
+        kind,
+        compression,
+        logical_size: u64::from_le_bytes(size),
+    })
+}
+
+/// Decodes an object, checking that it is of the `expected` kind.
+pub fn decode(bytes: &[u8], expected: ObjectKind) -> Result<Vec<u8>> {
+    let header = read_header(bytes)?;
+    
+    if header.kind != expected {
+        return Err(FlowError::CorruptObject(format!(
+            "expected a {:?} object, found {:?}",
+            expected, header.kind
+        )));
+    }
+    
+    let body = &bytes[HEADER_LEN..];
+    let payload = match header.compression {
+        Compression::None => body.to_vec(),
+        Compression::Zlib => {
+            // The size comes from disk: don't allocate for it up front, and
+            // read one byte past it so a payload that overruns is caught
+            let mut payload = Vec::new();
+            ZlibDecoder::new(body)
+                .take(header.logical_size.saturating_add(1))
+                .read_to_end(&mut payload)
+                .map_err(|e| FlowError::CorruptObject(format!("bad zlib payload: {}", e)))?;
+            payload
+        }
+    };
+    
+    if payload.len() as u64 != header.logical_size {
+        return Err(FlowError::CorruptObject(format!(
+            "payload is {} bytes, header says {}",
+            payload.len(),
+            header.logical_size
+        )));
+    }
+    
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_roundtrip_raw_and_compressed() {
+        let small = b"tiny";
+        let encoded = encode(ObjectKind::Blob, small).unwrap();
+        assert_eq!(read_header(&encoded).unwrap().compression, Compression::None);
+        assert_eq!(decode(&encoded, ObjectKind::Blob).unwrap(), small);
+        
+        let large = "repeated line\n".repeat(1000).into_bytes();
+        let encoded = encode(ObjectKind::Tree, &large).unwrap();
+        let header = read_header(&encoded).unwrap();
+        assert_eq!(header.compression, Compression::Zlib);
+        assert_eq!(header.logical_size, large.len() as u64);
+        assert!(encoded.len() < large.len() / 10);
+        assert_eq!(decode(&encoded, ObjectKind::Tree).unwrap(), large);
+    }
+    
+    #[test]
+    fn test_decode_rejects_bad_objects() {
+        let encoded = encode(ObjectKind::Commit, b"commit payload").unwrap();
+        assert!(matches!(decode(&encoded, ObjectKind::Blob), Err(FlowError::CorruptObject(_))));
+        assert!(matches!(decode(b"{\"json\": true}", ObjectKind::Blob), Err(FlowError::CorruptObject(_))));
+        
+        let mut truncated = encode(ObjectKind::Blob, &[7u8; 500]).unwrap();
+        truncated.truncate(truncated.len() - 3);
+        assert!(decode(&truncated, ObjectKind::Blob).is_err());
+    }
+    
+    #[test]
+    fn test_decode_rejects_corrupt_sizes() {
+        for payload in [b"tiny".to_vec(), "repeated line\n".repeat(1000).into_bytes()] {
+            for size in [1u64 << 62, u64::MAX, 3] {
+                let mut encoded = encode(ObjectKind::Blob, &payload).unwrap();
+                encoded[8..HEADER_LEN].copy_from_slice(&size.to_le_bytes());
+                
+                assert!(matches!(decode(&encoded, ObjectKind::Blob), Err(FlowError::CorruptObject(_))));
+            }
+        }
+    }
+}
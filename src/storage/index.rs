@@ -1,18 +1,35 @@
 use serde::{Deserialize, Serialize};
 use crate::error::{FlowError, Result};
 use crate::core::objects::Hash;
+use crate::utils::fs::write_atomic;
 use std::collections::HashMap;
 use std::path::Path;
 use tokio::sync::RwLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IndexEntry {
     pub hash: Hash,
     pub size: u64,
     pub modified_time: u64,
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+    /// Staged removal of a path that exists in the parent tree
+    #[serde(default)]
+    pub deleted: bool,
+    /// Path the file was moved from with `flow mv`, so the commit records
+    /// a rename even when the content changed too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_from: Option<String>,
+    /// Why this path changed, from `flow add --intention`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intention: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_mode() -> u32 {
+    0o644
+}
+
+#[derive(Debug)]
 pub struct Index {
     entries: RwLock<HashMap<String, IndexEntry>>,
 }
@@ -58,12 +75,22 @@ impl Index {
             std::fs::create_dir_all(parent)?;
         }
         
-        std::fs::write(path, content)?;
+        write_atomic(path, content)?;
         Ok(())
     }
     
+    #[cfg(test)]
     pub async fn add_file(&self, path: String, hash: Hash, size: u64) -> Result<()> {
+        self.add_file_with_mode(path, hash, size, default_mode()).await
+    }
+    
+    /// Stages new content for `path`; a pending move keeps its origin, and
+    /// the path its intention.
+    pub async fn add_file_with_mode(&self, path: String, hash: Hash, size: u64, mode: u32) -> Result<()> {
         let mut entries = self.entries.write().await;
+        let previous = entries.get(&path);
+        let renamed_from = previous.and_then(|entry| entry.renamed_from.clone());
+        let intention = previous.and_then(|entry| entry.intention.clone());
         
         let entry = IndexEntry {
             hash,
@@ -72,18 +99,69 @@ impl Index {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            mode,
+            deleted: false,
+            renamed_from,
+            intention,
         };
         
         entries.insert(path, entry);
         Ok(())
     }
     
+    /// Stages the removal of `path`; the next commit drops it from the tree.
+    pub async fn stage_deletion(&self, path: String) -> Result<()> {
+        let mut entries = self.entries.write().await;
+        let intention = entries.get(&path).and_then(|entry| entry.intention.clone());
+        
+        let entry = IndexEntry {
+            hash: Hash::zero(),
+            size: 0,
+            modified_time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            mode: default_mode(),
+            deleted: true,
+            renamed_from: None,
+            intention,
+        };
+        
+        entries.insert(path, entry);
+        Ok(())
+    }
+    
+    /// Records that the staged `path` was moved from `from`.
+    pub async fn set_renamed_from(&self, path: &str, from: Option<String>) -> Result<()> {
+        let mut entries = self.entries.write().await;
+        match entries.get_mut(path) {
+            Some(entry) => {
+                entry.renamed_from = from;
+                Ok(())
+            }
+            None => Err(FlowError::FileNotFound(path.to_string())),
+        }
+    }
+    
+    /// Sets why the staged `path` changed.
+    pub async fn set_intention(&self, path: &str, intention: Option<String>) -> Result<()> {
+        let mut entries = self.entries.write().await;
+        match entries.get_mut(path) {
+            Some(entry) => {
+                entry.intention = intention;
+                Ok(())
+            }
+            None => Err(FlowError::FileNotFound(path.to_string())),
+        }
+    }
+    
     pub async fn remove_file(&self, path: &str) -> Result<()> {
         let mut entries = self.entries.write().await;
         entries.remove(path);
         Ok(())
     }
     
+    #[cfg(test)]
     pub async fn get_file(&self, path: &str) -> Result<Option<IndexEntry>> {
         let entries = self.entries.read().await;
         Ok(entries.get(path).cloned())
@@ -94,9 +172,11 @@ impl Index {
         Ok(entries.clone())
     }
     
-    pub async fn is_file_staged(&self, path: &str) -> Result<bool> {
-        let entries = self.entries.read().await;
-        Ok(entries.contains_key(path))
+    /// Replaces every entry, e.g. when an operation is undone.
+    pub async fn replace(&self, new_entries: HashMap<String, IndexEntry>) -> Result<()> {
+        let mut entries = self.entries.write().await;
+        *entries = new_entries;
+        Ok(())
     }
     
     pub async fn clear(&self) -> Result<()> {
@@ -104,21 +184,6 @@ impl Index {
         entries.clear();
         Ok(())
     }
-    
-    pub async fn get_status(&self) -> Result<IndexStatus> {
-        let entries = self.entries.read().await;
-        
-        Ok(IndexStatus {
-            staged_count: entries.len(),
-            total_size: entries.values().map(|e| e.size).sum(),
-        })
-    }
-}
-
-#[derive(Debug)]
-pub struct IndexStatus {
-    pub staged_count: usize,
-    pub total_size: u64,
 }
 
 #[cfg(test)]
@@ -134,9 +199,6 @@ mod tests {
         // Add file
         index.add_file("test.txt".to_string(), hash.clone(), 100).await.unwrap();
         
-        // Check if file is staged
-        assert!(index.is_file_staged("test.txt").await.unwrap());
-        
         // Get file entry
         let entry = index.get_file("test.txt").await.unwrap();
         assert!(entry.is_some());
@@ -149,7 +211,7 @@ mod tests {
         
         // Remove file
         index.remove_file("test.txt").await.unwrap();
-        assert!(!index.is_file_staged("test.txt").await.unwrap());
+        assert!(index.get_file("test.txt").await.unwrap().is_none());
     }
     
     #[tokio::test]
@@ -171,18 +233,17 @@ mod tests {
     }
     
     #[tokio::test]
-    async fn test_index_status() {
-        let index = Index::new();
-        
-        let status = index.get_status().await.unwrap();
-        assert_eq!(status.staged_count, 0);
-        assert_eq!(status.total_size, 0);
+    async fn test_index_stage_deletion() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("index.json");
         
-        index.add_file("file1.txt".to_string(), Hash::new(b"content1"), 50).await.unwrap();
-        index.add_file("file2.txt".to_string(), Hash::new(b"content2"), 75).await.unwrap();
+        let index = Index::new();
+        index.stage_deletion("old.txt".to_string()).await.unwrap();
+        index.save(&index_path).unwrap();
         
-        let status = index.get_status().await.unwrap();
-        assert_eq!(status.staged_count, 2);
-        assert_eq!(status.total_size, 125);
+        let loaded_index = Index::load(&index_path).unwrap();
+        let entry = loaded_index.get_file("old.txt").await.unwrap().unwrap();
+        assert!(entry.deleted);
+        assert!(entry.hash.is_zero());
     }
 }
\ No newline at end of file
@@ -0,0 +1,122 @@
+use crate::error::{FlowError, Result};
+use crate::utils::fs::user_config_dir;
+use ed25519_dalek::SigningKey;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const PRIVATE_KEY_FILE: &str = "signing_key";
+const PUBLIC_KEY_FILE: &str = "signing_key.pub";
+
+/// The user's Ed25519 commit signing key, stored hex-encoded in the user
+/// configuration directory (`~/.config/flowversion` on Linux) so it is
+/// shared by every repository.
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+    
+    /// The per-user key store; `FLOWVERSION_CONFIG_DIR` overrides the
+    /// platform configuration directory.
+    pub fn user() -> Result<Self> {
+        let config_dir = user_config_dir()
+            .ok_or_else(|| FlowError::SigningKeyError("Cannot locate the user configuration directory".to_string()))?;
+        Ok(Self::new(&config_dir))
+    }
+    
+    pub fn load(&self) -> Result<Option<SigningKey>> {
+        let path = self.dir.join(PRIVATE_KEY_FILE);
+        
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let encoded = std::fs::read_to_string(&path)?;
+        let seed: [u8; 32] = hex::decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| FlowError::SigningKeyError(format!("{} is not a valid Ed25519 key", path.display())))?;
+        
+        Ok(Some(SigningKey::from_bytes(&seed)))
+    }
+    
+    /// Loads the signing key, generating and saving a new one on first use.
+    pub fn load_or_generate(&self) -> Result<SigningKey> {
+        if let Some(key) = self.load()? {
+            return Ok(key);
+        }
+        
+        let mut seed = [0u8; 32];
+        getrandom::getrandom(&mut seed)
+            .map_err(|e| FlowError::SigningKeyError(format!("Cannot generate key: {}", e)))?;
+        let key = SigningKey::from_bytes(&seed);
+        
+        std::fs::create_dir_all(&self.dir)?;
+        let private_path = self.dir.join(PRIVATE_KEY_FILE);
+        create_private(&private_path)?.write_all(hex::encode(seed).as_bytes())?;
+        std::fs::write(
+            self.dir.join(PUBLIC_KEY_FILE),
+            format!("{}\n", hex::encode(key.verifying_key().to_bytes())),
+        )?;
+        
+        info!("Generated signing key in {}", self.dir.display());
+        Ok(key)
+    }
+}
+
+/// Creates a file only its owner can read, with no window in which the
+/// umask's default permissions apply.
+#[cfg(unix)]
+fn create_private(path: &Path) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    Ok(OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new().write(true).create_new(true).open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_generate_and_reload_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = KeyStore::new(temp_dir.path());
+        
+        assert!(store.load().unwrap().is_none());
+        
+        let key = store.load_or_generate().unwrap();
+        let reloaded = store.load().unwrap().unwrap();
+        assert_eq!(key.to_bytes(), reloaded.to_bytes());
+        assert_eq!(store.load_or_generate().unwrap().to_bytes(), key.to_bytes());
+        
+        let public = std::fs::read_to_string(temp_dir.path().join(PUBLIC_KEY_FILE)).unwrap();
+        assert_eq!(public.trim(), hex::encode(key.verifying_key().to_bytes()));
+        
+        std::fs::write(temp_dir.path().join(PRIVATE_KEY_FILE), "not hex").unwrap();
+        assert!(matches!(store.load(), Err(FlowError::SigningKeyError(_))));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_private_key_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let temp_dir = TempDir::new().unwrap();
+        KeyStore::new(temp_dir.path()).load_or_generate().unwrap();
+        
+        let metadata = std::fs::metadata(temp_dir.path().join(PRIVATE_KEY_FILE)).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
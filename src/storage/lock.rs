@@ -0,0 +1,153 @@
+use crate::error::{FlowError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const LOCK_FILE: &str = "lock";
+
+/// How long an unreadable lock file may exist before it is considered
+/// abandoned; the holder writes its details right after creating it.
+const UNREADABLE_LOCK_GRACE: Duration = Duration::from_secs(10);
+
+/// What a lock file records about its holder.
+#[derive(Debug, Serialize, Deserialize)]
+struct LockInfo {
+    pid: u32,
+    acquired_at: DateTime<Utc>,
+}
+
+/// Exclusive lock on a repository, held by commands that change refs, the
+/// index or the object store.
+///
+/// The lock is `.flowversion/lock`, created with `create_new` so that only
+/// one process can hold it. It records the holder's pid and is removed
+/// when the lock is dropped. A lock whose holder is no longer running is
+/// reported as stale rather than silently taken over.
+#[derive(Debug)]
+pub struct RepoLock {
+    path: PathBuf,
+}
+
+impl RepoLock {
+    pub fn acquire(flow_dir: &Path) -> Result<Self> {
+        let path = flow_dir.join(LOCK_FILE);
+        
+        let mut file = match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Err(Self::held(&path)),
+            Err(e) => return Err(e.into()),
+        };
+        
+        // From here on the lock is ours, and dropping it cleans up
+        let lock = Self { path };
+        let info = LockInfo {
+            pid: std::process::id(),
+            acquired_at: Utc::now(),
+        };
+        file.write_all(&serde_json::to_vec(&info)?)?;
+        file.sync_all()?;
+        
+        Ok(lock)
+    }
+    
+    /// Explains why an existing lock file cannot be taken.
+    fn held(path: &Path) -> FlowError {
+        let info = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<LockInfo>(&content).ok());
+        
+        match info {
+            Some(info) => {
+                let since = info.acquired_at.format("%Y-%m-%d %H:%M:%S UTC");
+                
+                if process_alive(info.pid) == Some(false) {
+                    FlowError::StaleLock(format!(
+                        "{} was left by process {} (locked since {}), which is no longer running; \
+                         remove it if no other flow command is using this repository",
+                        path.display(), info.pid, since
+                    ))
+                } else {
+                    FlowError::RepositoryLocked(format!("process {} has held {} since {}", info.pid, path.display(), since))
+                }
+            }
+            None => {
+                let age = std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .unwrap_or_default();
+                
+                if age > UNREADABLE_LOCK_GRACE {
+                    FlowError::StaleLock(format!(
+                        "{} is unreadable, probably left by a crashed flow process; \
+                         remove it if no other flow command is using this repository",
+                        path.display()
+                    ))
+                } else {
+                    FlowError::RepositoryLocked(format!("{} is being taken by another process", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RepoLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether a process is running, or `None` where that cannot be told.
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> Option<bool> {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_alive(pid: u32) -> Option<bool> {
+    if pid == std::process::id() {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        let lock = RepoLock::acquire(temp_dir.path()).unwrap();
+        assert!(temp_dir.path().join(LOCK_FILE).exists());
+        assert!(matches!(RepoLock::acquire(temp_dir.path()), Err(FlowError::RepositoryLocked(_))));
+        
+        drop(lock);
+        assert!(!temp_dir.path().join(LOCK_FILE).exists());
+        RepoLock::acquire(temp_dir.path()).unwrap();
+    }
+    
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lock_of_dead_process_is_stale() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(LOCK_FILE);
+        
+        // Above the kernel's pid limit, so never a running process
+        let info = LockInfo { pid: 0x7fff_fff0, acquired_at: Utc::now() };
+        std::fs::write(&path, serde_json::to_vec(&info).unwrap()).unwrap();
+        
+        match RepoLock::acquire(temp_dir.path()) {
+            Err(FlowError::StaleLock(message)) => assert!(message.contains("no longer running")),
+            other => panic!("expected a stale lock error, got {:?}", other),
+        }
+        
+        // The stale lock is reported, never taken over
+        assert!(path.exists());
+    }
+}
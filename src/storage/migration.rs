@@ -0,0 +1,185 @@
+use crate::error::Result;
+use crate::core::{commit::FlowCommit, objects::CommitId};
+use crate::storage::object_store::ObjectStore;
+use crate::utils::fs::write_atomic;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+use walkdir::WalkDir;
+
+/// Rewrites commits stored under the old `objects/commits/<uuid>.json`
+/// layout into content-addressed commits and repoints every ref at them.
+///
+/// Returns the number of migrated commits; repositories without legacy
+/// commits are left untouched. The caller must hold the repository lock.
+pub async fn migrate_legacy_commits(flow_dir: &Path, object_store: &ObjectStore) -> Result<usize> {
+    let legacy_files = find_legacy_commit_files(&flow_dir.join("objects").join("commits"))?;
+    
+    if legacy_files.is_empty() {
+        return Ok(0);
+    }
+    
+    info!("Migrating {} UUID-keyed commits to content-addressed ids", legacy_files.len());
+    
+    let mut pending: HashMap<String, FlowCommit> = HashMap::new();
+    for path in &legacy_files {
+        let commit = FlowCommit::from_bytes(&std::fs::read(path)?)?;
+        pending.insert(commit.id.to_string(), commit);
+    }
+    
+    // Parents must be rewritten before their children, so repeatedly migrate
+    // every commit whose legacy parents already have a new id.
+    let mut id_map: HashMap<String, CommitId> = HashMap::new();
+    while !pending.is_empty() {
+        let ready: Vec<String> = pending.iter()
+            .filter(|(_, commit)| commit.parent_commits.iter().all(|parent| {
+                let parent = parent.to_string();
+                id_map.contains_key(&parent) || !pending.contains_key(&parent)
+            }))
+            .map(|(id, _)| id.clone())
+            .collect();
+        
+        if ready.is_empty() {
+            return Err(crate::error::FlowError::InvalidRepoState(
+                "Cycle detected in legacy commit history".to_string()
+            ));
+        }
+        
+        for old_id in ready {
+            let mut commit = pending.remove(&old_id).unwrap();
+            commit.parent_commits = commit.parent_commits.iter()
+                .map(|parent| id_map.get(&parent.to_string()).cloned().unwrap_or_else(|| parent.clone()))
+                .collect();
+            commit.id = CommitId::from_hash(commit.calculate_commit_hash());
+            
+            if !object_store.commit_exists(&commit.id).await {
+                object_store.store_commit(&commit).await?;
+            }
+            
+            debug!("Migrated commit {} -> {}", old_id, commit.id);
+            id_map.insert(old_id, commit.id);
+        }
+    }
+    
+    rewrite_refs(flow_dir, &id_map)?;
+    
+    for path in &legacy_files {
+        std::fs::remove_file(path)?;
+    }
+    
+    Ok(id_map.len())
+}
+
+/// Whether the repository still has commits in the old layout.
+pub fn has_legacy_commits(flow_dir: &Path) -> Result<bool> {
+    Ok(!find_legacy_commit_files(&flow_dir.join("objects").join("commits"))?.is_empty())
+}
+
+fn find_legacy_commit_files(commits_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    
+    if !commits_dir.exists() {
+        return Ok(files);
+    }
+    
+    for entry in std::fs::read_dir(commits_dir)? {
+        let path = entry?.path();
+        
+        if !path.is_file() {
+            continue;
+        }
+        
+        let is_legacy = path.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".json"))
+            .map(|stem| uuid::Uuid::parse_str(stem).is_ok())
+            .unwrap_or(false);
+        
+        if is_legacy {
+            files.push(path);
+        }
+    }
+    
+    Ok(files)
+}
+
+fn rewrite_refs(flow_dir: &Path, id_map: &HashMap<String, CommitId>) -> Result<()> {
+    let mut ref_files: Vec<PathBuf> = WalkDir::new(flow_dir.join("refs"))
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect();
+    
+    // A detached HEAD stores a commit id directly
+    ref_files.push(flow_dir.join("HEAD"));
+    
+    for ref_file in ref_files {
+        if !ref_file.exists() {
+            continue;
+        }
+        
+        let content = std::fs::read_to_string(&ref_file)?;
+        if let Some(new_id) = id_map.get(content.trim()) {
+            debug!("Rewriting ref {:?} -> {}", ref_file, new_id);
+            write_atomic(&ref_file, new_id.to_string())?;
+        }
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{intention::Intention, objects::Hash};
+    use tempfile::TempDir;
+    
+    fn write_legacy_commit(commits_dir: &Path, goal: &str, parent: Option<&str>) -> String {
+        let intention = Intention::new(goal.to_string(), None, None, 0.8);
+        let commit = FlowCommit::new(intention, vec![], Hash::new(goal.as_bytes()), vec![]);
+        
+        let legacy_id = uuid::Uuid::new_v4().to_string();
+        let mut value = serde_json::to_value(&commit).unwrap();
+        value["id"] = serde_json::json!(legacy_id);
+        value["parent_commits"] = serde_json::json!(parent.into_iter().collect::<Vec<_>>());
+        
+        std::fs::write(
+            commits_dir.join(format!("{}.json", legacy_id)),
+            serde_json::to_vec(&value).unwrap(),
+        ).unwrap();
+        
+        legacy_id
+    }
+    
+    #[tokio::test]
+    async fn test_migrate_legacy_commits() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow_dir = temp_dir.path();
+        let commits_dir = flow_dir.join("objects").join("commits");
+        std::fs::create_dir_all(&commits_dir).unwrap();
+        std::fs::create_dir_all(flow_dir.join("refs").join("streams")).unwrap();
+        
+        let root = write_legacy_commit(&commits_dir, "Root", None);
+        let child = write_legacy_commit(&commits_dir, "Child", Some(&root));
+        std::fs::write(flow_dir.join("refs").join("streams").join("main"), &child).unwrap();
+        std::fs::write(flow_dir.join("HEAD"), "ref: refs/streams/main\n").unwrap();
+        
+        let store = ObjectStore::new(&flow_dir.join("objects"));
+        assert_eq!(migrate_legacy_commits(flow_dir, &store).await.unwrap(), 2);
+        
+        // The ref now points at a content-addressed commit whose parent was migrated too
+        let head = std::fs::read_to_string(flow_dir.join("refs").join("streams").join("main")).unwrap();
+        let head_commit = store.load_commit(&CommitId::parse(&head).unwrap()).await.unwrap();
+        assert!(head_commit.verify_id());
+        assert_eq!(head_commit.intention.goal, "Child");
+        
+        let parent = store.load_commit(&head_commit.parent_commits[0]).await.unwrap();
+        assert!(parent.verify_id());
+        assert_eq!(parent.intention.goal, "Root");
+        
+        // Legacy files are gone and a second run is a no-op
+        assert!(find_legacy_commit_files(&commits_dir).unwrap().is_empty());
+        assert_eq!(migrate_legacy_commits(flow_dir, &store).await.unwrap(), 0);
+    }
+}
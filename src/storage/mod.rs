@@ -1,3 +1,11 @@
 pub mod object_store;
 pub mod index;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod migration;
+pub mod stream_store;
+pub mod keys;
+pub mod encoding;
+pub mod pack;
+pub mod reflog;
+pub mod lock;
+pub mod oplog;
\ No newline at end of file
@@ -1,176 +1,490 @@
 use crate::error::{FlowError, Result};
-use crate::core::{objects::Hash, commit::FlowCommit, objects::{CommitId, BlobObject}};
+use crate::core::{objects::Hash, commit::FlowCommit, objects::{CommitId, BlobObject, TreeObject}};
+use crate::storage::encoding::{self, ObjectKind, HEADER_LEN};
+use crate::storage::pack::{self, Pack, PackWriter};
+use crate::utils::fs::write_atomic;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 use tokio::fs;
+use tokio::io::AsyncReadExt;
 
+/// Extension of objects written before the binary encoding existed.
+const LEGACY_SUFFIX: &str = ".json";
+
+/// Content-addressed storage under `objects/{blobs,trees,commits}/xx/rest`.
+///
+/// Objects are written in the binary encoding from `storage::encoding`;
+/// JSON objects at `xx/rest.json` from older versions are still readable.
+/// `gc` moves objects into packs under `objects/pack/`; reads look at loose
+/// objects first and fall back to the packs.
 pub struct ObjectStore {
     objects_path: PathBuf,
+    /// Packs loaded on first use, refreshed when a lookup misses
+    packs: RwLock<Option<Arc<Vec<Pack>>>>,
 }
 
 impl ObjectStore {
     pub fn new(objects_path: &Path) -> Self {
         Self {
             objects_path: objects_path.to_path_buf(),
+            packs: RwLock::new(None),
         }
     }
     
+    pub fn pack_dir(&self) -> PathBuf {
+        self.objects_path.join("pack")
+    }
+    
     pub async fn store_blob(&self, hash: &Hash, content: Vec<u8>) -> Result<()> {
-        let blob_path = self.get_blob_path(hash);
-        
-        if blob_path.exists() {
+        if self.blob_exists(hash).await {
             // Blob already exists
             return Ok(());
         }
         
-        // Create directory if it doesn't exist
-        if let Some(parent) = blob_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-        
-        // Store blob
-        let blob = BlobObject::new(content);
-        let serialized = serde_json::to_vec(&blob)?;
-        
-        fs::write(&blob_path, serialized).await?;
-        
-        Ok(())
+        self.write_object(ObjectKind::Blob, hash.as_str(), &content).await
     }
     
     pub async fn load_blob(&self, hash: &Hash) -> Result<BlobObject> {
-        let blob_path = self.get_blob_path(hash);
+        let content = self.read_object(ObjectKind::Blob, hash.as_str()).await?;
         
-        if !blob_path.exists() {
-            return Err(FlowError::ObjectNotFound(hash.to_string()));
+        Ok(BlobObject { id: hash.clone(), content })
+    }
+    
+    pub async fn store_tree(&self, tree: &TreeObject) -> Result<()> {
+        if self.tree_exists(&tree.id).await {
+            // Trees are content-addressed, so an existing one is identical
+            return Ok(());
         }
         
-        let content = fs::read(&blob_path).await?;
-        let blob: BlobObject = serde_json::from_slice(&content)?;
+        let serialized = serde_json::to_vec(tree)?;
+        self.write_object(ObjectKind::Tree, tree.id.as_str(), &serialized).await
+    }
+    
+    pub async fn load_tree(&self, hash: &Hash) -> Result<TreeObject> {
+        let content = self.read_object(ObjectKind::Tree, hash.as_str()).await?;
+        let tree: TreeObject = serde_json::from_slice(&content)?;
         
-        Ok(blob)
+        Ok(tree)
+    }
+    
+    pub async fn tree_exists(&self, hash: &Hash) -> bool {
+        self.object_exists(ObjectKind::Tree, hash.as_str())
     }
     
     pub async fn store_commit(&self, commit: &FlowCommit) -> Result<()> {
-        let commit_path = self.get_commit_path(&commit.id);
-        
-        if commit_path.exists() {
+        if self.commit_exists(&commit.id).await {
             return Err(FlowError::ObjectAlreadyExists(commit.id.to_string()));
         }
         
-        // Create directory if it doesn't exist
-        if let Some(parent) = commit_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-        
-        // Store commit
         let serialized = commit.to_bytes()?;
-        fs::write(&commit_path, serialized).await?;
-        
-        Ok(())
+        self.write_object(ObjectKind::Commit, commit.id.as_str(), &serialized).await
     }
     
     pub async fn load_commit(&self, commit_id: &CommitId) -> Result<FlowCommit> {
-        let commit_path = self.get_commit_path(commit_id);
-        
-        if !commit_path.exists() {
-            return Err(FlowError::ObjectNotFound(commit_id.to_string()));
-        }
-        
-        let content = fs::read(&commit_path).await?;
+        let content = self.read_object(ObjectKind::Commit, commit_id.as_str()).await?;
         let commit = FlowCommit::from_bytes(&content)?;
         
         Ok(commit)
     }
     
     pub async fn commit_exists(&self, commit_id: &CommitId) -> bool {
-        let commit_path = self.get_commit_path(commit_id);
-        commit_path.exists()
+        self.object_exists(ObjectKind::Commit, commit_id.as_str())
     }
     
     pub async fn blob_exists(&self, hash: &Hash) -> bool {
-        let blob_path = self.get_blob_path(hash);
-        blob_path.exists()
+        self.object_exists(ObjectKind::Blob, hash.as_str())
     }
     
     pub async fn list_commits(&self) -> Result<Vec<CommitId>> {
-        let commits_dir = self.objects_path.join("commits");
+        // A commit may be both loose and packed, or in both loose encodings
+        let mut commits: BTreeSet<String> = self.list_loose(ObjectKind::Commit).await?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        
+        for pack in self.packs()?.iter() {
+            commits.extend(pack.records()
+                .filter(|record| record.kind == ObjectKind::Commit)
+                .map(|record| record.id.clone()));
+        }
+        
+        commits.into_iter().map(|id| CommitId::parse(&id)).collect()
+    }
+    
+    pub async fn get_stats(&self) -> Result<ObjectStoreStats> {
+        let mut stats = ObjectStoreStats::default();
         
-        if !commits_dir.exists() {
-            return Ok(Vec::new());
+        for kind in [ObjectKind::Blob, ObjectKind::Tree, ObjectKind::Commit] {
+            let (count, logical, physical) = Self::count_fanned_out(
+                &self.objects_path.join(Self::kind_dir(kind)),
+                kind,
+            ).await?;
+            
+            match kind {
+                ObjectKind::Blob => stats.blob_count = count,
+                ObjectKind::Tree => stats.tree_count = count,
+                ObjectKind::Commit => stats.commit_count = count,
+            }
+            stats.logical_size += logical;
+            stats.physical_size += physical;
         }
         
-        let mut commits = Vec::new();
-        let mut entries = fs::read_dir(&commits_dir).await?;
+        for pack in self.packs()?.iter() {
+            stats.pack_count += 1;
+            stats.packed_count += pack.len() as u64;
+            stats.physical_size += std::fs::metadata(pack.pack_path())?.len()
+                + std::fs::metadata(pack.index_path())?.len();
+            
+            for record in pack.records() {
+                match record.kind {
+                    ObjectKind::Blob => stats.blob_count += 1,
+                    ObjectKind::Tree => stats.tree_count += 1,
+                    ObjectKind::Commit => stats.commit_count += 1,
+                }
+                stats.logical_size += record.logical_size;
+            }
+        }
+        
+        Ok(stats)
+    }
+    
+    /// Packs the `reachable` objects into a single new pack, replacing the
+    /// loose copies and any older packs.
+    ///
+    /// Unreachable objects are pruned once they are older than `grace`, so
+    /// objects just written by a command still in progress survive. Young
+    /// unreachable objects from old packs are kept as loose objects.
+    /// `reachable` maps each object to a name hint (the file name, for
+    /// blobs) used to put versions of the same file next to each other,
+    /// where they delta well.
+    pub async fn gc(&self, reachable: &HashMap<(ObjectKind, String), String>, grace: Duration) -> Result<GcReport> {
+        let mut report = GcReport {
+            size_before: self.get_stats().await?.physical_size,
+            ..Default::default()
+        };
+        let old_packs = self.reload_packs()?;
+        
+        // Blobs by name and largest first, then trees, then commits
+        let mut order = Vec::with_capacity(reachable.len());
+        for ((kind, id), hint) in reachable {
+            let size = self.object_size(*kind, id).await?;
+            order.push((*kind, hint.as_str(), Reverse(size), id.as_str()));
+        }
+        order.sort();
         
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
+        let mut writer = PackWriter::create(&self.pack_dir())?;
+        for (kind, _, _, id) in &order {
+            let payload = self.read_object(*kind, id).await?;
+            writer.add(*kind, id, &payload)?;
+        }
+        report.packed = writer.len();
+        report.deltas = writer.deltas();
+        let new_pack = writer.finish()?.map(|pack| pack.pack_path().to_path_buf());
+        
+        let now = SystemTime::now();
+        let is_expired = |path: &Path| -> Result<bool> {
+            let modified = std::fs::metadata(path)?.modified()?;
+            Ok(now.duration_since(modified).unwrap_or_default() >= grace)
+        };
+        
+        let mut seen = HashSet::new();
+        for old in old_packs.iter().filter(|old| Some(old.pack_path()) != new_pack.as_deref()) {
+            let expired = is_expired(old.pack_path())?;
+            
+            for record in old.records() {
+                let key = (record.kind, record.id.clone());
+                if reachable.contains_key(&key) || self.loose_exists(record.kind, &record.id) || !seen.insert(key) {
+                    continue;
+                }
+                
+                if expired {
+                    report.pruned += 1;
+                } else {
+                    let payload = old.read(record.kind, &record.id)?
+                        .ok_or_else(|| FlowError::ObjectNotFound(record.id.clone()))?;
+                    self.write_object(record.kind, &record.id, &payload).await?;
+                }
+            }
             
-            if path.is_file() {
-                if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                    if file_name.ends_with(".json") {
-                        let commit_id_str = file_name.trim_end_matches(".json");
-                        if let Ok(uuid) = uuid::Uuid::parse_str(commit_id_str) {
-                            commits.push(CommitId::from_uuid(uuid));
-                        }
-                    }
+            std::fs::remove_file(old.index_path())?;
+            std::fs::remove_file(old.pack_path())?;
+        }
+        
+        for kind in [ObjectKind::Blob, ObjectKind::Tree, ObjectKind::Commit] {
+            for (id, path) in self.list_loose(kind).await? {
+                if reachable.contains_key(&(kind, id)) {
+                    std::fs::remove_file(&path)?;
+                } else if is_expired(&path)? {
+                    std::fs::remove_file(&path)?;
+                    report.pruned += 1;
+                } else {
+                    report.kept += 1;
+                    continue;
+                }
+                
+                // Drop the fan-out directory once empty
+                if let Some(parent) = path.parent() {
+                    let _ = std::fs::remove_dir(parent);
                 }
             }
         }
         
-        Ok(commits)
+        self.reload_packs()?;
+        report.size_after = self.get_stats().await?.physical_size;
+        Ok(report)
     }
     
-    pub async fn get_stats(&self) -> Result<ObjectStoreStats> {
-        let mut blob_count = 0;
-        let mut commit_count = 0;
-        let mut total_size = 0;
-        
-        // Count blobs
-        let blobs_dir = self.objects_path.join("blobs");
-        if blobs_dir.exists() {
-            let mut entries = fs::read_dir(&blobs_dir).await?;
+    /// Every stored object, loose or packed, each listed once.
+    pub async fn list_objects(&self) -> Result<BTreeSet<(ObjectKind, String)>> {
+        let mut objects = BTreeSet::new();
+        
+        for kind in [ObjectKind::Blob, ObjectKind::Tree, ObjectKind::Commit] {
+            objects.extend(self.list_loose(kind).await?.into_iter().map(|(id, _)| (kind, id)));
+        }
+        for pack in self.reload_packs()?.iter() {
+            objects.extend(pack.records().map(|record| (record.kind, record.id.clone())));
+        }
+        
+        Ok(objects)
+    }
+    
+    /// Packs whose content no longer matches the checksum in their name.
+    pub fn corrupt_packs(&self) -> Result<Vec<PathBuf>> {
+        let mut corrupt = Vec::new();
+        
+        for pack in self.packs()?.iter() {
+            if !pack.verify_checksum()? {
+                corrupt.push(pack.pack_path().to_path_buf());
+            }
+        }
+        
+        Ok(corrupt)
+    }
+    
+    /// Loose objects of `kind` as (id, path), in either encoding.
+    async fn list_loose(&self, kind: ObjectKind) -> Result<Vec<(String, PathBuf)>> {
+        let dir = self.objects_path.join(Self::kind_dir(kind));
+        let mut objects = Vec::new();
+        
+        if !dir.exists() {
+            return Ok(objects);
+        }
+        
+        let mut prefixes = fs::read_dir(&dir).await?;
+        while let Some(prefix) = prefixes.next_entry().await? {
+            // Legacy UUID-keyed commits live directly in commits/ and are skipped
+            if !prefix.path().is_dir() {
+                continue;
+            }
+            
+            let prefix_name = prefix.file_name().to_string_lossy().to_string();
+            let mut entries = fs::read_dir(prefix.path()).await?;
+            
             while let Some(entry) = entries.next_entry().await? {
-                if entry.path().is_file() {
-                    blob_count += 1;
-                    total_size += entry.metadata().await?.len();
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let id = format!("{}{}", prefix_name, file_name.strip_suffix(LEGACY_SUFFIX).unwrap_or(&file_name));
+                
+                if id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    objects.push((id, entry.path()));
                 }
             }
         }
         
-        // Count commits
-        let commits_dir = self.objects_path.join("commits");
-        if commits_dir.exists() {
-            let mut entries = fs::read_dir(&commits_dir).await?;
+        Ok(objects)
+    }
+    
+    async fn object_size(&self, kind: ObjectKind, id: &str) -> Result<u64> {
+        for path in [self.object_path(kind, id), self.legacy_object_path(kind, id)] {
+            if path.exists() {
+                return Self::logical_size(&path, kind).await;
+            }
+        }
+        
+        self.packs()?.iter()
+            .find_map(|pack| pack.find(kind, id).map(|record| record.logical_size))
+            .ok_or_else(|| FlowError::ObjectNotFound(id.to_string()))
+    }
+    
+    /// Counts objects in a fanned-out directory, returning the count, the
+    /// decoded (logical) size and the on-disk (physical) size.
+    async fn count_fanned_out(dir: &Path, kind: ObjectKind) -> Result<(u64, u64, u64)> {
+        let (mut count, mut logical, mut physical) = (0, 0, 0);
+        
+        if !dir.exists() {
+            return Ok((count, logical, physical));
+        }
+        
+        let mut prefixes = fs::read_dir(dir).await?;
+        while let Some(prefix) = prefixes.next_entry().await? {
+            if !prefix.path().is_dir() {
+                continue;
+            }
+            
+            let mut entries = fs::read_dir(prefix.path()).await?;
             while let Some(entry) = entries.next_entry().await? {
-                if entry.path().is_file() {
-                    commit_count += 1;
-                    total_size += entry.metadata().await?.len();
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+                
+                count += 1;
+                physical += entry.metadata().await?.len();
+                logical += Self::logical_size(&path, kind).await?;
+            }
+        }
+        
+        Ok((count, logical, physical))
+    }
+    
+    async fn logical_size(path: &Path, kind: ObjectKind) -> Result<u64> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        fs::File::open(path).await?
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)
+            .await?;
+        
+        if encoding::is_encoded(&header) {
+            return Ok(encoding::read_header(&header)?.logical_size);
+        }
+        
+        // Legacy JSON: blobs wrap their content, trees and commits are the JSON itself
+        let content = fs::read(path).await?;
+        match kind {
+            ObjectKind::Blob => Ok(serde_json::from_slice::<BlobObject>(&content)?.content.len() as u64),
+            _ => Ok(content.len() as u64),
+        }
+    }
+    
+    async fn write_object(&self, kind: ObjectKind, id: &str, payload: &[u8]) -> Result<()> {
+        let path = self.object_path(kind, id);
+        
+        // Create directory if it doesn't exist
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        
+        write_atomic(&path, encoding::encode(kind, payload)?)?;
+        Ok(())
+    }
+    
+    /// Reads an object's payload, falling back to the legacy JSON layout.
+    async fn read_object(&self, kind: ObjectKind, id: &str) -> Result<Vec<u8>> {
+        let path = self.object_path(kind, id);
+        
+        if path.exists() {
+            let bytes = fs::read(&path).await?;
+            return encoding::decode(&bytes, kind)
+                .map_err(|e| FlowError::CorruptObject(format!("{}: {}", id, e)));
+        }
+        
+        let legacy_path = self.legacy_object_path(kind, id);
+        if legacy_path.exists() {
+            let bytes = fs::read(&legacy_path).await?;
+            return match kind {
+                ObjectKind::Blob => Ok(serde_json::from_slice::<BlobObject>(&bytes)?.content),
+                _ => Ok(bytes),
+            };
+        }
+        
+        self.read_packed(kind, id)?
+            .ok_or_else(|| FlowError::ObjectNotFound(id.to_string()))
+    }
+    
+    fn read_packed(&self, kind: ObjectKind, id: &str) -> Result<Option<Vec<u8>>> {
+        // On a miss, look again in case another process repacked meanwhile
+        for reload in [false, true] {
+            let packs = if reload { self.reload_packs()? } else { self.packs()? };
+            
+            for pack in packs.iter() {
+                match pack.read(kind, id) {
+                    Ok(Some(payload)) => return Ok(Some(payload)),
+                    Ok(None) => {}
+                    Err(FlowError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound && !reload => break,
+                    Err(e) => return Err(FlowError::CorruptObject(format!("{}: {}", id, e))),
                 }
             }
         }
         
-        Ok(ObjectStoreStats {
-            blob_count,
-            commit_count,
-            total_size,
-        })
+        Ok(None)
     }
     
-    fn get_blob_path(&self, hash: &Hash) -> PathBuf {
-        let hash_str = hash.as_str();
-        let (prefix, suffix) = hash_str.split_at(2);
-        self.objects_path.join("blobs").join(prefix).join(format!("{}.json", suffix))
+    fn packs(&self) -> Result<Arc<Vec<Pack>>> {
+        let cached = self.packs.read()
+            .map_err(|_| FlowError::InternalError("Failed to acquire pack cache lock".to_string()))?
+            .clone();
+        
+        match cached {
+            Some(packs) => Ok(packs),
+            None => self.reload_packs(),
+        }
     }
     
-    fn get_commit_path(&self, commit_id: &CommitId) -> PathBuf {
-        self.objects_path.join("commits").join(format!("{}.json", commit_id))
+    fn reload_packs(&self) -> Result<Arc<Vec<Pack>>> {
+        let packs = Arc::new(pack::list_packs(&self.pack_dir())?);
+        
+        *self.packs.write()
+            .map_err(|_| FlowError::InternalError("Failed to acquire pack cache lock".to_string()))? = Some(packs.clone());
+        Ok(packs)
+    }
+    
+    fn object_exists(&self, kind: ObjectKind, id: &str) -> bool {
+        self.loose_exists(kind, id)
+            || self.packs().map(|packs| packs.iter().any(|pack| pack.contains(kind, id))).unwrap_or(false)
+    }
+    
+    fn loose_exists(&self, kind: ObjectKind, id: &str) -> bool {
+        self.object_path(kind, id).exists() || self.legacy_object_path(kind, id).exists()
+    }
+    
+    fn object_path(&self, kind: ObjectKind, id: &str) -> PathBuf {
+        let (prefix, suffix) = id.split_at(2);
+        self.objects_path.join(Self::kind_dir(kind)).join(prefix).join(suffix)
+    }
+    
+    fn legacy_object_path(&self, kind: ObjectKind, id: &str) -> PathBuf {
+        let (prefix, suffix) = id.split_at(2);
+        self.objects_path.join(Self::kind_dir(kind)).join(prefix).join(format!("{}{}", suffix, LEGACY_SUFFIX))
+    }
+    
+    fn kind_dir(kind: ObjectKind) -> &'static str {
+        match kind {
+            ObjectKind::Blob => "blobs",
+            ObjectKind::Tree => "trees",
+            ObjectKind::Commit => "commits",
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ObjectStoreStats {
     pub blob_count: u64,
+    pub tree_count: u64,
     pub commit_count: u64,
-    pub total_size: u64,
+    /// Total size of the decoded object contents
+    pub logical_size: u64,
+    /// Bytes the objects take on disk
+    pub physical_size: u64,
+    pub pack_count: u64,
+    /// Objects stored in packs rather than as loose files
+    pub packed_count: u64,
+}
+
+/// What `ObjectStore::gc` did.
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Reachable objects written to the new pack
+    pub packed: usize,
+    /// How many of those were stored as deltas
+    pub deltas: usize,
+    /// Unreachable objects deleted
+    pub pruned: usize,
+    /// Unreachable objects kept because they are within the grace period
+    pub kept: usize,
+    pub size_before: u64,
+    pub size_after: u64,
 }
 
 #[cfg(test)]
@@ -224,6 +538,29 @@ mod tests {
         let loaded_commit = store.load_commit(&commit.id).await.unwrap();
         assert_eq!(loaded_commit.id.to_string(), commit.id.to_string());
         assert_eq!(loaded_commit.intention.goal, commit.intention.goal);
+        
+        // Commits are keyed by their content hash
+        assert_eq!(store.list_commits().await.unwrap(), vec![commit.id.clone()]);
+    }
+    
+    #[tokio::test]
+    async fn test_tree_storage() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(&temp_dir.path().join("objects"));
+        
+        let mut tree = TreeObject::new();
+        tree.add_blob("file.txt".to_string(), Hash::new(b"content"), 0o644);
+        tree.calculate_hash();
+        
+        store.store_tree(&tree).await.unwrap();
+        assert!(store.tree_exists(&tree.id).await);
+        
+        // Storing the same tree twice is a no-op
+        store.store_tree(&tree).await.unwrap();
+        
+        let loaded_tree = store.load_tree(&tree.id).await.unwrap();
+        assert_eq!(loaded_tree.id, tree.id);
+        assert!(loaded_tree.entries.contains_key("file.txt"));
     }
     
     #[tokio::test]
@@ -247,6 +584,114 @@ mod tests {
         let stats = store.get_stats().await.unwrap();
         assert_eq!(stats.blob_count, 1);
         assert_eq!(stats.commit_count, 1);
-        assert!(stats.total_size > 0);
+        assert!(stats.physical_size > 0);
+        
+        // Highly repetitive content is stored compressed
+        let large = "the same line again\n".repeat(5000).into_bytes();
+        let large_hash = Hash::new(&large);
+        store.store_blob(&large_hash, large.clone()).await.unwrap();
+        
+        let stats = store.get_stats().await.unwrap();
+        assert!(stats.logical_size > large.len() as u64);
+        assert!(stats.physical_size < stats.logical_size / 10);
+    }
+    
+    #[tokio::test]
+    async fn test_loads_legacy_json_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let objects = temp_dir.path().join("objects");
+        let store = ObjectStore::new(&objects);
+        
+        // Objects as written by earlier versions: JSON at xx/rest.json
+        let blob = BlobObject::new(b"legacy content".to_vec());
+        let blob_path = store.legacy_object_path(ObjectKind::Blob, blob.id.as_str());
+        std::fs::create_dir_all(blob_path.parent().unwrap()).unwrap();
+        std::fs::write(&blob_path, serde_json::to_vec(&blob).unwrap()).unwrap();
+        
+        let commit = FlowCommit::new(
+            Intention::new("Legacy".to_string(), None, None, 0.8),
+            vec![],
+            Hash::new(b"tree"),
+            vec![],
+        );
+        let commit_path = store.legacy_object_path(ObjectKind::Commit, commit.id.as_str());
+        std::fs::create_dir_all(commit_path.parent().unwrap()).unwrap();
+        std::fs::write(&commit_path, commit.to_bytes().unwrap()).unwrap();
+        
+        assert!(store.blob_exists(&blob.id).await);
+        assert_eq!(store.load_blob(&blob.id).await.unwrap().content, b"legacy content");
+        assert_eq!(store.load_commit(&commit.id).await.unwrap().intention.goal, "Legacy");
+        assert_eq!(store.list_commits().await.unwrap(), vec![commit.id.clone()]);
+        assert!(matches!(store.store_commit(&commit).await, Err(FlowError::ObjectAlreadyExists(_))));
+        
+        let stats = store.get_stats().await.unwrap();
+        assert_eq!(stats.blob_count, 1);
+        assert_eq!(stats.logical_size, b"legacy content".len() as u64 + commit.to_bytes().unwrap().len() as u64);
+    }
+    
+    #[tokio::test]
+    async fn test_corrupt_object_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(&temp_dir.path().join("objects"));
+        
+        let hash = Hash::new(b"content");
+        store.store_blob(&hash, b"content".to_vec()).await.unwrap();
+        std::fs::write(store.object_path(ObjectKind::Blob, hash.as_str()), b"garbage").unwrap();
+        
+        assert!(matches!(store.load_blob(&hash).await, Err(FlowError::CorruptObject(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_gc_packs_reachable_and_prunes_unreachable() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ObjectStore::new(&temp_dir.path().join("objects"));
+        
+        // Three versions of one file, a commit, and a blob nothing refers to
+        let mut reachable = HashMap::new();
+        for version in 0..3 {
+            let content = format!("{}\nversion {}\n", "shared line\n".repeat(200), version).into_bytes();
+            let hash = Hash::new(&content);
+            store.store_blob(&hash, content).await.unwrap();
+            reachable.insert((ObjectKind::Blob, hash.to_string()), "file.txt".to_string());
+        }
+        let commit = FlowCommit::new(
+            Intention::new("Packed".to_string(), None, None, 0.8),
+            vec![],
+            Hash::new(b"tree"),
+            vec![],
+        );
+        store.store_commit(&commit).await.unwrap();
+        reachable.insert((ObjectKind::Commit, commit.id.to_string()), String::new());
+        
+        let orphan = Hash::new(b"orphan");
+        store.store_blob(&orphan, b"orphan".to_vec()).await.unwrap();
+        
+        // Within the grace period the orphan survives, still loose
+        let report = store.gc(&reachable, Duration::from_secs(3600)).await.unwrap();
+        assert_eq!(report.packed, 4);
+        assert_eq!(report.deltas, 2);
+        assert_eq!((report.pruned, report.kept), (0, 1));
+        assert!(store.loose_exists(ObjectKind::Blob, orphan.as_str()));
+        
+        // Everything reads back transparently from the pack
+        for (kind, id) in reachable.keys() {
+            assert!(!store.loose_exists(*kind, id));
+            assert!(store.object_exists(*kind, id));
+        }
+        assert_eq!(store.load_commit(&commit.id).await.unwrap().intention.goal, "Packed");
+        assert_eq!(store.list_commits().await.unwrap(), vec![commit.id.clone()]);
+        
+        let stats = store.get_stats().await.unwrap();
+        assert_eq!((stats.pack_count, stats.packed_count, stats.blob_count), (1, 4, 4));
+        
+        // Past the grace period it is pruned; repacking keeps a single pack
+        let report = store.gc(&reachable, Duration::ZERO).await.unwrap();
+        assert_eq!((report.packed, report.pruned, report.kept), (4, 1, 0));
+        assert!(!store.blob_exists(&orphan).await);
+        assert_eq!(pack::list_packs(&store.pack_dir()).unwrap().len(), 1);
+        
+        // A fresh store (another process) finds the packed objects too
+        let reopened = ObjectStore::new(&temp_dir.path().join("objects"));
+        assert!(reopened.commit_exists(&commit.id).await);
     }
 }
\ No newline at end of file
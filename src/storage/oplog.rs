@@ -0,0 +1,191 @@
+use crate::error::Result;
+use crate::core::objects::{CommitId, Hash};
+use crate::storage::index::IndexEntry;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Everything an operation can change apart from the working tree: HEAD,
+/// every ref and stream metadata file, merge state and the index.
+///
+/// States are stored as blobs (see `Repository::record_operation`); maps
+/// are ordered so identical states share a blob.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepoState {
+    /// Raw content of HEAD, e.g. `ref: refs/streams/main\n`
+    pub head: String,
+    /// Refs under `refs/`, by name relative to the repository directory
+    pub refs: BTreeMap<String, CommitId>,
+    /// Stream metadata files under `refs/`, by path, with their content
+    pub metadata: BTreeMap<String, String>,
+    pub merge_head: Option<CommitId>,
+    pub merge_conflicts: Vec<String>,
+    pub index: BTreeMap<String, IndexEntry>,
+}
+
+impl RepoState {
+    /// The commit HEAD resolves to in this state.
+    pub fn head_commit(&self) -> Option<CommitId> {
+        match self.head.trim().strip_prefix("ref: ") {
+            Some(reference) => self.refs.get(reference).cloned(),
+            None => CommitId::parse(&self.head).ok(),
+        }
+    }
+    
+    /// Every commit the state refers to.
+    pub fn commits(&self) -> impl Iterator<Item = &CommitId> {
+        self.refs.values().chain(self.merge_head.as_ref())
+    }
+}
+
+/// One recorded operation, e.g. a commit or a stream switch, with the
+/// blobs holding the repository state before and after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: u64,
+    pub operation: String,
+    pub timestamp: DateTime<Utc>,
+    pub before: Hash,
+    pub after: Hash,
+    /// Set on `flow undo` entries: the operation that was undone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<u64>,
+}
+
+/// Repository-wide operation log at `logs/operations`, one JSON entry per
+/// line, oldest first. Ids count up from 1.
+pub struct OpLog {
+    path: PathBuf,
+}
+
+impl OpLog {
+    pub const FILE: &'static str = "operations";
+    
+    pub fn new(logs_path: &Path) -> Self {
+        Self {
+            path: logs_path.join(Self::FILE),
+        }
+    }
+    
+    /// Appends an operation, assigning it the next id.
+    pub fn append(
+        &self,
+        operation: &str,
+        before: Hash,
+        after: Hash,
+        undoes: Option<u64>,
+    ) -> Result<Operation> {
+        let id = self.read()?.last().map_or(1, |last| last.id + 1);
+        let entry = Operation {
+            id,
+            operation: operation.to_string(),
+            timestamp: Utc::now(),
+            before,
+            after,
+            undoes,
+        };
+        
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
+        Ok(entry)
+    }
+    
+    /// Reads every operation, oldest first, skipping lines that do not parse.
+    pub fn read(&self) -> Result<Vec<Operation>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let content = std::fs::read_to_string(&self.path)?;
+        let mut operations = Vec::new();
+        
+        for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(operation) => operations.push(operation),
+                Err(e) => warn!("Skipping line {} of the operation log: {}", number + 1, e),
+            }
+        }
+        
+        Ok(operations)
+    }
+    
+    pub fn get(&self, id: u64) -> Result<Option<Operation>> {
+        Ok(self.read()?.into_iter().find(|operation| operation.id == id))
+    }
+    
+    /// The most recent operation that has not been undone, skipping the
+    /// undo entries themselves, so repeated undos walk further back.
+    pub fn last_undoable(&self) -> Result<Option<Operation>> {
+        let mut undone = std::collections::HashSet::new();
+        
+        for operation in self.read()?.into_iter().rev() {
+            if let Some(target) = operation.undoes {
+                undone.insert(target);
+            } else if !undone.contains(&operation.id) {
+                return Ok(Some(operation));
+            }
+        }
+        
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_ids_and_undo_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let oplog = OpLog::new(&temp_dir.path().join("logs"));
+        let state = |n: &[u8]| Hash::new(n);
+        
+        assert_eq!(oplog.last_undoable().unwrap(), None);
+        
+        let first = oplog.append("commit: one", state(b"0"), state(b"1"), None).unwrap();
+        let second = oplog.append("commit: two", state(b"1"), state(b"2"), None).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(oplog.last_undoable().unwrap(), Some(second.clone()));
+        
+        // Undoing twice walks back past the first undo
+        oplog.append("undo: commit: two", state(b"2"), state(b"1"), Some(2)).unwrap();
+        assert_eq!(oplog.last_undoable().unwrap(), Some(first.clone()));
+        oplog.append("undo: commit: one", state(b"1"), state(b"0"), Some(1)).unwrap();
+        assert_eq!(oplog.last_undoable().unwrap(), None);
+        
+        assert_eq!(oplog.get(2).unwrap(), Some(second));
+        assert_eq!(oplog.get(9).unwrap(), None);
+        assert_eq!(oplog.read().unwrap().len(), 4);
+    }
+    
+    #[test]
+    fn test_head_commit() {
+        let commit = CommitId::from_hash(Hash::new(b"head"));
+        let mut state = RepoState {
+            head: "ref: refs/streams/main\n".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(state.head_commit(), None);
+        
+        state.refs.insert("refs/streams/main".to_string(), commit.clone());
+        assert_eq!(state.head_commit(), Some(commit.clone()));
+        
+        state.head = format!("{}\n", commit);
+        state.refs.clear();
+        assert_eq!(state.head_commit(), Some(commit));
+    }
+}
@@ -0,0 +1,451 @@
+use crate::error::{FlowError, Result};
+use crate::storage::encoding::{self, ObjectKind};
+use crate::utils::{delta, fs::write_atomic};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const PACK_MAGIC: &[u8; 4] = b"FPAK";
+const INDEX_MAGIC: &[u8; 4] = b"FIDX";
+const PACK_VERSION: u8 = 1;
+/// magic (4) + version + 3 reserved bytes
+const PACK_HEADER_LEN: u64 = 8;
+/// magic (4) + version + 3 reserved bytes + record count (u32 LE)
+const INDEX_HEADER_LEN: usize = 12;
+/// id (32) + kind + offset (u64 LE) + logical size (u64 LE)
+const INDEX_RECORD_LEN: usize = 49;
+
+const ENTRY_FULL: u8 = 0;
+const ENTRY_DELTA: u8 = 1;
+
+/// Number of preceding blobs a new blob is tried as a delta against.
+const DELTA_WINDOW: usize = 10;
+/// Longest chain of deltas on deltas, bounding the cost of a read.
+const MAX_DELTA_DEPTH: usize = 10;
+/// Blobs smaller than this are stored whole.
+const MIN_DELTA_SIZE: usize = 64;
+
+/// One object's location in a pack, as recorded in the `.idx` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackRecord {
+    pub kind: ObjectKind,
+    pub id: String,
+    pub offset: u64,
+    /// Size of the reconstructed payload
+    pub logical_size: u64,
+}
+
+/// A read-only pack: `pack-<sha256>.pack` holding the objects back to back
+/// and `pack-<sha256>.idx` mapping ids to offsets, sorted for binary search.
+///
+/// Pack entries are `kind, type, [base offset], data length, data` where
+/// data is an encoded object, or for deltas an encoded delta against the
+/// entry at the base offset earlier in the same pack.
+pub struct Pack {
+    pack_path: PathBuf,
+    index_path: PathBuf,
+    /// Records sorted by raw id, then kind
+    records: Vec<([u8; 32], PackRecord)>,
+}
+
+impl Pack {
+    pub fn open(index_path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(index_path)?;
+        let corrupt = |message: &str| FlowError::CorruptObject(format!("{}: {}", index_path.display(), message));
+        
+        if bytes.len() < INDEX_HEADER_LEN || &bytes[..4] != INDEX_MAGIC {
+            return Err(corrupt("not a pack index"));
+        }
+        if bytes[4] != PACK_VERSION {
+            return Err(corrupt(&format!("unsupported pack version {}", bytes[4])));
+        }
+        
+        let count = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        if bytes.len() != INDEX_HEADER_LEN + count * INDEX_RECORD_LEN {
+            return Err(corrupt("truncated pack index"));
+        }
+        
+        let mut records = Vec::with_capacity(count);
+        for chunk in bytes[INDEX_HEADER_LEN..].chunks_exact(INDEX_RECORD_LEN) {
+            let raw: [u8; 32] = chunk[..32].try_into().unwrap();
+            let kind = ObjectKind::from_byte(chunk[32])
+                .ok_or_else(|| corrupt(&format!("unknown object kind {}", chunk[32])))?;
+            
+            records.push((raw, PackRecord {
+                kind,
+                id: hex::encode(raw),
+                offset: u64::from_le_bytes(chunk[33..41].try_into().unwrap()),
+                logical_size: u64::from_le_bytes(chunk[41..49].try_into().unwrap()),
+            }));
+        }
+        
+        Ok(Self {
+            pack_path: index_path.with_extension("pack"),
+            index_path: index_path.to_path_buf(),
+            records,
+        })
+    }
+    
+    pub fn pack_path(&self) -> &Path {
+        &self.pack_path
+    }
+    
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+    
+    pub fn records(&self) -> impl Iterator<Item = &PackRecord> {
+        self.records.iter().map(|(_, record)| record)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+    
+    pub fn find(&self, kind: ObjectKind, id: &str) -> Option<&PackRecord> {
+        let raw = raw_id(id).ok()?;
+        
+        self.records
+            .binary_search_by(|(other, record)| (other, record.kind).cmp(&(&raw, kind)))
+            .ok()
+            .map(|position| &self.records[position].1)
+    }
+    
+    pub fn contains(&self, kind: ObjectKind, id: &str) -> bool {
+        self.find(kind, id).is_some()
+    }
+    
+    /// Whether the pack still hashes to the checksum in its name.
+    pub fn verify_checksum(&self) -> Result<bool> {
+        let expected = self.pack_path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix("pack-"))
+            .unwrap_or_default()
+            .to_string();
+        
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(&self.pack_path)?, &mut hasher)?;
+        
+        Ok(hex::encode(hasher.finalize()) == expected)
+    }
+    
+    /// Reads an object's payload, resolving deltas against their bases.
+    pub fn read(&self, kind: ObjectKind, id: &str) -> Result<Option<Vec<u8>>> {
+        let record = match self.find(kind, id) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        
+        let mut file = File::open(&self.pack_path)?;
+        let payload = self.read_entry(&mut file, record.offset, kind, 0)?;
+        
+        if payload.len() as u64 != record.logical_size {
+            return Err(FlowError::CorruptObject(format!(
+                "{}: packed object is {} bytes, index says {}",
+                id, payload.len(), record.logical_size
+            )));
+        }
+        
+        Ok(Some(payload))
+    }
+    
+    fn read_entry(&self, file: &mut File, offset: u64, kind: ObjectKind, depth: usize) -> Result<Vec<u8>> {
+        let corrupt = |message: String| FlowError::CorruptObject(format!("{} at {}: {}", self.pack_path.display(), offset, message));
+        
+        if depth > MAX_DELTA_DEPTH {
+            return Err(corrupt("delta chain too long".to_string()));
+        }
+        
+        file.seek(SeekFrom::Start(offset))?;
+        let mut head = [0u8; 2];
+        file.read_exact(&mut head)?;
+        
+        if ObjectKind::from_byte(head[0]) != Some(kind) {
+            return Err(corrupt(format!("expected a {:?} entry", kind)));
+        }
+        
+        let base_offset = match head[1] {
+            ENTRY_FULL => None,
+            ENTRY_DELTA => {
+                let base_offset = read_u64(file)?;
+                // Bases always precede their deltas, which also rules out cycles
+                if base_offset >= offset {
+                    return Err(corrupt("delta base does not precede the delta".to_string()));
+                }
+                Some(base_offset)
+            }
+            other => return Err(corrupt(format!("unknown entry type {}", other))),
+        };
+        
+        let length = read_u64(file)?;
+        let mut data = Vec::new();
+        file.take(length).read_to_end(&mut data)?;
+        if data.len() as u64 != length {
+            return Err(corrupt("truncated entry".to_string()));
+        }
+        
+        let decoded = encoding::decode(&data, kind)?;
+        match base_offset {
+            None => Ok(decoded),
+            Some(base_offset) => {
+                let base = self.read_entry(file, base_offset, kind, depth + 1)?;
+                delta::apply(&base, &decoded)
+            }
+        }
+    }
+}
+
+/// Streams objects into a new pack, delta-compressing blobs against the
+/// ones added just before them. Callers get the best deltas by adding
+/// similar blobs (e.g. versions of the same file) next to each other.
+pub struct PackWriter {
+    pack_dir: PathBuf,
+    temp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    hasher: Sha256,
+    offset: u64,
+    records: Vec<([u8; 32], PackRecord)>,
+    /// Recent blobs as (offset, payload, delta depth)
+    window: VecDeque<(u64, Vec<u8>, usize)>,
+    deltas: usize,
+}
+
+impl PackWriter {
+    pub fn create(pack_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(pack_dir)?;
+        
+        let temp_path = pack_dir.join(format!("tmp-pack-{}", std::process::id()));
+        let mut writer = Self {
+            pack_dir: pack_dir.to_path_buf(),
+            writer: Some(BufWriter::new(File::create(&temp_path)?)),
+            temp_path,
+            hasher: Sha256::new(),
+            offset: 0,
+            records: Vec::new(),
+            window: VecDeque::new(),
+            deltas: 0,
+        };
+        
+        let mut header = PACK_MAGIC.to_vec();
+        header.extend_from_slice(&[PACK_VERSION, 0, 0, 0]);
+        writer.write(&header)?;
+        debug_assert_eq!(writer.offset, PACK_HEADER_LEN);
+        
+        Ok(writer)
+    }
+    
+    /// Number of objects stored as deltas so far.
+    pub fn deltas(&self) -> usize {
+        self.deltas
+    }
+    
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+    
+    pub fn add(&mut self, kind: ObjectKind, id: &str, payload: &[u8]) -> Result<()> {
+        let raw = raw_id(id)?;
+        let offset = self.offset;
+        
+        let best_delta = if kind == ObjectKind::Blob && payload.len() >= MIN_DELTA_SIZE {
+            self.window.iter()
+                .filter(|(_, _, depth)| *depth < MAX_DELTA_DEPTH)
+                .map(|(base_offset, base, depth)| (*base_offset, delta::compute(base, payload), depth + 1))
+                .filter(|(_, delta, _)| delta.len() < payload.len() / 2)
+                .min_by_key(|(_, delta, _)| delta.len())
+        } else {
+            None
+        };
+        
+        let depth = match &best_delta {
+            Some((base_offset, delta, depth)) => {
+                let mut head = vec![kind.to_byte(), ENTRY_DELTA];
+                head.extend_from_slice(&base_offset.to_le_bytes());
+                self.write_entry(head, &encoding::encode(kind, delta)?)?;
+                self.deltas += 1;
+                *depth
+            }
+            None => {
+                self.write_entry(vec![kind.to_byte(), ENTRY_FULL], &encoding::encode(kind, payload)?)?;
+                0
+            }
+        };
+        
+        if kind == ObjectKind::Blob {
+            if self.window.len() == DELTA_WINDOW {
+                self.window.pop_front();
+            }
+            self.window.push_back((offset, payload.to_vec(), depth));
+        }
+        
+        self.records.push((raw, PackRecord {
+            kind,
+            id: id.to_string(),
+            offset,
+            logical_size: payload.len() as u64,
+        }));
+        
+        Ok(())
+    }
+    
+    /// Writes the index and moves the pack into place, named after the
+    /// hash of its content. Returns `None` if nothing was added.
+    pub fn finish(mut self) -> Result<Option<Pack>> {
+        let mut writer = self.writer.take().expect("pack writer already finished");
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+        
+        if self.records.is_empty() {
+            std::fs::remove_file(&self.temp_path)?;
+            return Ok(None);
+        }
+        
+        self.records.sort_by(|(a, first), (b, second)| (a, first.kind).cmp(&(b, second.kind)));
+        self.records.dedup_by(|(a, first), (b, second)| a == b && first.kind == second.kind);
+        
+        let name = format!("pack-{}", hex::encode(std::mem::take(&mut self.hasher).finalize()));
+        let pack_path = self.pack_dir.join(format!("{}.pack", name));
+        let index_path = self.pack_dir.join(format!("{}.idx", name));
+        
+        let mut index = INDEX_MAGIC.to_vec();
+        index.extend_from_slice(&[PACK_VERSION, 0, 0, 0]);
+        index.extend_from_slice(&(self.records.len() as u32).to_le_bytes());
+        for (raw, record) in &self.records {
+            index.extend_from_slice(raw);
+            index.push(record.kind.to_byte());
+            index.extend_from_slice(&record.offset.to_le_bytes());
+            index.extend_from_slice(&record.logical_size.to_le_bytes());
+        }
+        
+        // The index goes in last: a pack without one is never read
+        std::fs::rename(&self.temp_path, &pack_path)?;
+        write_atomic(&index_path, &index)?;
+        
+        Ok(Some(Pack {
+            pack_path,
+            index_path,
+            records: std::mem::take(&mut self.records),
+        }))
+    }
+    
+    fn write_entry(&mut self, mut head: Vec<u8>, data: &[u8]) -> Result<()> {
+        head.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        self.write(&head)?;
+        self.write(data)
+    }
+    
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.as_mut().expect("pack writer already finished").write_all(bytes)?;
+        self.hasher.update(bytes);
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+impl Drop for PackWriter {
+    fn drop(&mut self) {
+        // An abandoned pack leaves nothing behind
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Lists the packs in `pack_dir`, skipping any without an index.
+pub fn list_packs(pack_dir: &Path) -> Result<Vec<Pack>> {
+    let mut packs = Vec::new();
+    
+    if !pack_dir.exists() {
+        return Ok(packs);
+    }
+    
+    let mut index_paths = Vec::new();
+    for entry in std::fs::read_dir(pack_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("idx") && path.with_extension("pack").exists() {
+            index_paths.push(path);
+        }
+    }
+    
+    index_paths.sort();
+    for index_path in index_paths {
+        packs.push(Pack::open(&index_path)?);
+    }
+    
+    Ok(packs)
+}
+
+fn raw_id(id: &str) -> Result<[u8; 32]> {
+    hex::decode(id)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| FlowError::InvalidObjectHash(id.to_string()))
+}
+
+fn read_u64(file: &mut File) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::objects::Hash;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_pack_roundtrip_with_deltas() {
+        let temp_dir = TempDir::new().unwrap();
+        let pack_dir = temp_dir.path().join("pack");
+        
+        // Successive versions of one file, plus a tree
+        let versions: Vec<Vec<u8>> = (0..5)
+            .map(|v| (0..300).map(|i| format!("line {} of version {}\n", i, if i == 150 { v } else { 0 })).collect::<String>().into_bytes())
+            .collect();
+        let tree = br#"{"entries":{}}"#.to_vec();
+        
+        let mut writer = PackWriter::create(&pack_dir).unwrap();
+        for version in &versions {
+            writer.add(ObjectKind::Blob, Hash::new(version).as_str(), version).unwrap();
+        }
+        writer.add(ObjectKind::Tree, Hash::new(&tree).as_str(), &tree).unwrap();
+        assert_eq!(writer.deltas(), 4);
+        
+        let pack = writer.finish().unwrap().unwrap();
+        let raw_size: usize = versions.iter().map(|v| v.len()).sum();
+        assert!(std::fs::metadata(pack.pack_path()).unwrap().len() < raw_size as u64 / 5);
+        
+        // Read back through a freshly opened index
+        let packs = list_packs(&pack_dir).unwrap();
+        assert_eq!(packs.len(), 1);
+        assert_eq!(packs[0].len(), 6);
+        for version in &versions {
+            let id = Hash::new(version);
+            assert_eq!(packs[0].read(ObjectKind::Blob, id.as_str()).unwrap().as_ref(), Some(version));
+            assert!(!packs[0].contains(ObjectKind::Tree, id.as_str()));
+        }
+        assert_eq!(packs[0].read(ObjectKind::Tree, Hash::new(&tree).as_str()).unwrap(), Some(tree));
+        assert_eq!(packs[0].read(ObjectKind::Blob, Hash::new(b"missing").as_str()).unwrap(), None);
+        
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&pack_dir).unwrap().count(), 2);
+    }
+    
+    #[test]
+    fn test_empty_and_abandoned_packs_leave_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let pack_dir = temp_dir.path().join("pack");
+        
+        assert!(PackWriter::create(&pack_dir).unwrap().finish().unwrap().is_none());
+        
+        let mut writer = PackWriter::create(&pack_dir).unwrap();
+        writer.add(ObjectKind::Blob, Hash::new(b"x").as_str(), b"x").unwrap();
+        drop(writer);
+        
+        assert_eq!(std::fs::read_dir(&pack_dir).unwrap().count(), 0);
+    }
+}
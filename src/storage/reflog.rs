@@ -0,0 +1,116 @@
+use crate::error::Result;
+use crate::core::objects::CommitId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// One update of a ref: where it pointed before and after, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReflogEntry {
+    pub old: Option<CommitId>,
+    /// `None` when the ref was deleted
+    pub new: Option<CommitId>,
+    pub operation: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ReflogEntry {
+    pub fn new(old: Option<CommitId>, new: Option<CommitId>, operation: &str) -> Self {
+        Self {
+            old,
+            new,
+            operation: operation.to_string(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Per-ref history under `logs/`: the log for `refs/streams/main` is
+/// `logs/refs/streams/main`, one JSON entry per line, oldest first.
+pub struct Reflog {
+    logs_path: PathBuf,
+}
+
+impl Reflog {
+    pub fn new(logs_path: &Path) -> Self {
+        Self {
+            logs_path: logs_path.to_path_buf(),
+        }
+    }
+    
+    pub fn append(&self, reference: &str, entry: &ReflogEntry) -> Result<()> {
+        let path = self.log_path(reference);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+    
+    /// Reads a ref's entries, oldest first. Lines that do not parse (e.g. a
+    /// write cut short by a crash) are skipped.
+    pub fn read(&self, reference: &str) -> Result<Vec<ReflogEntry>> {
+        let path = self.log_path(reference);
+        
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let content = std::fs::read_to_string(&path)?;
+        let mut entries = Vec::new();
+        
+        for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Skipping line {} of reflog {}: {}", number + 1, reference, e),
+            }
+        }
+        
+        Ok(entries)
+    }
+    
+    fn log_path(&self, reference: &str) -> PathBuf {
+        self.logs_path.join(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::objects::Hash;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_append_and_read() {
+        let temp_dir = TempDir::new().unwrap();
+        let reflog = Reflog::new(&temp_dir.path().join("logs"));
+        let first = CommitId::from_hash(Hash::new(b"first"));
+        let second = CommitId::from_hash(Hash::new(b"second"));
+        
+        assert!(reflog.read("refs/streams/main").unwrap().is_empty());
+        
+        reflog.append("refs/streams/main", &ReflogEntry::new(None, Some(first.clone()), "commit")).unwrap();
+        reflog.append("refs/streams/main", &ReflogEntry::new(Some(first.clone()), Some(second.clone()), "commit")).unwrap();
+        
+        // A torn final line is ignored
+        let path = temp_dir.path().join("logs/refs/streams/main");
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"old\": nul").unwrap();
+        
+        let entries = reflog.read("refs/streams/main").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].new, Some(first.clone()));
+        assert_eq!(entries[1].old, Some(first));
+        assert_eq!(entries[1].new, Some(second));
+    }
+}
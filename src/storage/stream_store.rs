@@ -0,0 +1,273 @@
+use crate::error::{FlowError, Result};
+use crate::core::{objects::CommitId, stream::{Stream, StreamMode}};
+use crate::utils::fs::write_atomic;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const METADATA_SUFFIX: &str = ".meta.json";
+const VARIANT_REFS: &str = "quantum";
+const ARCHIVED_VARIANT_REFS: &str = "archive/quantum";
+
+/// Streams under `refs/streams/`: the ref file `<name>` holds the head
+/// commit id and `<name>.meta.json` next to it holds the `Stream` metadata.
+///
+/// Quantum stream variants have their heads in `refs/quantum/<name>/<variant>`
+/// and move to `refs/archive/quantum/<name>/<variant>` once rejected.
+pub struct StreamStore {
+    streams_path: PathBuf,
+    refs_path: PathBuf,
+}
+
+impl StreamStore {
+    pub fn new(streams_path: &Path) -> Self {
+        Self {
+            streams_path: streams_path.to_path_buf(),
+            refs_path: streams_path.parent().unwrap_or(streams_path).to_path_buf(),
+        }
+    }
+    
+    pub fn exists(&self, name: &str) -> bool {
+        self.ref_path(name).exists() || self.metadata_path(name).exists()
+    }
+    
+    pub fn save(&self, stream: &Stream) -> Result<()> {
+        Stream::validate_name(&stream.name)?;
+        
+        let metadata_path = self.metadata_path(&stream.name);
+        if let Some(parent) = metadata_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        write_atomic(&metadata_path, serde_json::to_string_pretty(stream)?)?;
+        
+        if let Some(head) = &stream.head_commit {
+            self.write_head(&stream.name, head)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Loads a stream; streams that only have a ref (e.g. created before
+    /// metadata existed) get default linear metadata.
+    pub fn load(&self, name: &str) -> Result<Stream> {
+        let mut stream = self.load_metadata(name)?;
+        stream.head_commit = self.read_head(name)?;
+        Ok(stream)
+    }
+    
+    /// Loads a stream's metadata without reading its ref, leaving
+    /// `head_commit` unset; works even when the ref is damaged.
+    pub fn load_metadata(&self, name: &str) -> Result<Stream> {
+        if !self.exists(name) {
+            return Err(FlowError::StreamNotFound(name.to_string()));
+        }
+        
+        let metadata_path = self.metadata_path(name);
+        if metadata_path.exists() {
+            Ok(serde_json::from_str(&std::fs::read_to_string(&metadata_path)?)?)
+        } else {
+            Ok(Stream::new(name.to_string(), StreamMode::Linear))
+        }
+    }
+    
+    pub fn list(&self) -> Result<Vec<Stream>> {
+        self.names()?.iter().map(|name| self.load(name)).collect()
+    }
+    
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = std::collections::BTreeSet::new();
+        
+        if self.streams_path.exists() {
+            for entry in WalkDir::new(&self.streams_path).into_iter().filter_map(|e| e.ok()) {
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                
+                let relative = entry.path()
+                    .strip_prefix(&self.streams_path)
+                    .unwrap_or(entry.path())
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                let name = relative.strip_suffix(METADATA_SUFFIX).unwrap_or(&relative);
+                
+                if Stream::validate_name(name).is_ok() {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        
+        Ok(names.into_iter().collect())
+    }
+    
+    pub fn delete(&self, name: &str) -> Result<()> {
+        if !self.exists(name) {
+            return Err(FlowError::StreamNotFound(name.to_string()));
+        }
+        
+        for path in [self.ref_path(name), self.metadata_path(name)] {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        
+        // Drop directories left empty by names like feature/x
+        let mut dir = self.ref_path(name).parent().map(|p| p.to_path_buf());
+        while let Some(current) = dir {
+            if current == self.streams_path || std::fs::remove_dir(&current).is_err() {
+                break;
+            }
+            dir = current.parent().map(|p| p.to_path_buf());
+        }
+        
+        Ok(())
+    }
+    
+    pub fn read_head(&self, name: &str) -> Result<Option<CommitId>> {
+        Self::read_ref(&self.ref_path(name))
+    }
+    
+    pub fn write_head(&self, name: &str, commit_id: &CommitId) -> Result<()> {
+        Self::write_ref(&self.ref_path(name), commit_id)
+    }
+    
+    /// Ref name (relative to the repository directory) of a variant head,
+    /// suitable for a symbolic HEAD.
+    pub fn variant_ref(stream: &str, variant: &str) -> String {
+        format!("refs/{}/{}/{}", VARIANT_REFS, stream, variant)
+    }
+    
+    /// Splits a ref produced by `variant_ref` back into stream and variant.
+    pub fn parse_variant_ref(reference: &str) -> Option<(String, String)> {
+        let rest = reference.strip_prefix(&format!("refs/{}/", VARIANT_REFS))?;
+        let (stream, variant) = rest.rsplit_once('/')?;
+        Some((stream.to_string(), variant.to_string()))
+    }
+    
+    pub fn read_variant_head(&self, stream: &str, variant: &str) -> Result<Option<CommitId>> {
+        Self::read_ref(&self.variant_path(VARIANT_REFS, stream, variant))
+    }
+    
+    pub fn write_variant_head(&self, stream: &str, variant: &str, commit_id: &CommitId) -> Result<()> {
+        Self::write_ref(&self.variant_path(VARIANT_REFS, stream, variant), commit_id)
+    }
+    
+    pub fn read_archived_variant_head(&self, stream: &str, variant: &str) -> Result<Option<CommitId>> {
+        Self::read_ref(&self.variant_path(ARCHIVED_VARIANT_REFS, stream, variant))
+    }
+    
+    /// Moves a variant head into the archive, keeping its commits reachable.
+    pub fn archive_variant(&self, stream: &str, variant: &str) -> Result<()> {
+        let active = self.variant_path(VARIANT_REFS, stream, variant);
+        
+        if let Some(head) = Self::read_ref(&active)? {
+            Self::write_ref(&self.variant_path(ARCHIVED_VARIANT_REFS, stream, variant), &head)?;
+        }
+        
+        self.remove_variant(stream, variant)
+    }
+    
+    pub fn remove_variant(&self, stream: &str, variant: &str) -> Result<()> {
+        let active = self.variant_path(VARIANT_REFS, stream, variant);
+        
+        if active.exists() {
+            std::fs::remove_file(&active)?;
+        }
+        
+        // Drop refs/quantum/<stream> once its last variant is gone
+        let mut dir = active.parent().map(|p| p.to_path_buf());
+        while let Some(current) = dir {
+            if current == self.refs_path || std::fs::remove_dir(&current).is_err() {
+                break;
+            }
+            dir = current.parent().map(|p| p.to_path_buf());
+        }
+        
+        Ok(())
+    }
+    
+    fn variant_path(&self, namespace: &str, stream: &str, variant: &str) -> PathBuf {
+        self.refs_path.join(namespace).join(stream).join(variant)
+    }
+    
+    fn read_ref(path: &Path) -> Result<Option<CommitId>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        Ok(Some(CommitId::parse(&std::fs::read_to_string(path)?)?))
+    }
+    
+    fn write_ref(path: &Path, commit_id: &CommitId) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        write_atomic(path, commit_id.to_string())?;
+        Ok(())
+    }
+    
+    fn ref_path(&self, name: &str) -> PathBuf {
+        self.streams_path.join(name)
+    }
+    
+    fn metadata_path(&self, name: &str) -> PathBuf {
+        self.streams_path.join(format!("{}{}", name, METADATA_SUFFIX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::objects::Hash;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_stream_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = StreamStore::new(&temp_dir.path().join("streams"));
+        
+        let mut stream = Stream::new("feature/auth".to_string(), StreamMode::Parallel);
+        stream.set_description("Login flow".to_string());
+        stream.set_head(CommitId::from_hash(Hash::new(b"head")));
+        store.save(&stream).unwrap();
+        
+        let loaded = store.load("feature/auth").unwrap();
+        assert_eq!(loaded.id, stream.id);
+        assert!(matches!(loaded.mode, StreamMode::Parallel));
+        assert_eq!(loaded.description.as_deref(), Some("Login flow"));
+        assert_eq!(loaded.head_commit, stream.head_commit);
+        
+        // Ref-only streams are listed with default metadata
+        store.write_head("main", &CommitId::from_hash(Hash::new(b"main"))).unwrap();
+        let names: Vec<String> = store.list().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["feature/auth".to_string(), "main".to_string()]);
+        
+        store.delete("feature/auth").unwrap();
+        assert!(!store.exists("feature/auth"));
+        assert!(!temp_dir.path().join("streams").join("feature").exists());
+        assert!(matches!(store.load("feature/auth"), Err(FlowError::StreamNotFound(_))));
+    }
+    
+    #[test]
+    fn test_variant_refs() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = StreamStore::new(&temp_dir.path().join("refs").join("streams"));
+        let head = CommitId::from_hash(Hash::new(b"variant"));
+        
+        let reference = StreamStore::variant_ref("exp/cache", "lru");
+        assert_eq!(reference, "refs/quantum/exp/cache/lru");
+        assert_eq!(
+            StreamStore::parse_variant_ref(&reference),
+            Some(("exp/cache".to_string(), "lru".to_string()))
+        );
+        assert_eq!(StreamStore::parse_variant_ref("refs/streams/main"), None);
+        
+        store.write_variant_head("exp/cache", "lru", &head).unwrap();
+        assert_eq!(store.read_variant_head("exp/cache", "lru").unwrap(), Some(head.clone()));
+        
+        store.archive_variant("exp/cache", "lru").unwrap();
+        assert_eq!(store.read_variant_head("exp/cache", "lru").unwrap(), None);
+        assert_eq!(store.read_archived_variant_head("exp/cache", "lru").unwrap(), Some(head));
+        assert!(!temp_dir.path().join("refs").join("quantum").exists());
+    }
+}
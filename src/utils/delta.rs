@@ -0,0 +1,202 @@
+use crate::error::{FlowError, Result};
+use std::collections::HashMap;
+
+/// Matches shorter than this are cheaper to insert than to copy.
+const BLOCK_SIZE: usize = 16;
+/// Candidate base positions remembered per block, to bound the work on
+/// highly repetitive input.
+const MAX_CANDIDATES: usize = 8;
+
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+/// Encodes `target` as copy/insert instructions against `base`.
+///
+/// The delta starts with the base and target lengths as varints, followed by
+/// `copy <offset> <len>` ranges of the base and `insert <len> <bytes>` runs.
+pub fn compute(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut blocks: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for offset in (0..base.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE) {
+        let candidates = blocks.entry(&base[offset..offset + BLOCK_SIZE]).or_default();
+        if candidates.len() < MAX_CANDIDATES {
+            candidates.push(offset);
+        }
+    }
+    
+    let mut delta = Vec::new();
+    write_varint(&mut delta, base.len() as u64);
+    write_varint(&mut delta, target.len() as u64);
+    
+    let mut pending_start = 0;
+    let mut position = 0;
+    
+    while position + BLOCK_SIZE <= target.len() {
+        let best = blocks.get(&target[position..position + BLOCK_SIZE])
+            .into_iter()
+            .flatten()
+            .map(|&offset| {
+                let length = base[offset..].iter()
+                    .zip(&target[position..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (offset, length)
+            })
+            .max_by_key(|&(_, length)| length);
+        
+        match best {
+            Some((mut offset, mut length)) => {
+                // Grow the match backwards over bytes that would otherwise be inserted
+                while offset > 0 && position > pending_start && base[offset - 1] == target[position - 1] {
+                    offset -= 1;
+                    position -= 1;
+                    length += 1;
+                }
+                
+                write_insert(&mut delta, &target[pending_start..position]);
+                delta.push(OP_COPY);
+                write_varint(&mut delta, offset as u64);
+                write_varint(&mut delta, length as u64);
+                
+                position += length;
+                pending_start = position;
+            }
+            None => position += 1,
+        }
+    }
+    
+    write_insert(&mut delta, &target[pending_start..]);
+    delta
+}
+
+/// Rebuilds the target from `base` and a delta produced by `compute`.
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut cursor = 0;
+    let base_len = read_varint(delta, &mut cursor)? as usize;
+    let target_len = read_varint(delta, &mut cursor)? as usize;
+    
+    if base_len != base.len() {
+        return Err(corrupt(format!("delta expects a {} byte base, got {}", base_len, base.len())));
+    }
+    
+    // target_len comes from pack data; only trust it up to what a delta
+    // without repeated copies could produce
+    let mut target = Vec::with_capacity(target_len.min(base.len() + delta.len()));
+    while cursor < delta.len() {
+        let op = delta[cursor];
+        cursor += 1;
+        
+        match op {
+            OP_COPY => {
+                let offset = read_varint(delta, &mut cursor)? as usize;
+                let length = read_varint(delta, &mut cursor)? as usize;
+                let range = base.get(offset..offset.saturating_add(length))
+                    .ok_or_else(|| corrupt("copy outside of the base".to_string()))?;
+                target.extend_from_slice(range);
+            }
+            OP_INSERT => {
+                let length = read_varint(delta, &mut cursor)? as usize;
+                let bytes = delta.get(cursor..cursor.saturating_add(length))
+                    .ok_or_else(|| corrupt("truncated insert".to_string()))?;
+                target.extend_from_slice(bytes);
+                cursor += length;
+            }
+            other => return Err(corrupt(format!("unknown delta instruction {}", other))),
+        }
+        
+        if target.len() > target_len {
+            return Err(corrupt(format!("delta produces more than the expected {} bytes", target_len)));
+        }
+    }
+    
+    if target.len() != target_len {
+        return Err(corrupt(format!("delta produced {} bytes, expected {}", target.len(), target_len)));
+    }
+    
+    Ok(target)
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    
+    delta.push(OP_INSERT);
+    write_varint(delta, bytes.len() as u64);
+    delta.extend_from_slice(bytes);
+}
+
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn read_varint(bytes: &[u8], cursor: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    
+    loop {
+        let byte = *bytes.get(*cursor).ok_or_else(|| corrupt("truncated varint".to_string()))?;
+        *cursor += 1;
+        
+        if shift >= 64 {
+            return Err(corrupt("varint overflow".to_string()));
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+fn corrupt(message: String) -> FlowError {
+    FlowError::CorruptObject(format!("bad delta: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_delta_roundtrip() {
+        let base: Vec<u8> = (0..200).flat_map(|i| format!("line {} of the original file\n", i).into_bytes()).collect();
+        let mut target = base.clone();
+        target.splice(1000..1010, b"an edited stretch".iter().copied());
+        target.extend_from_slice(b"appended at the end\n");
+        
+        let delta = compute(&base, &target);
+        assert!(delta.len() < target.len() / 10);
+        assert_eq!(apply(&base, &delta).unwrap(), target);
+        
+        // Unrelated and empty inputs still round-trip
+        for (base, target) in [(&b"abc"[..], &b"completely different"[..]), (b"", b"new"), (b"old", b"")] {
+            assert_eq!(apply(base, &compute(base, target)).unwrap(), target);
+        }
+    }
+    
+    #[test]
+    fn test_apply_rejects_wrong_base() {
+        let delta = compute(b"the original base content", b"the original base content, extended");
+        assert!(matches!(apply(b"another base", &delta), Err(FlowError::CorruptObject(_))));
+        assert!(apply(b"the original base content", &delta[..delta.len() - 2]).is_err());
+    }
+    
+    #[test]
+    fn test_apply_rejects_corrupt_target_length() {
+        let base = b"the original base content";
+        for target_len in [1u64 << 62, 3] {
+            let mut delta = Vec::new();
+            write_varint(&mut delta, base.len() as u64);
+            write_varint(&mut delta, target_len);
+            delta.push(OP_COPY);
+            write_varint(&mut delta, 0);
+            write_varint(&mut delta, base.len() as u64);
+            
+            assert!(matches!(apply(base, &delta), Err(FlowError::CorruptObject(_))));
+        }
+    }
+}
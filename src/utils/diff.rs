@@ -1,12 +1,33 @@
-// Placeholder for diff functionality
-// This will be implemented in future iterations
-
 use crate::error::{FlowError, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffAlgorithm {
+    /// Classic shortest edit script (Myers, 1986)
+    #[default]
+    Myers,
+    /// Anchors on lines that are unique on both sides, then falls back to
+    /// Myers between anchors; tends to read better for moved blocks
+    Patience,
+}
+
+impl std::str::FromStr for DiffAlgorithm {
+    type Err = FlowError;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "myers" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            other => Err(FlowError::ConfigError(format!("Unknown diff algorithm: {}", other))),
+        }
+    }
+}
 
 pub struct DiffOptions {
     pub context_lines: usize,
     pub ignore_whitespace: bool,
     pub ignore_case: bool,
+    pub algorithm: DiffAlgorithm,
 }
 
 impl Default for DiffOptions {
@@ -15,6 +36,7 @@ impl Default for DiffOptions {
             context_lines: 3,
             ignore_whitespace: false,
             ignore_case: false,
+            algorithm: DiffAlgorithm::default(),
         }
     }
 }
@@ -22,61 +44,442 @@ impl Default for DiffOptions {
 pub struct DiffResult {
     pub additions: usize,
     pub deletions: usize,
+    /// Full edit script (empty when the inputs compare equal)
     pub changes: Vec<DiffLine>,
+    /// Changes grouped with `context_lines` of surrounding context
+    pub hunks: Vec<Hunk>,
+}
+
+impl DiffResult {
+    pub fn empty() -> Self {
+        Self {
+            additions: 0,
+            deletions: 0,
+            changes: Vec::new(),
+            hunks: Vec::new(),
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DiffLine {
     Context(String),
     Addition(String),
     Deletion(String),
 }
 
-pub fn diff_strings(old: &str, new: &str, _options: &DiffOptions) -> Result<DiffResult> {
-    // Placeholder implementation
-    // TODO: Implement proper diff algorithm
+/// A unified-diff hunk; line numbers are 1-based as in `@@ -a,b +c,d @@`.
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{} +{} @@",
+            Self::range(self.old_start, self.old_lines),
+            Self::range(self.new_start, self.new_lines),
+        )
+    }
     
+    fn range(start: usize, len: usize) -> String {
+        match len {
+            // An empty range points at the line *before* the change
+            0 => format!("{},0", start.saturating_sub(1)),
+            1 => start.to_string(),
+            _ => format!("{},{}", start, len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+pub fn diff_strings(old: &str, new: &str, options: &DiffOptions) -> Result<DiffResult> {
     if old == new {
-        return Ok(DiffResult {
-            additions: 0,
-            deletions: 0,
-            changes: vec![],
-        });
+        return Ok(DiffResult::empty());
     }
     
-    // Simple line-by-line comparison
     let old_lines: Vec<&str> = old.lines().collect();
     let new_lines: Vec<&str> = new.lines().collect();
     
-    let mut changes = Vec::new();
+    // Lines are compared through a normalized key so the ignore options
+    // only affect matching, never the text that is printed
+    let old_keys: Vec<String> = old_lines.iter().map(|l| comparison_key(l, options)).collect();
+    let new_keys: Vec<String> = new_lines.iter().map(|l| comparison_key(l, options)).collect();
+    
+    let edits = match options.algorithm {
+        DiffAlgorithm::Myers => myers(&old_keys, &new_keys, 0, old_keys.len(), 0, new_keys.len()),
+        DiffAlgorithm::Patience => patience(&old_keys, &new_keys, 0, old_keys.len(), 0, new_keys.len()),
+    };
+    
+    let mut changes = Vec::with_capacity(edits.len());
     let mut additions = 0;
     let mut deletions = 0;
     
-    // Very basic diff - just show all old lines as deletions and new lines as additions
-    for line in &old_lines {
-        changes.push(DiffLine::Deletion(line.to_string()));
-        deletions += 1;
+    for edit in &edits {
+        match *edit {
+            Edit::Equal(_, j) => changes.push(DiffLine::Context(new_lines[j].to_string())),
+            Edit::Delete(i) => {
+                changes.push(DiffLine::Deletion(old_lines[i].to_string()));
+                deletions += 1;
+            }
+            Edit::Insert(j) => {
+                changes.push(DiffLine::Addition(new_lines[j].to_string()));
+                additions += 1;
+            }
+        }
     }
     
-    for line in &new_lines {
-        changes.push(DiffLine::Addition(line.to_string()));
-        additions += 1;
+    if additions == 0 && deletions == 0 {
+        return Ok(DiffResult::empty());
     }
     
+    let hunks = build_hunks(&edits, &changes, options.context_lines);
+    
     Ok(DiffResult {
         additions,
         deletions,
         changes,
+        hunks,
     })
 }
 
-pub fn diff_files(old_path: &str, new_path: &str, options: &DiffOptions) -> Result<DiffResult> {
-    let old_content = std::fs::read_to_string(old_path)
-        .map_err(|_| FlowError::FileNotFound(old_path.to_string()))?;
+/// For every line of `old`, the index of the line it is matched with in
+/// `new` by a shortest edit script, or `None` if it was deleted.
+pub fn match_lines(old: &[String], new: &[String]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; old.len()];
+    
+    for edit in myers(old, new, 0, old.len(), 0, new.len()) {
+        if let Edit::Equal(i, j) = edit {
+            matches[i] = Some(j);
+        }
+    }
+    
+    matches
+}
+
+fn comparison_key(line: &str, options: &DiffOptions) -> String {
+    let line = if options.ignore_whitespace {
+        line.chars().filter(|c| !c.is_whitespace()).collect()
+    } else {
+        line.to_string()
+    };
+    
+    if options.ignore_case {
+        line.to_lowercase()
+    } else {
+        line
+    }
+}
+
+/// Myers' O(ND) shortest edit script over `a[a_lo..a_hi]` and `b[b_lo..b_hi]`,
+/// in linear space: each round splits the problem at the middle snake of
+/// an optimal path rather than keeping every round's furthest reaches.
+fn myers(a: &[String], b: &[String], a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    myers_into(a, b, a_lo, a_hi, b_lo, b_hi, &mut edits);
     
-    let new_content = std::fs::read_to_string(new_path)
-        .map_err(|_| FlowError::FileNotFound(new_path.to_string()))?;
+    // Splits can leave insertions ahead of deletions; show each change's
+    // removed lines first
+    for run in edits.split_mut(|edit| matches!(edit, Edit::Equal(..))) {
+        run.sort_by_key(|edit| matches!(edit, Edit::Insert(_)));
+    }
+    edits
+}
+
+fn myers_into(
+    a: &[String],
+    b: &[String],
+    mut a_lo: usize,
+    mut a_hi: usize,
+    mut b_lo: usize,
+    mut b_hi: usize,
+    edits: &mut Vec<Edit>,
+) {
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        edits.push(Edit::Equal(a_lo, b_lo));
+        a_lo += 1;
+        b_lo += 1;
+    }
+    
+    let mut suffix = 0;
+    while a_lo < a_hi && b_lo < b_hi && a[a_hi - 1] == b[b_hi - 1] {
+        a_hi -= 1;
+        b_hi -= 1;
+        suffix += 1;
+    }
+    
+    if a_lo == a_hi {
+        edits.extend((b_lo..b_hi).map(Edit::Insert));
+    } else if b_lo == b_hi {
+        edits.extend((a_lo..a_hi).map(Edit::Delete));
+    } else if let Some((x, y)) = middle_snake(a, b, a_lo, a_hi, b_lo, b_hi) {
+        myers_into(a, b, a_lo, x, b_lo, y, edits);
+        myers_into(a, b, x, a_hi, y, b_hi, edits);
+    } else {
+        edits.extend((a_lo..a_hi).map(Edit::Delete));
+        edits.extend((b_lo..b_hi).map(Edit::Insert));
+    }
+    
+    edits.extend((0..suffix).map(|i| Edit::Equal(a_hi + i, b_hi + i)));
+}
+
+/// Where an optimal path through `a[a_lo..a_hi]` and `b[b_lo..b_hi]` crosses
+/// its middle, found by searching forward from the start and backward from
+/// the end until the two meet.
+fn middle_snake(a: &[String], b: &[String], a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> Option<(usize, usize)> {
+    let n = (a_hi - a_lo) as isize;
+    let m = (b_hi - b_lo) as isize;
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    let max_d = (n + m + 1) / 2 + 1;
+    
+    // Furthest x reached on each diagonal k, at index k + offset
+    let offset = max_d;
+    let mut forward = vec![0isize; 2 * max_d as usize + 1];
+    let mut backward = vec![0isize; 2 * max_d as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+    
+    for d in 0..max_d {
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[a_lo + x as usize] == b[b_lo + y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            
+            if odd && (k - delta).abs() < d && forward[at(k)] + backward[at(delta - k)] >= n {
+                return Some((a_lo + x0 as usize, b_lo + y0 as usize));
+            }
+            k -= 2;
+        }
+        
+        let mut k = d;
+        while k >= -d {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[a_hi - 1 - x as usize] == b[b_hi - 1 - y as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            
+            if !odd && (k - delta).abs() <= d && backward[at(k)] + forward[at(delta - k)] >= n {
+                return Some((a_hi - x as usize, b_hi - y as usize));
+            }
+            k -= 2;
+        }
+    }
+    
+    None
+}
+
+/// Patience diff over `a[a_lo..a_hi]` and `b[b_lo..b_hi]`.
+fn patience(a: &[String], b: &[String], a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut a_lo, mut a_hi, mut b_lo, mut b_hi) = (a_lo, a_hi, b_lo, b_hi);
+    
+    // Common prefix
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        edits.push(Edit::Equal(a_lo, b_lo));
+        a_lo += 1;
+        b_lo += 1;
+    }
+    
+    // Common suffix (emitted last)
+    let mut suffix = Vec::new();
+    while a_lo < a_hi && b_lo < b_hi && a[a_hi - 1] == b[b_hi - 1] {
+        a_hi -= 1;
+        b_hi -= 1;
+        suffix.push(Edit::Equal(a_hi, b_hi));
+    }
+    
+    let anchors = unique_common_lines(a, b, a_lo, a_hi, b_lo, b_hi);
+    
+    if anchors.is_empty() {
+        edits.extend(myers(a, b, a_lo, a_hi, b_lo, b_hi));
+    } else {
+        let (mut prev_a, mut prev_b) = (a_lo, b_lo);
+        
+        for (i, j) in anchors {
+            edits.extend(patience(a, b, prev_a, i, prev_b, j));
+            edits.push(Edit::Equal(i, j));
+            prev_a = i + 1;
+            prev_b = j + 1;
+        }
+        
+        edits.extend(patience(a, b, prev_a, a_hi, prev_b, b_hi));
+    }
+    
+    edits.extend(suffix.into_iter().rev());
+    edits
+}
+
+/// Lines occurring exactly once on each side, reduced to the longest
+/// sequence whose positions increase on both sides.
+fn unique_common_lines(
+    a: &[String],
+    b: &[String],
+    a_lo: usize,
+    a_hi: usize,
+    b_lo: usize,
+    b_hi: usize,
+) -> Vec<(usize, usize)> {
+    let mut counts: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    
+    for (i, line) in a.iter().enumerate().take(a_hi).skip(a_lo) {
+        let entry = counts.entry(line.as_str()).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    
+    let mut pairs = Vec::new();
+    let mut b_counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (j, line) in b.iter().enumerate().take(b_hi).skip(b_lo) {
+        let entry = b_counts.entry(line.as_str()).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 = j;
+    }
+    
+    for (line, (a_count, _, i)) in &counts {
+        if let Some((b_count, j)) = b_counts.get(line) {
+            if *a_count == 1 && *b_count == 1 {
+                pairs.push((*i, *j));
+            }
+        }
+    }
     
-    diff_strings(&old_content, &new_content, options)
+    pairs.sort();
+    longest_increasing_by_b(&pairs)
+}
+
+fn longest_increasing_by_b(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Patience sorting: `tails[k]` is the index of the smallest tail of an
+    // increasing run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let position = tails.partition_point(|&t| pairs[t].1 < j);
+        
+        if position > 0 {
+            previous[index] = Some(tails[position - 1]);
+        }
+        
+        if position == tails.len() {
+            tails.push(index);
+        } else {
+            tails[position] = index;
+        }
+    }
+    
+    let mut result = Vec::new();
+    let mut current = tails.last().copied();
+    while let Some(index) = current {
+        result.push(pairs[index]);
+        current = previous[index];
+    }
+    
+    result.reverse();
+    result
+}
+
+fn build_hunks(edits: &[Edit], lines: &[DiffLine], context: usize) -> Vec<Hunk> {
+    let changed: Vec<usize> = edits.iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(index, _)| index)
+        .collect();
+    
+    // Group changes whose surrounding context would overlap or touch
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(edits.len());
+        
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    
+    // 1-based line numbers at the start of each edit
+    let mut old_line = 1;
+    let mut new_line = 1;
+    let mut positions = Vec::with_capacity(edits.len());
+    for edit in edits {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Equal(..) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Delete(_) => old_line += 1,
+            Edit::Insert(_) => new_line += 1,
+        }
+    }
+    
+    ranges.into_iter()
+        .map(|(start, end)| {
+            let (old_start, new_start) = positions[start];
+            let old_lines = edits[start..end].iter().filter(|e| !matches!(e, Edit::Insert(_))).count();
+            let new_lines = edits[start..end].iter().filter(|e| !matches!(e, Edit::Delete(_))).count();
+            
+            Hunk {
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                lines: lines[start..end].to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Renders the hunks of a diff, each preceded by its `@@` header.
+pub fn format_unified(result: &DiffResult) -> String {
+    let mut output = String::new();
+    
+    for hunk in &result.hunks {
+        output.push_str(&hunk.header());
+        output.push('\n');
+        
+        for line in &hunk.lines {
+            output.push_str(&format_line(line));
+        }
+    }
+    
+    output
+}
+
+fn format_line(line: &DiffLine) -> String {
+    match line {
+        DiffLine::Context(text) => format!(" {}\n", text),
+        DiffLine::Deletion(text) => format!("-{}\n", text),
+        DiffLine::Addition(text) => format!("+{}\n", text),
+    }
 }
 
 #[cfg(test)]
@@ -102,4 +505,159 @@ mod tests {
         assert_eq!(result.deletions, 1);
         assert_eq!(result.changes.len(), 2);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_format_unified_lines() {
+        let options = DiffOptions::default();
+        let result = diff_strings("hello", "world", &options).unwrap();
+        
+        assert_eq!(format_unified(&result), "@@ -1 +1 @@\n-hello\n+world\n");
+    }
+    
+    #[test]
+    fn test_diff_keeps_common_lines() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nd\ne\n";
+        
+        for algorithm in [DiffAlgorithm::Myers, DiffAlgorithm::Patience] {
+            let options = DiffOptions { algorithm, ..Default::default() };
+            let result = diff_strings(old, new, &options).unwrap();
+            
+            assert_eq!(result.deletions, 1, "{:?}", algorithm);
+            assert_eq!(result.additions, 1, "{:?}", algorithm);
+            assert_eq!(
+                result.changes,
+                vec![
+                    DiffLine::Context("a".to_string()),
+                    DiffLine::Deletion("b".to_string()),
+                    DiffLine::Context("c".to_string()),
+                    DiffLine::Context("d".to_string()),
+                    DiffLine::Addition("e".to_string()),
+                ]
+            );
+        }
+    }
+    
+    #[test]
+    fn test_unified_hunks_respect_context() {
+        let old: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let new = old.replace("line 3\n", "line three\n").replace("line 18\n", "line eighteen\n");
+        
+        let options = DiffOptions { context_lines: 2, ..Default::default() };
+        let result = diff_strings(&old, &new, &options).unwrap();
+        
+        assert_eq!(result.hunks.len(), 2);
+        assert_eq!(result.hunks[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(result.hunks[1].header(), "@@ -16,5 +16,5 @@");
+        
+        let unified = format_unified(&result);
+        assert!(unified.starts_with("@@ -1,5 +1,5 @@\n line 1\n line 2\n-line 3\n+line three\n"));
+        
+        // Enough context merges both changes into one hunk
+        let options = DiffOptions { context_lines: 10, ..Default::default() };
+        let result = diff_strings(&old, &new, &options).unwrap();
+        assert_eq!(result.hunks.len(), 1);
+    }
+    
+    #[test]
+    fn test_pure_insertion_hunk_header() {
+        let options = DiffOptions::default();
+        let result = diff_strings("", "new\n", &options).unwrap();
+        
+        assert_eq!(result.hunks[0].header(), "@@ -0,0 +1 @@");
+    }
+    
+    #[test]
+    fn test_ignore_whitespace_and_case() {
+        let old = "fn main() {\n    Hello();\n}\n";
+        let new = "fn main() {\n\thello ();\n}\n";
+        
+        let strict = diff_strings(old, new, &DiffOptions::default()).unwrap();
+        assert_eq!(strict.additions, 1);
+        
+        let whitespace_only = DiffOptions { ignore_whitespace: true, ..Default::default() };
+        assert_eq!(diff_strings(old, new, &whitespace_only).unwrap().additions, 1);
+        
+        let lenient = DiffOptions { ignore_whitespace: true, ignore_case: true, ..Default::default() };
+        let result = diff_strings(old, new, &lenient).unwrap();
+        assert_eq!(result.additions, 0);
+        assert!(result.hunks.is_empty());
+    }
+    
+    #[test]
+    fn test_patience_anchors_on_unique_lines() {
+        let old = "fn a() {\n}\nfn b() {\n}\n";
+        let new = "fn b() {\n}\nfn a() {\n}\n";
+        
+        let options = DiffOptions { algorithm: DiffAlgorithm::Patience, ..Default::default() };
+        let result = diff_strings(old, new, &options).unwrap();
+        
+        // Every line is accounted for exactly once
+        let context = result.changes.iter().filter(|l| matches!(l, DiffLine::Context(_))).count();
+        assert_eq!(context + result.deletions, 4);
+        assert_eq!(context + result.additions, 4);
+    }
+    
+    #[test]
+    fn test_myers_finds_shortest_scripts() {
+        let lines = |text: &str| -> Vec<String> { text.chars().map(String::from).collect() };
+        let lcs = |a: &[String], b: &[String]| {
+            let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    table[i][j] = if a[i] == b[j] { table[i + 1][j + 1] + 1 } else { table[i + 1][j].max(table[i][j + 1]) };
+                }
+            }
+            table[0][0]
+        };
+        
+        let pairs = [("abcabba", "cbabac"), ("", "abc"), ("abc", ""), ("abcdef", "fedcba"), ("aaaaab", "baaaaa"), ("xaxbxc", "abc")];
+        for (old, new) in pairs {
+            let (a, b) = (lines(old), lines(new));
+            let edits = myers(&a, &b, 0, a.len(), 0, b.len());
+            
+            // Replaying the script walks both sides in order
+            let (mut i, mut j) = (0, 0);
+            for edit in &edits {
+                match *edit {
+                    Edit::Equal(x, y) => {
+                        assert_eq!((x, y), (i, j));
+                        assert_eq!(a[x], b[y]);
+                        i += 1;
+                        j += 1;
+                    }
+                    Edit::Delete(x) => {
+                        assert_eq!(x, i);
+                        i += 1;
+                    }
+                    Edit::Insert(y) => {
+                        assert_eq!(y, j);
+                        j += 1;
+                    }
+                }
+            }
+            assert_eq!((i, j), (a.len(), b.len()));
+            
+            let equal = edits.iter().filter(|edit| matches!(edit, Edit::Equal(..))).count();
+            assert_eq!(equal, lcs(&a, &b), "{} -> {}", old, new);
+        }
+    }
+    
+    #[test]
+    fn test_myers_fully_rewritten_large_input() {
+        // Quadratic memory in the edit distance would need gigabytes here
+        let old: String = (0..8000).map(|i| format!("old line {}\n", i)).collect();
+        let new: String = (0..8000).map(|i| format!("new line {}\n", i)).collect();
+        
+        let result = diff_strings(&old, &new, &DiffOptions::default()).unwrap();
+        assert_eq!(result.deletions, 8000);
+        assert_eq!(result.additions, 8000);
+    }
+    
+    #[test]
+    fn test_algorithm_from_str() {
+        assert_eq!("myers".parse::<DiffAlgorithm>().unwrap(), DiffAlgorithm::Myers);
+        assert_eq!("Patience".parse::<DiffAlgorithm>().unwrap(), DiffAlgorithm::Patience);
+        assert!("histogram".parse::<DiffAlgorithm>().is_err());
+    }
+}
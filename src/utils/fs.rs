@@ -1,61 +1,41 @@
 use crate::error::{FlowError, Result};
+use crate::utils::ignore_rules::IgnoreRules;
+use globset::{Glob, GlobMatcher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
-pub fn find_repository_root(start_path: &Path) -> Option<PathBuf> {
-    let mut current_path = start_path;
-    
-    loop {
-        let flow_dir = current_path.join(".flowversion");
-        if flow_dir.exists() && flow_dir.is_dir() {
-            return Some(current_path.to_path_buf());
-        }
-        
-        match current_path.parent() {
-            Some(parent) => current_path = parent,
-            None => return None,
-        }
-    }
-}
+/// Distinguishes temporary files of concurrent writes within one process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-pub fn is_ignored(path: &Path) -> bool {
-    let path_str = path.to_string_lossy();
-    
-    // Ignore FlowVersion directory
-    if path_str.contains(".flowversion") {
-        return true;
+/// The per-user configuration directory (`~/.config/flowversion` on
+/// Linux); `FLOWVERSION_CONFIG_DIR` overrides it.
+pub fn user_config_dir() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("FLOWVERSION_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
     }
     
-    // Ignore common files and directories
-    let ignore_patterns = [
-        ".git",
-        ".gitignore",
-        "node_modules",
-        "target",
-        ".DS_Store",
-        "Thumbs.db",
-        ".tmp",
-        ".temp",
-    ];
-    
-    for pattern in &ignore_patterns {
-        if path_str.contains(pattern) {
-            return true;
-        }
-    }
-    
-    false
+    dirs::config_dir().map(|dir| dir.join("flowversion"))
 }
 
-pub fn list_tracked_files(repo_root: &Path) -> Result<Vec<PathBuf>> {
+/// Lists the files under `repo_root` that `rules` do not ignore, relative
+/// to the root. Ignored directories are not descended into.
+pub fn list_tracked_files(repo_root: &Path, rules: &IgnoreRules) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
     
     for entry in WalkDir::new(repo_root)
+        .min_depth(1)
         .into_iter()
-        .filter_entry(|e| !is_ignored(e.path()))
+        .filter_entry(|e| {
+            // Only the part below the repository root is matched, so a root
+            // that happens to live under e.g. a temp directory is not ignored
+            let relative = normalize_path(e.path().strip_prefix(repo_root).unwrap_or(e.path()));
+            rules.matched(&relative.to_string_lossy(), e.file_type().is_dir())
+                .is_none_or(|rule| !rule.ignored)
+        })
     {
-        let entry = entry.map_err(|e| FlowError::IoError(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        let entry = entry.map_err(|e| FlowError::IoError(std::io::Error::other(
             format!("Walk directory error: {}", e)
         )))?;
         
@@ -69,57 +49,199 @@ pub fn list_tracked_files(repo_root: &Path) -> Result<Vec<PathBuf>> {
     Ok(files)
 }
 
+/// A path or pattern naming files in the repository, as given to `flow add`
+/// and `flow restore`. A directory matches everything below it and `.`
+/// matches everything; specs containing `*`, `?` or `[` are globs over the
+/// whole path, where `*` also matches `/`.
+pub struct Pathspec {
+    spec: String,
+    glob: Option<GlobMatcher>,
+}
+
+impl Pathspec {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let normalized = normalize_repo_path(spec);
+        
+        let glob = if normalized.contains(['*', '?', '[']) {
+            let glob = Glob::new(&normalized).map_err(|e| FlowError::InvalidPathspec(format!("{}: {}", spec, e)))?;
+            Some(glob.compile_matcher())
+        } else {
+            None
+        };
+        
+        Ok(Self { spec: normalized, glob })
+    }
+    
+    pub fn matches(&self, path: &str) -> bool {
+        match &self.glob {
+            Some(glob) => glob.is_match(path),
+            None => {
+                self.spec.is_empty()
+                    || path == self.spec
+                    || path.strip_prefix(&self.spec).is_some_and(|rest| rest.starts_with('/'))
+            }
+        }
+    }
+    
+    pub fn is_glob(&self) -> bool {
+        self.glob.is_some()
+    }
+    
+    /// The normalized spec, relative to the repository root.
+    pub fn as_str(&self) -> &str {
+        &self.spec
+    }
+}
+
+/// Returns the tree mode for a file: 0o755 when any execute bit is set,
+/// 0o644 otherwise (and always on platforms without Unix permissions).
+pub fn file_mode(metadata: &std::fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return 0o755;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    
+    0o644
+}
+
+/// Applies a tree mode to a file on disk (no-op without Unix permissions).
+pub fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    
+    Ok(())
+}
+
 pub fn normalize_path(path: &Path) -> PathBuf {
     // Convert to forward slashes for consistency across platforms
     let path_str = path.to_string_lossy().replace('\\', "/");
     PathBuf::from(path_str)
 }
 
-pub fn ensure_dir_exists(path: &Path) -> Result<()> {
-    if !path.exists() {
-        std::fs::create_dir_all(path)?;
+/// Normalizes a user-supplied relative path into the form used as a key in
+/// the index and in trees: forward slashes, no `.` components, no leading `./`.
+pub fn normalize_repo_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Replaces `path` with `contents` so that readers, and a crash at any
+/// point, see either the old file or the complete new one.
+///
+/// The data goes to a hidden temporary file next to `path`, is synced to
+/// disk, and is then renamed over `path`; the directory is synced too so
+/// the rename itself survives a crash.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let file_name = path.file_name()
+        .ok_or_else(|| FlowError::InternalError(format!("Cannot write to {}", path.display())))?
+        .to_string_lossy();
+    let temp_path = path.with_file_name(format!(
+        ".{}.tmp-{}-{}",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        sync_dir(path.parent())
+    })();
+    
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: Option<&Path>) -> Result<()> {
+    if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::File::open(dir)?.sync_all()?;
     }
     Ok(())
 }
 
+#[cfg(not(unix))]
+fn sync_dir(_dir: Option<&Path>) -> Result<()> {
+    // Directories cannot be opened for syncing here; the rename is still atomic
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
     
     #[test]
-    fn test_find_repository_root() {
+    fn test_list_tracked_files_relative_to_root() {
         let temp_dir = TempDir::new().unwrap();
-        let repo_root = temp_dir.path();
-        let flow_dir = repo_root.join(".flowversion");
-        std::fs::create_dir(&flow_dir).unwrap();
-        
-        let sub_dir = repo_root.join("sub").join("directory");
-        std::fs::create_dir_all(&sub_dir).unwrap();
+        let repo_root = temp_dir.path().join(".tmp-project");
+        std::fs::create_dir_all(repo_root.join("src")).unwrap();
+        std::fs::create_dir_all(repo_root.join(".flowversion")).unwrap();
+        std::fs::write(repo_root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(repo_root.join(".flowversion/HEAD"), "ref: refs/streams/main").unwrap();
         
-        let found_root = find_repository_root(&sub_dir);
-        assert_eq!(found_root, Some(repo_root.to_path_buf()));
+        let files = list_tracked_files(&repo_root, &IgnoreRules::with_global_file(&repo_root, None)).unwrap();
+        assert_eq!(files, vec![PathBuf::from("src/main.rs")]);
     }
     
     #[test]
-    fn test_find_repository_root_not_found() {
+    fn test_write_atomic_replaces_without_leftovers() {
         let temp_dir = TempDir::new().unwrap();
-        let path = temp_dir.path().join("no-repo");
-        std::fs::create_dir(&path).unwrap();
+        let path = temp_dir.path().join("index.json");
         
-        let found_root = find_repository_root(&path);
-        assert_eq!(found_root, None);
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        
+        // A failed write leaves the old content and no temporary file behind
+        let missing_dir = temp_dir.path().join("missing").join("file");
+        assert!(write_atomic(&missing_dir, "x").is_err());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+    
+    #[test]
+    fn test_normalize_repo_path() {
+        assert_eq!(normalize_repo_path("./src/main.rs"), "src/main.rs");
+        assert_eq!(normalize_repo_path("src\\core//mod.rs"), "src/core/mod.rs");
+        assert_eq!(normalize_repo_path("README.md"), "README.md");
     }
     
     #[test]
-    fn test_is_ignored() {
-        assert!(is_ignored(Path::new(".flowversion/objects")));
-        assert!(is_ignored(Path::new("node_modules/package")));
-        assert!(is_ignored(Path::new("target/debug/app")));
-        assert!(is_ignored(Path::new(".git/config")));
+    fn test_pathspec_matching() {
+        let dir = Pathspec::parse("./src/").unwrap();
+        assert!(dir.matches("src/main.rs"));
+        assert!(dir.matches("src/core/mod.rs"));
+        assert!(!dir.matches("srcs/lib.rs"));
+        
+        assert!(Pathspec::parse(".").unwrap().matches("README.md"));
+        assert!(Pathspec::parse("README.md").unwrap().matches("README.md"));
+        
+        let glob = Pathspec::parse("*.rs").unwrap();
+        assert!(glob.is_glob());
+        assert!(glob.matches("main.rs"));
+        assert!(glob.matches("src/core/mod.rs"));
+        assert!(!glob.matches("Cargo.toml"));
+        assert!(Pathspec::parse("src/*/mod.rs").unwrap().matches("src/core/mod.rs"));
         
-        assert!(!is_ignored(Path::new("src/main.rs")));
-        assert!(!is_ignored(Path::new("README.md")));
+        assert!(matches!(Pathspec::parse("src/[.rs"), Err(FlowError::InvalidPathspec(_))));
     }
     
     #[test]
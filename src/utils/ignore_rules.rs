@@ -0,0 +1,287 @@
+use crate::error::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Per-directory ignore file, with gitignore syntax.
+pub const IGNORE_FILE: &str = ".flowignore";
+
+/// Name of the global ignore file in the user configuration directory.
+pub const GLOBAL_IGNORE_FILE: &str = "ignore";
+
+/// Always ignored, whatever the rules say.
+const REPOSITORY_DIR: &str = ".flowversion";
+
+/// Applied after every user rule, so `.flowignore` can re-include them.
+const DEFAULT_PATTERNS: &[&str] = &[
+    ".git/",
+    ".gitignore",
+    "node_modules/",
+    "target/",
+    ".DS_Store",
+    "Thumbs.db",
+    "*.tmp",
+    "*.temp",
+];
+
+/// Where the rule that decided a path comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleSource {
+    /// A `.flowignore`, by path relative to the repository root
+    File(PathBuf),
+    /// The user's global ignore file
+    Global(PathBuf),
+    BuiltIn,
+}
+
+impl std::fmt::Display for RuleSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleSource::File(path) | RuleSource::Global(path) => write!(f, "{}", path.display()),
+            RuleSource::BuiltIn => write!(f, "<built-in>"),
+        }
+    }
+}
+
+/// The rule that decided whether a path is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IgnoreMatch {
+    /// The pattern as written, including a leading `!` for negations
+    pub pattern: String,
+    pub source: RuleSource,
+    /// 1-based line in the source file; 0 for built-in rules
+    pub line: usize,
+    /// False when a negation pattern re-included the path
+    pub ignored: bool,
+    /// The path the rule matched: the path itself or an ignored parent
+    /// directory, which ignores everything below it
+    pub matched_path: String,
+}
+
+/// Compiled patterns of one ignore file, with the line of each pattern.
+struct RuleSet {
+    matcher: Gitignore,
+    lines: HashMap<String, usize>,
+    source: RuleSource,
+}
+
+impl RuleSet {
+    fn parse(root: &Path, content: &str, from: &Path, source: RuleSource) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        let mut lines = HashMap::new();
+        
+        for (number, line) in content.lines().enumerate() {
+            match builder.add_line(Some(from.to_path_buf()), line) {
+                // Later lines win, as they do when matching
+                Ok(_) => {
+                    lines.insert(line.trim_end().to_string(), number + 1);
+                }
+                Err(e) => warn!("Ignoring line {} of {}: {}", number + 1, from.display(), e),
+            }
+        }
+        
+        let matcher = builder.build().unwrap_or_else(|e| {
+            warn!("Ignoring {}: {}", from.display(), e);
+            Gitignore::empty()
+        });
+        
+        Self { matcher, lines, source }
+    }
+    
+    fn load(root: &Path, path: &Path, source: RuleSource) -> Option<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Some(Self::parse(root, &content, path, source)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("Cannot read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+    
+    /// Matches `path`, given relative to the directory the rules are for.
+    fn matched(&self, path: &str, is_dir: bool, matched_path: &str) -> Option<IgnoreMatch> {
+        let (glob, ignored) = match self.matcher.matched(path, is_dir) {
+            Match::None => return None,
+            Match::Ignore(glob) => (glob, true),
+            Match::Whitelist(glob) => (glob, false),
+        };
+        
+        Some(IgnoreMatch {
+            pattern: glob.original().to_string(),
+            source: self.source.clone(),
+            line: self.lines.get(glob.original()).copied().unwrap_or(0),
+            ignored,
+            matched_path: matched_path.to_string(),
+        })
+    }
+}
+
+/// Ignore rules of a working tree, with gitignore semantics.
+///
+/// Rules come from `.flowignore` files in any directory, the user's global
+/// ignore file and a few built-in defaults, in that order of precedence;
+/// within a directory the deepest `.flowignore` wins, and within a file the
+/// last matching line. `!pattern` re-includes a path, except below an
+/// ignored directory. `.flowversion` is always ignored.
+pub struct IgnoreRules {
+    root: PathBuf,
+    global: Option<RuleSet>,
+    defaults: RuleSet,
+    /// `.flowignore` of each directory looked at so far, by relative path
+    directories: Mutex<HashMap<String, Option<Arc<RuleSet>>>>,
+}
+
+impl IgnoreRules {
+    /// Rules for the working tree at `root`, with the global ignore file
+    /// from the user configuration directory.
+    pub fn load(root: &Path) -> Result<Self> {
+        let global = crate::utils::fs::user_config_dir().map(|dir| dir.join(GLOBAL_IGNORE_FILE));
+        Ok(Self::with_global_file(root, global.as_deref()))
+    }
+    
+    pub fn with_global_file(root: &Path, global: Option<&Path>) -> Self {
+        let mut defaults = RuleSet::parse(root, &DEFAULT_PATTERNS.join("\n"), Path::new("<built-in>"), RuleSource::BuiltIn);
+        defaults.lines.clear();
+        let global = global.and_then(|path| RuleSet::load(root, path, RuleSource::Global(path.to_path_buf())));
+        
+        Self {
+            root: root.to_path_buf(),
+            global,
+            defaults,
+            directories: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Whether a path relative to the root is ignored, itself or through
+    /// one of its parent directories.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.explain(path, is_dir).is_some_and(|rule| rule.ignored)
+    }
+    
+    /// The rule deciding a path relative to the root: the first ignored
+    /// parent directory, or else the last rule matching the path itself
+    /// (which may be a negation). `None` when no rule matches.
+    pub fn explain(&self, path: &str, is_dir: bool) -> Option<IgnoreMatch> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        
+        for depth in 1..components.len() {
+            let parent = components[..depth].join("/");
+            if let Some(rule) = self.matched(&parent, true).filter(|rule| rule.ignored) {
+                return Some(rule);
+            }
+        }
+        
+        self.matched(&components.join("/"), is_dir)
+    }
+    
+    /// The rule deciding a path on its own, without looking at parent
+    /// directories; enough when walking the tree top-down and skipping
+    /// ignored directories.
+    pub fn matched(&self, path: &str, is_dir: bool) -> Option<IgnoreMatch> {
+        if path == REPOSITORY_DIR || path.starts_with(&format!("{}/", REPOSITORY_DIR)) {
+            return Some(IgnoreMatch {
+                pattern: format!("{}/", REPOSITORY_DIR),
+                source: RuleSource::BuiltIn,
+                line: 0,
+                ignored: true,
+                matched_path: path.to_string(),
+            });
+        }
+        
+        // Deepest directory first
+        let mut dir = path;
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            dir = parent;
+            if let Some(rule) = self.directory_rules(dir).and_then(|rules| rules.matched(&path[dir.len() + 1..], is_dir, path)) {
+                return Some(rule);
+            }
+        }
+        
+        self.directory_rules("")
+            .and_then(|rules| rules.matched(path, is_dir, path))
+            .or_else(|| self.global.as_ref().and_then(|rules| rules.matched(path, is_dir, path)))
+            .or_else(|| self.defaults.matched(path, is_dir, path))
+    }
+    
+    fn directory_rules(&self, dir: &str) -> Option<Arc<RuleSet>> {
+        let mut directories = self.directories.lock().unwrap_or_else(|e| e.into_inner());
+        
+        directories
+            .entry(dir.to_string())
+            .or_insert_with(|| {
+                let dir_path = self.root.join(dir);
+                let relative = Path::new(dir).join(IGNORE_FILE);
+                RuleSet::load(&dir_path, &dir_path.join(IGNORE_FILE), RuleSource::File(relative)).map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_patterns_and_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src/generated")).unwrap();
+        std::fs::write(root.join(".flowignore"), "# build output\n*.log\n!keep.log\n/build/\n").unwrap();
+        std::fs::write(root.join("src/.flowignore"), "generated/\n!debug.log\n").unwrap();
+        let global = root.join("global-ignore");
+        std::fs::write(&global, "*.swp\n").unwrap();
+        
+        let rules = IgnoreRules::with_global_file(root, Some(&global));
+        
+        assert!(rules.is_ignored("server.log", false));
+        assert!(!rules.is_ignored("keep.log", false));
+        assert!(rules.is_ignored("build", true));
+        assert!(rules.is_ignored("build/app", false));
+        assert!(!rules.is_ignored("src/build", true));
+        assert!(rules.is_ignored("notes.swp", false));
+        assert!(rules.is_ignored(".flowversion/HEAD", false));
+        
+        // Deeper files take precedence, and negations re-include
+        assert!(!rules.is_ignored("src/debug.log", false));
+        assert!(rules.is_ignored("src/trace.log", false));
+        
+        // Nothing below an ignored directory can be re-included
+        let rule = rules.explain("src/generated/keep.log", false).unwrap();
+        assert!(rule.ignored);
+        assert_eq!(rule.matched_path, "src/generated");
+        assert_eq!(rule.source, RuleSource::File(PathBuf::from("src/.flowignore")));
+        assert_eq!((rule.pattern.as_str(), rule.line), ("generated/", 1));
+        
+        let rule = rules.explain("keep.log", false).unwrap();
+        assert!(!rule.ignored);
+        assert_eq!((rule.pattern.as_str(), rule.line), ("!keep.log", 3));
+        assert_eq!(rules.explain("notes.swp", false).unwrap().source, RuleSource::Global(global));
+        assert_eq!(rules.explain("src/main.rs", false), None);
+    }
+    
+    #[test]
+    fn test_defaults_match_whole_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let rules = IgnoreRules::with_global_file(temp_dir.path(), None);
+        
+        assert!(rules.is_ignored("target/debug/app", false));
+        assert!(rules.is_ignored("web/node_modules/react/index.js", false));
+        assert!(rules.is_ignored(".git/config", false));
+        assert_eq!(rules.explain("target/debug/app", false).unwrap().line, 0);
+        
+        // Substrings of the defaults are not ignored
+        assert!(!rules.is_ignored("src/targets.rs", false));
+        assert!(!rules.is_ignored("docs/.tmp-notes.md", false));
+        assert!(!rules.is_ignored(".flowversion-backup/readme", false));
+        
+        // A .flowignore can re-include a default
+        std::fs::write(temp_dir.path().join(".flowignore"), "!target/\n").unwrap();
+        let rules = IgnoreRules::with_global_file(temp_dir.path(), None);
+        assert!(!rules.is_ignored("target/debug/app", false));
+    }
+}
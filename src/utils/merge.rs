@@ -0,0 +1,135 @@
+use crate::utils::diff::match_lines;
+
+/// Outcome of a line-level three-way merge.
+pub struct MergeResult {
+    pub content: String,
+    pub conflicts: usize,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// Three-way merges `ours` and `theirs` against their common `base`
+/// (diff3-style). Regions changed on only one side are taken from that
+/// side; regions changed differently on both sides are wrapped in
+/// conflict markers labelled with `ours_label` / `theirs_label`.
+pub fn merge3(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> MergeResult {
+    let base_lines: Vec<String> = base.lines().map(|l| l.to_string()).collect();
+    let ours_lines: Vec<String> = ours.lines().map(|l| l.to_string()).collect();
+    let theirs_lines: Vec<String> = theirs.lines().map(|l| l.to_string()).collect();
+    
+    let ours_match = match_lines(&base_lines, &ours_lines);
+    let theirs_match = match_lines(&base_lines, &theirs_lines);
+    
+    let mut output: Vec<String> = Vec::new();
+    let mut conflicts = 0;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    
+    while b < base_lines.len() || o < ours_lines.len() || t < theirs_lines.len() {
+        // Stable line: unchanged on both sides
+        if b < base_lines.len() && ours_match[b] == Some(o) && theirs_match[b] == Some(t) {
+            output.push(base_lines[b].clone());
+            b += 1;
+            o += 1;
+            t += 1;
+            continue;
+        }
+        
+        // The unstable chunk runs up to the next base line kept by both sides
+        let next_stable = (b..base_lines.len()).find(|&i| {
+            matches!((ours_match[i], theirs_match[i]), (Some(x), Some(y)) if x >= o && y >= t)
+        });
+        
+        let (b_end, o_end, t_end) = match next_stable {
+            Some(i) => (i, ours_match[i].unwrap(), theirs_match[i].unwrap()),
+            None => (base_lines.len(), ours_lines.len(), theirs_lines.len()),
+        };
+        
+        let base_chunk = &base_lines[b..b_end];
+        let ours_chunk = &ours_lines[o..o_end];
+        let theirs_chunk = &theirs_lines[t..t_end];
+        
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            output.extend_from_slice(theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            output.extend_from_slice(ours_chunk);
+        } else {
+            conflicts += 1;
+            output.push(format!("<<<<<<< {}", ours_label));
+            output.extend_from_slice(ours_chunk);
+            output.push("=======".to_string());
+            output.extend_from_slice(theirs_chunk);
+            output.push(format!(">>>>>>> {}", theirs_label));
+        }
+        
+        b = b_end;
+        o = o_end;
+        t = t_end;
+    }
+    
+    let mut content = output.join("\n");
+    let trailing_newline = [base, ours, theirs].iter().any(|text| text.ends_with('\n'));
+    if trailing_newline && !content.is_empty() {
+        content.push('\n');
+    }
+    
+    MergeResult { content, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_merge_non_overlapping_changes() {
+        let base = "one\ntwo\nthree\nfour\nfive\n";
+        let ours = "ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs = "one\ntwo\nthree\nfour\nFIVE\nsix\n";
+        
+        let result = merge3(base, ours, theirs, "main", "feature");
+        
+        assert!(result.is_clean());
+        assert_eq!(result.content, "ONE\ntwo\nthree\nfour\nFIVE\nsix\n");
+    }
+    
+    #[test]
+    fn test_merge_identical_changes() {
+        let base = "a\nb\n";
+        let changed = "a\nB\n";
+        
+        let result = merge3(base, changed, changed, "main", "feature");
+        
+        assert!(result.is_clean());
+        assert_eq!(result.content, changed);
+    }
+    
+    #[test]
+    fn test_merge_conflict_markers() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+        
+        let result = merge3(base, ours, theirs, "main", "feature");
+        
+        assert_eq!(result.conflicts, 1);
+        assert_eq!(
+            result.content,
+            "a\n<<<<<<< main\nours\n=======\ntheirs\n>>>>>>> feature\nc\n"
+        );
+    }
+    
+    #[test]
+    fn test_merge_insertions_at_both_ends() {
+        let base = "middle\n";
+        let ours = "top\nmiddle\n";
+        let theirs = "middle\nbottom\n";
+        
+        let result = merge3(base, ours, theirs, "main", "feature");
+        
+        assert!(result.is_clean());
+        assert_eq!(result.content, "top\nmiddle\nbottom\n");
+    }
+}
@@ -1,3 +1,5 @@
-pub mod hash;
 pub mod diff;
-pub mod fs;
\ No newline at end of file
+pub mod fs;
+pub mod merge;
+pub mod delta;
+pub mod ignore_rules;
\ No newline at end of file